@@ -2,16 +2,21 @@
 
 use std::{
     ffi::{c_void, CString},
+    future::{Future, IntoFuture},
     mem::size_of,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{self, Poll, Waker},
+    time::Duration,
     usize, vec,
 };
 
 use crate::Error;
 
-use super::{ffi::*, OCLErrorKind};
+use super::{ffi::*, BuildError, OCLErrorKind};
 
-#[derive(Clone, Copy, Debug)]
-pub struct Platform(cl_platform_id);
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Platform(pub cl_platform_id);
 
 impl Platform {
     pub fn as_ptr(self) -> *mut cl_platform_id {
@@ -20,6 +25,10 @@ impl Platform {
 }
 
 pub fn get_platforms() -> Result<Vec<Platform>, Error> {
+    // reports a missing OpenCL library instead of panicking on the first call
+    #[cfg(any(test, feature = "dynamic"))]
+    super::load_default_library()?;
+
     let mut platforms: cl_uint = 0;
     let value = unsafe { clGetPlatformIDs(0, std::ptr::null_mut(), &mut platforms) };
 
@@ -47,36 +56,103 @@ pub fn get_platforms() -> Result<Vec<Platform>, Error> {
     Ok(platforms_vec)
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum PlatformInfo {
-    PlatformName = 0x0903,
+    Profile = 0x0900,
+    Version = 0x0901,
+    Name = 0x0902,
+    Vendor = 0x0903,
+    Extensions = 0x0904,
+    HostTimerResolution = 0x0905,
+}
+
+impl Platform {
+    pub fn profile(self) -> Result<String, Error> {
+        get_platform_info(self, PlatformInfo::Profile)
+    }
+    pub fn version(self) -> Result<String, Error> {
+        get_platform_info(self, PlatformInfo::Version)
+    }
+    pub fn name(self) -> Result<String, Error> {
+        get_platform_info(self, PlatformInfo::Name)
+    }
+    pub fn vendor(self) -> Result<String, Error> {
+        get_platform_info(self, PlatformInfo::Vendor)
+    }
+    pub fn extensions(self) -> Result<Vec<String>, Error> {
+        Ok(get_platform_info(self, PlatformInfo::Extensions)?
+            .split_whitespace()
+            .map(String::from)
+            .collect())
+    }
+    /// Returns the resolution of the host timer in nanoseconds. Requires OpenCL 2.1.
+    pub fn host_timer_resolution(self) -> Result<u64, Error> {
+        let data = get_platform_info_raw(self, PlatformInfo::HostTimerResolution)?;
+        Ok(DeviceReturnInfo::new(data).size)
+    }
+    /// Returns all devices of the platform that match the `DeviceType` bitfield.
+    pub fn devices(self, device_type: u64) -> Result<Vec<CLIntDevice>, Error> {
+        get_device_ids(self, &device_type)
+    }
+    /// Checks whether `pattern` is contained in the name or vendor of the platform, ignoring case.
+    pub fn matches(self, pattern: &str) -> Result<bool, Error> {
+        let pattern = pattern.to_lowercase();
+        Ok(self.name()?.to_lowercase().contains(&pattern)
+            || self.vendor()?.to_lowercase().contains(&pattern))
+    }
+}
+
+/// Returns the first platform whose name or vendor contains `pattern`, ignoring case.
+pub fn find_platform(pattern: &str) -> Result<Platform, Error> {
+    for platform in get_platforms()? {
+        if platform.matches(pattern)? {
+            return Ok(platform);
+        }
+    }
+    Err(OCLErrorKind::PlatformNotFound.into())
+}
+
+/// Returns a string platform info parameter without the terminating nul.
+pub fn get_platform_info(platform: Platform, param_name: PlatformInfo) -> Result<String, Error> {
+    let mut param_value = get_platform_info_raw(platform, param_name)?;
+    if param_value.last() == Some(&0) {
+        param_value.pop();
+    }
+    Ok(String::from_utf8_lossy(&param_value).into_owned())
 }
-pub fn get_platform_info(platform: Platform, param_name: PlatformInfo) -> String {
+
+pub fn get_platform_info_raw(
+    platform: Platform,
+    param_name: PlatformInfo,
+) -> Result<Vec<u8>, Error> {
     let mut size: size_t = 0;
-    unsafe {
+    let value = unsafe {
         clGetPlatformInfo(
             platform.0,
             param_name as cl_platform_info,
             0,
             std::ptr::null_mut(),
             &mut size,
-        );
+        )
     };
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
 
-    let mut param_value = vec![32u8; size];
-
-    unsafe {
+    let mut param_value = vec![0u8; size];
+    let value = unsafe {
         clGetPlatformInfo(
             platform.0,
             param_name as cl_platform_info,
             size,
             param_value.as_mut_ptr() as *mut c_void,
             std::ptr::null_mut(),
-        );
+        )
     };
-
-    println!("param value: {:?}", param_value);
-    String::from_utf8_lossy(&param_value).to_string()
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(param_value)
 }
 
 pub enum DeviceType {
@@ -87,17 +163,132 @@ pub enum DeviceType {
     //ALL =         0xFFFFFFFF
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum DeviceInfo {
+    Type = 0x1000,
+    VendorId = 0x1001,
+    MaxComputeUnits = 0x1002,
+    MaxWorkItemDimensions = 0x1003,
+    MaxWorkGroupSize = 0x1004,
+    MaxWorkItemSizes = 0x1005,
+    PreferredVectorWidthChar = 0x1006,
+    PreferredVectorWidthShort = 0x1007,
+    PreferredVectorWidthInt = 0x1008,
+    PreferredVectorWidthLong = 0x1009,
+    PreferredVectorWidthFloat = 0x100A,
+    PreferredVectorWidthDouble = 0x100B,
+    MaxClockFrequency = 0x100C,
+    AddressBits = 0x100D,
+    MaxReadImageArgs = 0x100E,
+    MaxWriteImageArgs = 0x100F,
     MaxMemAllocSize = 0x1010,
+    Image2dMaxWidth = 0x1011,
+    Image2dMaxHeight = 0x1012,
+    Image3dMaxWidth = 0x1013,
+    Image3dMaxHeight = 0x1014,
+    Image3dMaxDepth = 0x1015,
+    ImageSupport = 0x1016,
+    MaxParameterSize = 0x1017,
+    MaxSamplers = 0x1018,
+    MemBaseAddrAlign = 0x1019,
+    SingleFpConfig = 0x101B,
+    GlobalMemCacheType = 0x101C,
+    GlobalMemCachelineSize = 0x101D,
+    GlobalMemCacheSize = 0x101E,
     GlobalMemSize = 0x101F,
+    MaxConstantBufferSize = 0x1020,
+    MaxConstantArgs = 0x1021,
+    LocalMemType = 0x1022,
+    LocalMemSize = 0x1023,
+    ErrorCorrectionSupport = 0x1024,
+    ProfilingTimerResolution = 0x1025,
+    EndianLittle = 0x1026,
+    Available = 0x1027,
+    CompilerAvailable = 0x1028,
+    ExecutionCapabilities = 0x1029,
+    QueueProperties = 0x102A,
     NAME = 0x102B,
+    Vendor = 0x102C,
+    DriverVersion = 0x102D,
+    Profile = 0x102E,
     VERSION = 0x102F,
+    Extensions = 0x1030,
+    Platform = 0x1031,
+    DoubleFpConfig = 0x1032,
+    HalfFpConfig = 0x1033,
+    PreferredVectorWidthHalf = 0x1034,
     HostUnifiedMemory = 0x1035,
+    NativeVectorWidthChar = 0x1036,
+    NativeVectorWidthShort = 0x1037,
+    NativeVectorWidthInt = 0x1038,
+    NativeVectorWidthLong = 0x1039,
+    NativeVectorWidthFloat = 0x103A,
+    NativeVectorWidthDouble = 0x103B,
+    NativeVectorWidthHalf = 0x103C,
+    OpenCLCVersion = 0x103D,
+    LinkerAvailable = 0x103E,
+    BuiltInKernels = 0x103F,
+    ImageMaxBufferSize = 0x1040,
+    ImageMaxArraySize = 0x1041,
+    PrintfBufferSize = 0x1049,
+    /// OpenCL 2.0
+    SvmCapabilities = 0x1053,
+}
+
+/// Bits of the floating point configuration returned by `DeviceInfo::SingleFpConfig`, `DoubleFpConfig` and `HalfFpConfig`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FpConfig {
+    Denorm = 1 << 0,
+    InfNan = 1 << 1,
+    RoundToNearest = 1 << 2,
+    RoundToZero = 1 << 3,
+    RoundToInf = 1 << 4,
+    Fma = 1 << 5,
+    SoftFloat = 1 << 6,
+    CorrectlyRoundedDivideSqrt = 1 << 7,
+}
+
+impl FpConfig {
+    #[inline]
+    pub fn is_set(self, config: u64) -> bool {
+        config & self as u64 != 0
+    }
+}
+
+/// Bits of the shared virtual memory capabilities returned by `DeviceInfo::SvmCapabilities`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SvmCapability {
+    /// `clSVMAlloc` allocations that are shared at map and unmap points.
+    CoarseGrainBuffer = 1 << 0,
+    /// `clSVMAlloc` allocations that the host can access while kernels run.
+    FineGrainBuffer = 1 << 1,
+    /// Any host allocation can be passed to kernels.
+    FineGrainSystem = 1 << 2,
+    /// Atomics on fine-grain allocations are visible to host and device.
+    Atomics = 1 << 3,
+}
+
+impl SvmCapability {
+    #[inline]
+    pub fn is_set(self, capabilities: u64) -> bool {
+        capabilities & self as u64 != 0
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LocalMemType {
+    None = 0,
+    Local = 1,
+    Global = 2,
 }
+
 #[derive(Clone, Copy, Debug, Hash)]
 pub struct CLIntDevice(pub cl_device_id);
 
+// Device ids are immutable handles and OpenCL API calls on them are thread-safe.
+unsafe impl Send for CLIntDevice {}
+unsafe impl Sync for CLIntDevice {}
+
 impl CLIntDevice {
     pub fn get_name(self) -> Result<String, Error> {
         Ok(get_device_info(self, DeviceInfo::NAME)?.string)
@@ -105,12 +296,132 @@ impl CLIntDevice {
     pub fn get_version(self) -> Result<String, Error> {
         Ok(get_device_info(self, DeviceInfo::VERSION)?.string)
     }
+    pub fn get_driver_version(self) -> Result<String, Error> {
+        Ok(get_device_info(self, DeviceInfo::DriverVersion)?.string)
+    }
+    pub fn get_vendor(self) -> Result<String, Error> {
+        Ok(get_device_info(self, DeviceInfo::Vendor)?.string)
+    }
+    pub fn get_vendor_id(self) -> Result<u32, Error> {
+        Ok(get_device_info(self, DeviceInfo::VendorId)?.size as u32)
+    }
+    pub fn get_profile(self) -> Result<String, Error> {
+        Ok(get_device_info(self, DeviceInfo::Profile)?.string)
+    }
+    pub fn get_opencl_c_version(self) -> Result<String, Error> {
+        Ok(get_device_info(self, DeviceInfo::OpenCLCVersion)?.string)
+    }
+    pub fn get_extensions(self) -> Result<Vec<String>, Error> {
+        Ok(get_device_info(self, DeviceInfo::Extensions)?
+            .string
+            .split_whitespace()
+            .map(String::from)
+            .collect())
+    }
+    pub fn has_extension(self, extension: &str) -> Result<bool, Error> {
+        Ok(self.get_extensions()?.iter().any(|ext| ext == extension))
+    }
+    pub fn get_platform(self) -> Result<Platform, Error> {
+        let ptr = get_device_info(self, DeviceInfo::Platform)?.size;
+        Ok(Platform(ptr as usize as cl_platform_id))
+    }
+    /// Returns the `DeviceType` bitfield of the device.
+    pub fn get_device_type(self) -> Result<u64, Error> {
+        Ok(get_device_info(self, DeviceInfo::Type)?.size)
+    }
     pub fn get_global_mem(self) -> Result<u64, Error> {
         Ok(get_device_info(self, DeviceInfo::GlobalMemSize)?.size)
     }
     pub fn get_max_mem_alloc(self) -> Result<u64, Error> {
         Ok(get_device_info(self, DeviceInfo::MaxMemAllocSize)?.size)
     }
+    pub fn get_global_mem_cache_size(self) -> Result<u64, Error> {
+        Ok(get_device_info(self, DeviceInfo::GlobalMemCacheSize)?.size)
+    }
+    pub fn get_global_mem_cacheline_size(self) -> Result<u32, Error> {
+        Ok(get_device_info(self, DeviceInfo::GlobalMemCachelineSize)?.size as u32)
+    }
+    pub fn get_max_constant_buffer_size(self) -> Result<u64, Error> {
+        Ok(get_device_info(self, DeviceInfo::MaxConstantBufferSize)?.size)
+    }
+    pub fn get_local_mem_size(self) -> Result<u64, Error> {
+        Ok(get_device_info(self, DeviceInfo::LocalMemSize)?.size)
+    }
+    pub fn get_local_mem_type(self) -> Result<LocalMemType, Error> {
+        match get_device_info(self, DeviceInfo::LocalMemType)?.size {
+            1 => Ok(LocalMemType::Local),
+            2 => Ok(LocalMemType::Global),
+            _ => Ok(LocalMemType::None),
+        }
+    }
+    pub fn get_max_compute_units(self) -> Result<u32, Error> {
+        Ok(get_device_info(self, DeviceInfo::MaxComputeUnits)?.size as u32)
+    }
+    /// Returns the maximum clock frequency in MHz.
+    pub fn get_max_clock_frequency(self) -> Result<u32, Error> {
+        Ok(get_device_info(self, DeviceInfo::MaxClockFrequency)?.size as u32)
+    }
+    pub fn get_max_work_group_size(self) -> Result<usize, Error> {
+        Ok(get_device_info(self, DeviceInfo::MaxWorkGroupSize)?.size as usize)
+    }
+    /// Returns the maximum number of work items per work group for each dimension.
+    pub fn get_max_work_item_sizes(self) -> Result<Vec<usize>, Error> {
+        Ok(get_device_info(self, DeviceInfo::MaxWorkItemSizes)?.usizes())
+    }
+    pub fn get_address_bits(self) -> Result<u32, Error> {
+        Ok(get_device_info(self, DeviceInfo::AddressBits)?.size as u32)
+    }
+    pub fn get_mem_base_addr_align(self) -> Result<u32, Error> {
+        Ok(get_device_info(self, DeviceInfo::MemBaseAddrAlign)?.size as u32)
+    }
+    /// Returns the resolution of the device timer in nanoseconds.
+    pub fn get_profiling_timer_resolution(self) -> Result<usize, Error> {
+        Ok(get_device_info(self, DeviceInfo::ProfilingTimerResolution)?.size as usize)
+    }
+    pub fn image_support(self) -> Result<bool, Error> {
+        Ok(get_device_info(self, DeviceInfo::ImageSupport)?.size != 0)
+    }
+    /// Returns the maximum width and height of 2D images in pixels.
+    pub fn get_image2d_max_size(self) -> Result<[usize; 2], Error> {
+        Ok([
+            get_device_info(self, DeviceInfo::Image2dMaxWidth)?.size as usize,
+            get_device_info(self, DeviceInfo::Image2dMaxHeight)?.size as usize,
+        ])
+    }
+    /// Returns the maximum width, height and depth of 3D images in pixels.
+    pub fn get_image3d_max_size(self) -> Result<[usize; 3], Error> {
+        Ok([
+            get_device_info(self, DeviceInfo::Image3dMaxWidth)?.size as usize,
+            get_device_info(self, DeviceInfo::Image3dMaxHeight)?.size as usize,
+            get_device_info(self, DeviceInfo::Image3dMaxDepth)?.size as usize,
+        ])
+    }
+    pub fn get_image_max_array_size(self) -> Result<usize, Error> {
+        Ok(get_device_info(self, DeviceInfo::ImageMaxArraySize)?.size as usize)
+    }
+    pub fn endian_little(self) -> Result<bool, Error> {
+        Ok(get_device_info(self, DeviceInfo::EndianLittle)?.size != 0)
+    }
+    /// Returns the `FpConfig` bitfield for single precision floats.
+    pub fn get_single_fp_config(self) -> Result<u64, Error> {
+        Ok(get_device_info(self, DeviceInfo::SingleFpConfig)?.size)
+    }
+    /// Returns the `FpConfig` bitfield for double precision floats. `0` if doubles are not supported.
+    pub fn get_double_fp_config(self) -> Result<u64, Error> {
+        Ok(get_device_info(self, DeviceInfo::DoubleFpConfig)?.size)
+    }
+    /// Returns the `FpConfig` bitfield for half precision floats. `0` if halfs are not supported.
+    pub fn get_half_fp_config(self) -> Result<u64, Error> {
+        Ok(get_device_info(self, DeviceInfo::HalfFpConfig)?.size)
+    }
+    /// Returns the `SvmCapability` bitfield. Requires OpenCL 2.0.
+    pub fn get_svm_capabilities(self) -> Result<u64, Error> {
+        Ok(get_device_info(self, DeviceInfo::SvmCapabilities)?.size)
+    }
+    /// Returns the `CommandQueueProperties` bitfield supported by the device.
+    pub fn get_queue_properties(self) -> Result<u64, Error> {
+        Ok(get_device_info(self, DeviceInfo::QueueProperties)?.size)
+    }
     pub fn unified_mem(self) -> Result<bool, Error> {
         Ok(get_device_info(self, DeviceInfo::HostUnifiedMemory)?.size != 0)
     }
@@ -142,7 +453,7 @@ pub fn get_device_ids(platform: Platform, device_type: &u64) -> Result<Vec<CLInt
     let value = unsafe {
         clGetDeviceIDs(
             platform.0,
-            DeviceType::GPU as u64,
+            *device_type,
             num_devices,
             devices.as_mut_ptr() as *mut cl_device_id,
             std::ptr::null_mut(),
@@ -154,12 +465,39 @@ pub fn get_device_ids(platform: Platform, device_type: &u64) -> Result<Vec<CLInt
     Ok(devices)
 }
 
+/// Raw value of a device info parameter.
+/// `string` omits the terminating nul; `size` is the native-endian integer value of parameters with 1, 2, 4 or 8 bytes.
 pub struct DeviceReturnInfo {
     pub string: String,
     pub size: u64,
     pub data: Vec<u8>,
 }
 
+impl DeviceReturnInfo {
+    pub fn new(data: Vec<u8>) -> DeviceReturnInfo {
+        let string = match data.split_last() {
+            Some((0, string)) => String::from_utf8_lossy(string).into_owned(),
+            _ => String::from_utf8_lossy(&data).into_owned(),
+        };
+        let size = match data.len() {
+            1 => data[0] as u64,
+            2 => u16::from_ne_bytes([data[0], data[1]]) as u64,
+            4 => u32::from_ne_bytes(data[..4].try_into().unwrap()) as u64,
+            8 => u64::from_ne_bytes(data[..8].try_into().unwrap()),
+            _ => 0,
+        };
+        DeviceReturnInfo { string, size, data }
+    }
+
+    /// Decodes the value as an array of `size_t`.
+    pub fn usizes(&self) -> Vec<usize> {
+        self.data
+            .chunks_exact(std::mem::size_of::<usize>())
+            .map(|chunk| usize::from_ne_bytes(chunk.try_into().unwrap()))
+            .collect()
+    }
+}
+
 pub fn get_device_info(
     device: CLIntDevice,
     param_name: DeviceInfo,
@@ -190,14 +528,7 @@ pub fn get_device_info(
     if value != 0 {
         return Err(Error::from(OCLErrorKind::from_value(value)));
     }
-    let string = String::from_utf8_lossy(&param_value).to_string();
-    let size = param_value.iter().fold(0, |x, &i| x << 4 | i as u64);
-
-    Ok(DeviceReturnInfo {
-        string,
-        size,
-        data: param_value,
-    })
+    Ok(DeviceReturnInfo::new(param_value))
 }
 
 #[derive(Debug, Hash)]
@@ -209,6 +540,26 @@ impl Drop for Context {
     }
 }
 
+unsafe impl Send for Context {}
+unsafe impl Sync for Context {}
+
+impl Clone for Context {
+    fn clone(&self) -> Self {
+        unsafe { retain_context(self).unwrap() };
+        Context(self.0)
+    }
+}
+
+/// # Safety
+/// valid context
+pub unsafe fn retain_context(context: &Context) -> Result<(), Error> {
+    let value = clRetainContext(context.0);
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(())
+}
+
 pub fn create_context(devices: &[CLIntDevice]) -> Result<Context, Error> {
     let mut err = 0;
     let r = unsafe {
@@ -227,9 +578,30 @@ pub fn create_context(devices: &[CLIntDevice]) -> Result<Context, Error> {
     Ok(Context(r))
 }
 
-#[derive(Debug /* remove: */, Clone)]
+#[derive(Debug)]
 pub struct CommandQueue(pub cl_command_queue);
 
+// Enqueueing commands from several host threads is thread-safe since OpenCL 1.1.
+unsafe impl Send for CommandQueue {}
+unsafe impl Sync for CommandQueue {}
+
+impl Clone for CommandQueue {
+    fn clone(&self) -> Self {
+        unsafe { retain_command_queue(self).unwrap() };
+        CommandQueue(self.0)
+    }
+}
+
+/// # Safety
+/// valid command queue
+pub unsafe fn retain_command_queue(cq: &CommandQueue) -> Result<(), Error> {
+    let err = clRetainCommandQueue(cq.0);
+    if err != 0 {
+        return Err(OCLErrorKind::from_value(err).into());
+    }
+    Ok(())
+}
+
 pub fn release_command_queue(cq: &mut CommandQueue) -> Result<(), Error> {
     let err = unsafe { clReleaseCommandQueue(cq.0) };
     if err != 0 {
@@ -244,9 +616,24 @@ impl Drop for CommandQueue {
     }
 }
 
+pub enum CommandQueueProperties {
+    /// Commands may execute in any order that respects their wait lists.
+    OutOfOrderExecModeEnable = 1 << 0,
+    ProfilingEnable = 1 << 1,
+}
+
 pub fn create_command_queue(context: &Context, device: CLIntDevice) -> Result<CommandQueue, Error> {
+    create_command_queue_with_properties(context, device, 0)
+}
+
+/// Creates a command queue with the given `CommandQueueProperties` (combined as bitfield).
+pub fn create_command_queue_with_properties(
+    context: &Context,
+    device: CLIntDevice,
+    properties: u64,
+) -> Result<CommandQueue, Error> {
     let mut err = 0;
-    let r = unsafe { clCreateCommandQueue(context.0, device.0, 0, &mut err) };
+    let r = unsafe { clCreateCommandQueue(context.0, device.0, properties, &mut err) };
 
     if err != 0 {
         return Err(Error::from(OCLErrorKind::from_value(err)));
@@ -254,8 +641,21 @@ pub fn create_command_queue(context: &Context, device: CLIntDevice) -> Result<Co
     Ok(CommandQueue(r))
 }
 
-pub fn finish(cq: CommandQueue) {
-    unsafe { clFinish(cq.0) };
+/// Submits the enqueued commands of the queue to the device without waiting for them.
+pub fn flush(cq: &CommandQueue) -> Result<(), Error> {
+    let err = unsafe { clFlush(cq.0) };
+    if err != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(err)));
+    }
+    Ok(())
+}
+
+pub fn finish(cq: &CommandQueue) -> Result<(), Error> {
+    let err = unsafe { clFinish(cq.0) };
+    if err != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(err)));
+    }
+    Ok(())
 }
 
 #[derive(Debug)]
@@ -266,8 +666,277 @@ impl Event {
     pub fn wait(self) -> Result<(), Error> {
         unsafe { wait_for_event(self) }
     }
+
+    /// Returns the raw handle of the command queue the command of this event was enqueued to.
+    pub fn command_queue(&self) -> Result<cl_command_queue, Error> {
+        let mut queue: cl_command_queue = std::ptr::null_mut();
+        let err = unsafe {
+            clGetEventInfo(
+                self.0,
+                CL_EVENT_COMMAND_QUEUE,
+                size_of::<cl_command_queue>(),
+                &mut queue as *mut cl_command_queue as *mut c_void,
+                std::ptr::null_mut(),
+            )
+        };
+        if err != 0 {
+            return Err(Error::from(OCLErrorKind::from_value(err)));
+        }
+        Ok(queue)
+    }
+
+    /// Flushes the command queue of this event, hence its command is submitted to the device.
+    /// Commands of other queues and callbacks can only wait for a submitted command.
+    pub fn flush_queue(&self) -> Result<(), Error> {
+        let err = unsafe { clFlush(self.command_queue()?) };
+        if err != 0 {
+            return Err(Error::from(OCLErrorKind::from_value(err)));
+        }
+        Ok(())
+    }
+
+    /// Returns the execution status of the command of this event.
+    /// A command that terminated abnormally is reported as error.
+    pub fn status(&self) -> Result<EventStatus, Error> {
+        let mut status: cl_int = 0;
+        let err = unsafe {
+            clGetEventInfo(
+                self.0,
+                CL_EVENT_COMMAND_EXECUTION_STATUS,
+                size_of::<cl_int>(),
+                &mut status as *mut cl_int as *mut c_void,
+                std::ptr::null_mut(),
+            )
+        };
+        if err != 0 {
+            return Err(Error::from(OCLErrorKind::from_value(err)));
+        }
+        Ok(match status {
+            CL_COMPLETE => EventStatus::Complete,
+            CL_RUNNING => EventStatus::Running,
+            CL_SUBMITTED => EventStatus::Submitted,
+            CL_QUEUED => EventStatus::Queued,
+            _ => return Err(Error::from(OCLErrorKind::from_value(status))),
+        })
+    }
+
+    /// Returns the device timestamps of the command of this event.
+    /// Requires a command queue created with `CommandQueueProperties::ProfilingEnable` and a completed command.
+    pub fn profiling(&self) -> Result<EventProfiling, Error> {
+        Ok(EventProfiling {
+            queued: get_event_profiling_info(self, CL_PROFILING_COMMAND_QUEUED)?,
+            submit: get_event_profiling_info(self, CL_PROFILING_COMMAND_SUBMIT)?,
+            start: get_event_profiling_info(self, CL_PROFILING_COMMAND_START)?,
+            end: get_event_profiling_info(self, CL_PROFILING_COMMAND_END)?,
+        })
+    }
+}
+
+/// Execution status of the command of an [`Event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventStatus {
+    /// The command was enqueued by the host.
+    Queued,
+    /// The command was submitted to the device.
+    Submitted,
+    /// The command is executing.
+    Running,
+    /// The command finished executing.
+    Complete,
+}
+
+/// Waits for the command of an [`Event`] without blocking, created by awaiting the event.
+///
+/// The task is woken by a callback that the OpenCL implementation calls on a thread of its own
+/// when the command completes, hence the future works with any executor.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct EventFuture {
+    event: Event,
+    /// `None` until the callback is registered on the first poll
+    completion: Option<Arc<Mutex<Completion>>>,
+}
+
+/// State shared between an [`EventFuture`] and the callback of its event.
+#[derive(Debug, Default)]
+struct Completion {
+    /// The execution status the callback was called with
+    status: Option<cl_int>,
+    /// The waker of the task that last polled the future
+    waker: Option<Waker>,
+}
+
+unsafe extern "system" fn wake_completion(
+    _event: cl_event,
+    event_command_status: cl_int,
+    user_data: *mut c_void,
+) {
+    let completion = unsafe { Arc::from_raw(user_data as *const Mutex<Completion>) };
+    let waker = {
+        let mut completion = completion
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        completion.status = Some(event_command_status);
+        completion.waker.take()
+    };
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+impl EventFuture {
+    /// The event the future waits for.
+    pub fn event(&self) -> &Event {
+        &self.event
+    }
+
+    /// Returns the event, e.g. to block on it with [`Event::wait`].
+    pub fn into_event(self) -> Event {
+        self.event
+    }
+
+    /// Whether the command of the event terminated, successfully or abnormally.
+    /// `false` until the future is polled.
+    pub fn is_terminated(&self) -> bool {
+        self.completion.as_ref().is_some_and(|completion| {
+            let completion = completion
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            completion.status.is_some()
+        })
+    }
+
+    fn register(&mut self) -> Result<(), Error> {
+        // the callback is never called for a command that is not submitted
+        self.event.flush_queue()?;
+        let completion = self.completion.as_ref().unwrap();
+        // the callback owns one reference, which is released when it is called
+        let user_data = Arc::into_raw(completion.clone()) as *mut c_void;
+        let err = unsafe {
+            clSetEventCallback(self.event.0, CL_COMPLETE, Some(wake_completion), user_data)
+        };
+        if err != 0 {
+            drop(unsafe { Arc::from_raw(user_data as *const Mutex<Completion>) });
+            return Err(Error::from(OCLErrorKind::from_value(err)));
+        }
+        Ok(())
+    }
+}
+
+impl Future for EventFuture {
+    /// A command that terminated abnormally is reported as error.
+    type Output = Result<(), Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let register = self.completion.is_none();
+        let completion = self.completion.get_or_insert_with(Default::default).clone();
+        {
+            let mut completion = completion
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            if let Some(status) = completion.status {
+                return Poll::Ready(completion_result(status));
+            }
+            // the waker is stored before registering, as the callback may be called immediately
+            match &mut completion.waker {
+                Some(waker) if waker.will_wake(cx.waker()) => {}
+                waker => *waker = Some(cx.waker().clone()),
+            }
+        }
+        if register {
+            if let Err(err) = self.register() {
+                // polling again retries the registration
+                self.completion = None;
+                return Poll::Ready(Err(err));
+            }
+            // the callback may have been called during the registration
+            let status = completion
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .status;
+            if let Some(status) = status {
+                return Poll::Ready(completion_result(status));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+fn completion_result(status: cl_int) -> Result<(), Error> {
+    match status {
+        CL_COMPLETE => Ok(()),
+        status => Err(Error::from(OCLErrorKind::from_value(status))),
+    }
+}
+
+impl IntoFuture for Event {
+    type Output = Result<(), Error>;
+    type IntoFuture = EventFuture;
+
+    /// Waits for the command of the event without blocking the thread.
+    fn into_future(self) -> EventFuture {
+        EventFuture {
+            event: self,
+            completion: None,
+        }
+    }
+}
+
+/// Device timestamps in nanoseconds of the stages of a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventProfiling {
+    /// The command was enqueued by the host.
+    pub queued: u64,
+    /// The command was submitted to the device.
+    pub submit: u64,
+    /// The command started executing.
+    pub start: u64,
+    /// The command finished executing.
+    pub end: u64,
+}
+
+impl EventProfiling {
+    /// Execution time of the command on the device.
+    pub fn duration(&self) -> Duration {
+        Duration::from_nanos(self.end.saturating_sub(self.start))
+    }
+
+    /// Time between enqueueing the command and the start of its execution.
+    pub fn latency(&self) -> Duration {
+        Duration::from_nanos(self.start.saturating_sub(self.queued))
+    }
+}
+
+pub fn get_event_profiling_info(
+    event: &Event,
+    param_name: cl_profiling_info,
+) -> Result<u64, Error> {
+    let mut value: cl_ulong = 0;
+    let err = unsafe {
+        clGetEventProfilingInfo(
+            event.0,
+            param_name,
+            size_of::<cl_ulong>(),
+            &mut value as *mut cl_ulong as *mut c_void,
+            std::ptr::null_mut(),
+        )
+    };
+    if err != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(err)));
+    }
+    Ok(value)
+}
+
+impl Clone for Event {
+    fn clone(&self) -> Self {
+        unsafe { retain_event(self).unwrap() };
+        Event(self.0)
+    }
 }
 
+unsafe impl Send for Event {}
+unsafe impl Sync for Event {}
+
 impl Drop for Event {
     fn drop(&mut self) {
         unsafe { release_event(self).unwrap() };
@@ -299,6 +968,16 @@ pub unsafe fn wait_for_events(events: &[Event]) -> Result<(), Error> {
     Ok(())
 }
 
+/// # Safety
+/// The event must be valid.
+pub unsafe fn retain_event(event: &Event) -> Result<(), Error> {
+    let value = unsafe { clRetainEvent(event.0) };
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(())
+}
+
 pub unsafe fn release_event(event: &mut Event) -> Result<(), Error> {
     let value = unsafe { clReleaseEvent(event.0) };
     if value != 0 {
@@ -317,6 +996,10 @@ pub enum MemFlags {
     MemHostWriteOnly = 1 << 7,
     MemHostReadOnly = 1 << 8,
     MemHostNoAccess = 1 << 9,
+    /// SVM only, requires `SvmCapability::FineGrainBuffer`
+    MemSvmFineGrainBuffer = 1 << 10,
+    /// SVM only, requires `SvmCapability::Atomics` and `MemSvmFineGrainBuffer`
+    MemSvmAtomics = 1 << 11,
 }
 
 impl core::ops::BitOr for MemFlags {
@@ -372,29 +1055,152 @@ pub unsafe fn retain_mem_object(mem: *mut c_void) -> Result<(), Error> {
     Ok(())
 }
 
+/// Creates a sub-buffer that aliases `len` elements of `mem`, starting at element `offset`.
 /// # Safety
-/// valid mem object
-pub unsafe fn enqueue_write_buffer<T>(
-    cq: &CommandQueue,
+/// valid mem object, which must not be a sub-buffer itself
+pub unsafe fn create_sub_buffer<T>(
     mem: *mut c_void,
-    data: &[T],
-    block: bool,
-    event_wait_list: Option<&[Event]>,
-) -> Result<Event, Error> {
-    let mut events = [std::ptr::null_mut(); 1];
+    flags: u64,
+    offset: usize,
+    len: usize,
+) -> Result<*mut c_void, Error> {
+    let region = cl_buffer_region {
+        origin: offset * size_of::<T>(),
+        size: len * size_of::<T>(),
+    };
+    let mut err = 0;
+    let r = unsafe {
+        clCreateSubBuffer(
+            mem,
+            flags,
+            CL_BUFFER_CREATE_TYPE_REGION,
+            &region as *const cl_buffer_region as *const c_void,
+            &mut err,
+        )
+    };
+    if err != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(err)));
+    }
+    Ok(r)
+}
 
-    let (num_events_in_wait_list, event_wait_list) = extract_event_wait_list(event_wait_list);
+#[derive(Clone, Copy)]
+pub enum MemInfo {
+    Size = 0x1102,
+    Context = 0x1106,
+    AssociatedMemObject = 0x1107,
+    Offset = 0x1108,
+}
 
-    let value = clEnqueueWriteBuffer(
-        cq.0,
-        mem,
-        block as u32,
-        0,
-        data.len() * core::mem::size_of::<T>(),
-        data.as_ptr() as *mut c_void,
-        num_events_in_wait_list,
-        event_wait_list,
-        events.as_mut_ptr() as *mut cl_event,
+/// Returns the raw value of a memory object info parameter.
+/// # Safety
+/// valid mem object
+pub unsafe fn get_mem_object_info(mem: *mut c_void, param_name: MemInfo) -> Result<Vec<u8>, Error> {
+    let mut size: size_t = 0;
+    let value = unsafe {
+        clGetMemObjectInfo(
+            mem,
+            param_name as cl_mem_info,
+            0,
+            std::ptr::null_mut(),
+            &mut size,
+        )
+    };
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+
+    let mut param_value = vec![0u8; size];
+    let value = unsafe {
+        clGetMemObjectInfo(
+            mem,
+            param_name as cl_mem_info,
+            size,
+            param_value.as_mut_ptr() as *mut c_void,
+            std::ptr::null_mut(),
+        )
+    };
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(param_value)
+}
+
+/// Returns the devices of the context the memory object was created in.
+/// # Safety
+/// valid mem object
+pub unsafe fn get_mem_object_devices(mem: *mut c_void) -> Result<Vec<CLIntDevice>, Error> {
+    let context = get_mem_object_info(mem, MemInfo::Context)?;
+    let context = usize::from_ne_bytes(context.try_into().unwrap()) as cl_context;
+    get_context_devices(context)
+}
+
+/// # Safety
+/// valid context
+pub unsafe fn get_context_devices(context: cl_context) -> Result<Vec<CLIntDevice>, Error> {
+    // CL_CONTEXT_DEVICES
+    const DEVICES: cl_context_info = 0x1081;
+
+    let mut size: size_t = 0;
+    let value = unsafe { clGetContextInfo(context, DEVICES, 0, std::ptr::null_mut(), &mut size) };
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+
+    let mut devices = vec![CLIntDevice(std::ptr::null_mut()); size / size_of::<cl_device_id>()];
+    let value = unsafe {
+        clGetContextInfo(
+            context,
+            DEVICES,
+            size,
+            devices.as_mut_ptr() as *mut c_void,
+            std::ptr::null_mut(),
+        )
+    };
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(devices)
+}
+
+/// # Safety
+/// valid mem object
+pub unsafe fn enqueue_write_buffer<T>(
+    cq: &CommandQueue,
+    mem: *mut c_void,
+    data: &[T],
+    block: bool,
+    event_wait_list: Option<&[Event]>,
+) -> Result<Event, Error> {
+    enqueue_write_buffer_at(cq, mem, 0, data, block, event_wait_list)
+}
+
+/// Writes `data` to the buffer, starting at element `offset`.
+/// # Safety
+/// valid mem object; `offset + data.len()` must not exceed the length of the buffer.
+/// If `block` is false, `data` must stay valid until the returned event completes.
+pub unsafe fn enqueue_write_buffer_at<T>(
+    cq: &CommandQueue,
+    mem: *mut c_void,
+    offset: usize,
+    data: &[T],
+    block: bool,
+    event_wait_list: Option<&[Event]>,
+) -> Result<Event, Error> {
+    let mut events = [std::ptr::null_mut(); 1];
+
+    let (num_events_in_wait_list, event_wait_list) = extract_event_wait_list(event_wait_list);
+
+    let value = clEnqueueWriteBuffer(
+        cq.0,
+        mem,
+        block as u32,
+        offset * size_of::<T>(),
+        std::mem::size_of_val(data),
+        data.as_ptr() as *mut c_void,
+        num_events_in_wait_list,
+        event_wait_list,
+        events.as_mut_ptr() as *mut cl_event,
     );
     if value != 0 {
         return Err(Error::from(OCLErrorKind::from_value(value)));
@@ -410,6 +1216,21 @@ pub unsafe fn enqueue_read_buffer<T>(
     data: &mut [T],
     block: bool,
     event_wait_list: Option<&[Event]>,
+) -> Result<Event, Error> {
+    enqueue_read_buffer_at(cq, mem, 0, data, block, event_wait_list)
+}
+
+/// Reads `data.len()` elements of the buffer, starting at element `offset`, into `data`.
+/// # Safety
+/// valid mem object; `offset + data.len()` must not exceed the length of the buffer.
+/// If `block` is false, `data` must stay valid and must not be accessed until the returned event completes.
+pub unsafe fn enqueue_read_buffer_at<T>(
+    cq: &CommandQueue,
+    mem: *mut c_void,
+    offset: usize,
+    data: &mut [T],
+    block: bool,
+    event_wait_list: Option<&[Event]>,
 ) -> Result<Event, Error> {
     let mut events = [std::ptr::null_mut(); 1];
 
@@ -419,8 +1240,8 @@ pub unsafe fn enqueue_read_buffer<T>(
         cq.0,
         mem,
         block as u32,
-        0,
-        data.len() * core::mem::size_of::<T>(),
+        offset * size_of::<T>(),
+        std::mem::size_of_val(data),
         data.as_ptr() as *mut c_void,
         num_events_in_wait_list,
         event_wait_list,
@@ -506,91 +1327,1083 @@ where
         }
     }
 
-    // borrow to avoid moving while the event is still in progress
-    for event in &events {
-        wait_for_event(Event(event[0]))?;
+    // borrow to avoid moving while the event is still in progress
+    for event in &events {
+        wait_for_event(Event(event[0]))?;
+    }
+
+    Ok(())
+}
+
+/// Describes the layout of a rectangular (2D or 3D) region inside a linear allocation. All values are in elements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RectLayout {
+    /// Column (x), row (y) and slice (z) of the first element of the region.
+    pub origin: [usize; 3],
+    /// Elements per row. `0` uses the width of the region.
+    pub row_pitch: usize,
+    /// Elements per slice. `0` uses `row_pitch` times the height of the region.
+    pub slice_pitch: usize,
+}
+
+impl RectLayout {
+    pub fn new(origin: [usize; 3], row_pitch: usize, slice_pitch: usize) -> RectLayout {
+        RectLayout {
+            origin,
+            row_pitch,
+            slice_pitch,
+        }
+    }
+
+    /// A row-major matrix with `cols` columns, starting at the first element.
+    pub fn matrix(cols: usize) -> RectLayout {
+        RectLayout::new([0; 3], cols, 0)
+    }
+
+    /// Moves the origin to `col`, `row` (and slice `0`).
+    pub fn at(mut self, col: usize, row: usize) -> RectLayout {
+        self.origin = [col, row, 0];
+        self
+    }
+
+    /// Returns the row and slice pitch with the defaults of `0` applied.
+    /// Returns `OCLErrorKind::InvalidRect` if the default slice pitch overflows.
+    pub fn pitches(&self, region: [usize; 3]) -> Result<(usize, usize), Error> {
+        let row_pitch = if self.row_pitch == 0 {
+            region[0]
+        } else {
+            self.row_pitch
+        };
+        let slice_pitch = if self.slice_pitch == 0 {
+            row_pitch
+                .checked_mul(region[1])
+                .ok_or(OCLErrorKind::InvalidRect)?
+        } else {
+            self.slice_pitch
+        };
+        Ok((row_pitch, slice_pitch))
+    }
+
+    /// Checks that `region` fits into the layout and into an allocation of `len` elements.
+    pub fn validate(&self, region: [usize; 3], len: usize) -> Result<(), Error> {
+        let (row_pitch, slice_pitch) = self.pitches(region)?;
+        let [row_end, rows_end, slices_end] = checked_ends(self.origin, region)?;
+        let rows_size = row_pitch
+            .checked_mul(region[1])
+            .ok_or(OCLErrorKind::InvalidRect)?;
+        if region.contains(&0) || row_pitch < region[0] || slice_pitch < rows_size {
+            return Err(OCLErrorKind::InvalidRect.into());
+        }
+        // rows must not wrap into the next row, nor slices into the next slice
+        let multiple_slices = self.origin[2] > 0 || region[2] > 1;
+        if row_end > row_pitch
+            || multiple_slices
+                && rows_end
+                    .checked_mul(row_pitch)
+                    .is_none_or(|rows_end| rows_end > slice_pitch)
+        {
+            return Err(OCLErrorKind::InvalidRect.into());
+        }
+        let end = (slices_end - 1)
+            .checked_mul(slice_pitch)
+            .and_then(|offset| offset.checked_add((rows_end - 1).checked_mul(row_pitch)?))
+            .and_then(|offset| offset.checked_add(row_end))
+            .ok_or(OCLErrorKind::InvalidRect)?;
+        if end > len {
+            return Err(OCLErrorKind::InvalidBufferRange.into());
+        }
+        Ok(())
+    }
+
+    // origin, row pitch and slice pitch in bytes as expected by the *Rect functions
+    fn as_bytes<T>(&self, region: [usize; 3]) -> Result<([usize; 3], usize, usize), Error> {
+        let (row_pitch, slice_pitch) = self.pitches(region)?;
+        let bytes = |elements: usize| {
+            elements
+                .checked_mul(size_of::<T>())
+                .ok_or(OCLErrorKind::InvalidRect)
+        };
+        Ok((
+            [bytes(self.origin[0])?, self.origin[1], self.origin[2]],
+            bytes(row_pitch)?,
+            bytes(slice_pitch)?,
+        ))
+    }
+}
+
+/// The exclusive end of `region` at `origin` in each dimension.
+fn checked_ends(origin: [usize; 3], region: [usize; 3]) -> Result<[usize; 3], Error> {
+    let mut ends = [0; 3];
+    for (end, (origin, region)) in ends.iter_mut().zip(origin.into_iter().zip(region)) {
+        *end = origin
+            .checked_add(region)
+            .ok_or(OCLErrorKind::InvalidRect)?;
+    }
+    Ok(ends)
+}
+
+fn region_as_bytes<T>(region: [usize; 3]) -> Result<[usize; 3], Error> {
+    let width = region[0]
+        .checked_mul(size_of::<T>())
+        .ok_or(OCLErrorKind::InvalidRect)?;
+    Ok([width, region[1], region[2]])
+}
+
+/// Reads the `region` (width, height, depth in elements) of the buffer into `data`.
+/// # Safety
+/// valid mem object; both layouts must be validated against the buffer and `data`
+#[allow(clippy::too_many_arguments)]
+pub unsafe fn enqueue_read_buffer_rect<T>(
+    cq: &CommandQueue,
+    mem: *mut c_void,
+    buffer_layout: RectLayout,
+    data: &mut [T],
+    host_layout: RectLayout,
+    region: [usize; 3],
+    block: bool,
+    event_wait_list: Option<&[Event]>,
+) -> Result<Event, Error> {
+    let mut events = [std::ptr::null_mut(); 1];
+
+    let (num_events_in_wait_list, event_wait_list) = extract_event_wait_list(event_wait_list);
+    let (buffer_origin, buffer_row_pitch, buffer_slice_pitch) =
+        buffer_layout.as_bytes::<T>(region)?;
+    let (host_origin, host_row_pitch, host_slice_pitch) = host_layout.as_bytes::<T>(region)?;
+    let region = region_as_bytes::<T>(region)?;
+
+    let value = clEnqueueReadBufferRect(
+        cq.0,
+        mem,
+        block as u32,
+        buffer_origin.as_ptr(),
+        host_origin.as_ptr(),
+        region.as_ptr(),
+        buffer_row_pitch,
+        buffer_slice_pitch,
+        host_row_pitch,
+        host_slice_pitch,
+        data.as_mut_ptr() as *mut c_void,
+        num_events_in_wait_list,
+        event_wait_list,
+        events.as_mut_ptr() as *mut cl_event,
+    );
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(Event(events[0]))
+}
+
+/// Writes the `region` (width, height, depth in elements) of `data` to the buffer.
+/// # Safety
+/// valid mem object; both layouts must be validated against the buffer and `data`
+#[allow(clippy::too_many_arguments)]
+pub unsafe fn enqueue_write_buffer_rect<T>(
+    cq: &CommandQueue,
+    mem: *mut c_void,
+    buffer_layout: RectLayout,
+    data: &[T],
+    host_layout: RectLayout,
+    region: [usize; 3],
+    block: bool,
+    event_wait_list: Option<&[Event]>,
+) -> Result<Event, Error> {
+    let mut events = [std::ptr::null_mut(); 1];
+
+    let (num_events_in_wait_list, event_wait_list) = extract_event_wait_list(event_wait_list);
+    let (buffer_origin, buffer_row_pitch, buffer_slice_pitch) =
+        buffer_layout.as_bytes::<T>(region)?;
+    let (host_origin, host_row_pitch, host_slice_pitch) = host_layout.as_bytes::<T>(region)?;
+    let region = region_as_bytes::<T>(region)?;
+
+    let value = clEnqueueWriteBufferRect(
+        cq.0,
+        mem,
+        block as u32,
+        buffer_origin.as_ptr(),
+        host_origin.as_ptr(),
+        region.as_ptr(),
+        buffer_row_pitch,
+        buffer_slice_pitch,
+        host_row_pitch,
+        host_slice_pitch,
+        data.as_ptr() as *const c_void,
+        num_events_in_wait_list,
+        event_wait_list,
+        events.as_mut_ptr() as *mut cl_event,
+    );
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(Event(events[0]))
+}
+
+/// Copies the `region` (width, height, depth in elements) from `src_mem` to `dst_mem`.
+/// # Safety
+/// valid mem objects; both layouts must be validated against the buffers
+pub unsafe fn enqueue_copy_buffer_rect<T>(
+    cq: &CommandQueue,
+    src_mem: *mut c_void,
+    src_layout: RectLayout,
+    dst_mem: *mut c_void,
+    dst_layout: RectLayout,
+    region: [usize; 3],
+    event_wait_list: Option<&[Event]>,
+) -> Result<Event, Error> {
+    let mut events = [std::ptr::null_mut(); 1];
+
+    let (num_events_in_wait_list, event_wait_list) = extract_event_wait_list(event_wait_list);
+    let (src_origin, src_row_pitch, src_slice_pitch) = src_layout.as_bytes::<T>(region)?;
+    let (dst_origin, dst_row_pitch, dst_slice_pitch) = dst_layout.as_bytes::<T>(region)?;
+    let region = region_as_bytes::<T>(region)?;
+
+    let value = clEnqueueCopyBufferRect(
+        cq.0,
+        src_mem,
+        dst_mem,
+        src_origin.as_ptr(),
+        dst_origin.as_ptr(),
+        region.as_ptr(),
+        src_row_pitch,
+        src_slice_pitch,
+        dst_row_pitch,
+        dst_slice_pitch,
+        num_events_in_wait_list,
+        event_wait_list,
+        events.as_mut_ptr() as *mut cl_event,
+    );
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(Event(events[0]))
+}
+
+#[inline]
+pub unsafe fn enqueue_full_copy_buffer<T>(
+    cq: &CommandQueue,
+    src_mem: *mut c_void,
+    dst_mem: *mut c_void,
+    size: usize,
+    event_wait_list: Option<&[Event]>,
+) -> Result<Event, Error> {
+    enqueue_copy_buffer::<T>(cq, src_mem, dst_mem, 0, 0, size, event_wait_list)
+}
+
+pub unsafe fn unified_ptr<T>(
+    cq: &CommandQueue,
+    ptr: *mut c_void,
+    len: usize,
+    event_wait_list: Option<&[Event]>,
+) -> Result<*mut T, Error> {
+    unsafe {
+        enqueue_map_buffer::<T>(
+            cq,
+            ptr,
+            true,
+            MapFlags::Read | MapFlags::Write,
+            0,
+            len,
+            event_wait_list,
+        )
+        .map(|ptr| ptr as *mut T)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MapFlags {
+    Read = 1,
+    Write = 1 << 1,
+    /// The contents of the mapped region are undefined and are overwritten on unmap. Excludes `Read` and `Write`.
+    WriteInvalidateRegion = 1 << 2,
+}
+
+impl core::ops::BitOr for MapFlags {
+    type Output = u64;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self as u64 | rhs as u64
+    }
+}
+
+/// map_flags: `MapFlags` combined as bitfield
+/// # Safety
+/// valid mem object
+pub unsafe fn enqueue_map_buffer<T>(
+    cq: &CommandQueue,
+    buffer: *mut c_void,
+    block: bool,
+    map_flags: u64,
+    offset: usize,
+    len: usize,
+    event_wait_list: Option<&[Event]>,
+) -> Result<*mut c_void, Error> {
+    let (ptr, event) = enqueue_map_buffer_with_event::<T>(
+        cq,
+        buffer,
+        block,
+        map_flags,
+        offset,
+        len,
+        event_wait_list,
+    )?;
+    wait_for_event(event)?;
+    Ok(ptr)
+}
+
+/// Like [`enqueue_map_buffer`], but returns the event of the map instead of waiting for it.
+/// # Safety
+/// valid mem object; if `block` is false, the region must not be accessed until the event completes
+pub unsafe fn enqueue_map_buffer_with_event<T>(
+    cq: &CommandQueue,
+    buffer: *mut c_void,
+    block: bool,
+    map_flags: u64,
+    offset: usize,
+    len: usize,
+    event_wait_list: Option<&[Event]>,
+) -> Result<(*mut c_void, Event), Error> {
+    let offset = offset * core::mem::size_of::<T>();
+    let size = len * core::mem::size_of::<T>();
+
+    let mut event = [std::ptr::null_mut(); 1];
+
+    let (num_events_in_wait_list, event_wait_list) = extract_event_wait_list(event_wait_list);
+
+    let mut err = 0;
+
+    let ptr = clEnqueueMapBuffer(
+        cq.0,
+        buffer,
+        block as u32,
+        map_flags,
+        offset,
+        size,
+        num_events_in_wait_list,
+        event_wait_list,
+        event.as_mut_ptr() as *mut cl_event,
+        &mut err,
+    );
+
+    if err != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(err)));
+    }
+    Ok((ptr, Event(event[0])))
+}
+
+/// Unmaps a region previously mapped with [`enqueue_map_buffer`]. Writes to the region become visible to the device afterwards.
+/// # Safety
+/// `ptr` must be returned by mapping `mem` and must not be used after this call.
+pub unsafe fn enqueue_unmap_mem_object(
+    cq: &CommandQueue,
+    mem: *mut c_void,
+    ptr: *mut c_void,
+    event_wait_list: Option<&[Event]>,
+) -> Result<Event, Error> {
+    let mut events = [std::ptr::null_mut(); 1];
+
+    let (num_events_in_wait_list, event_wait_list) = extract_event_wait_list(event_wait_list);
+
+    let value = clEnqueueUnmapMemObject(
+        cq.0,
+        mem,
+        ptr,
+        num_events_in_wait_list,
+        event_wait_list,
+        events.as_mut_ptr() as *mut cl_event,
+    );
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(Event(events[0]))
+}
+
+/// Enqueues a barrier: following commands wait for the events of `event_wait_list`,
+/// or for all previous commands of the queue if there are none.
+pub fn enqueue_barrier(
+    cq: &CommandQueue,
+    event_wait_list: Option<&[Event]>,
+) -> Result<Event, Error> {
+    let mut events = [std::ptr::null_mut(); 1];
+
+    let (num_events_in_wait_list, event_wait_list) = extract_event_wait_list(event_wait_list);
+
+    let value = unsafe {
+        clEnqueueBarrierWithWaitList(
+            cq.0,
+            num_events_in_wait_list,
+            event_wait_list,
+            events.as_mut_ptr() as *mut cl_event,
+        )
+    };
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(Event(events[0]))
+}
+
+/// Fills `len` elements starting at element `offset` with `pattern`.
+/// The size of `T` must be a power of two and at most 128 bytes.
+/// # Safety
+/// valid mem object
+pub unsafe fn enqueue_fill_buffer<T>(
+    cq: &CommandQueue,
+    mem: *mut c_void,
+    pattern: &T,
+    offset: usize,
+    len: usize,
+    event_wait_list: Option<&[Event]>,
+) -> Result<Event, Error> {
+    let mut events = [std::ptr::null_mut(); 1];
+
+    let (num_events_in_wait_list, event_wait_list) = extract_event_wait_list(event_wait_list);
+
+    let value = unsafe {
+        clEnqueueFillBuffer(
+            cq.0,
+            mem,
+            pattern as *const T as *const c_void,
+            size_of::<T>(),
+            offset * size_of::<T>(),
+            len * size_of::<T>(),
+            num_events_in_wait_list,
+            event_wait_list,
+            events.as_mut_ptr() as *mut cl_event,
+        )
+    };
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(Event(events[0]))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChannelOrder {
+    R = 0x10B0,
+    A = 0x10B1,
+    RG = 0x10B2,
+    RA = 0x10B3,
+    RGB = 0x10B4,
+    RGBA = 0x10B5,
+    BGRA = 0x10B6,
+    ARGB = 0x10B7,
+    Intensity = 0x10B8,
+    Luminance = 0x10B9,
+}
+
+impl ChannelOrder {
+    const ALL: [ChannelOrder; 10] = [
+        ChannelOrder::R,
+        ChannelOrder::A,
+        ChannelOrder::RG,
+        ChannelOrder::RA,
+        ChannelOrder::RGB,
+        ChannelOrder::RGBA,
+        ChannelOrder::BGRA,
+        ChannelOrder::ARGB,
+        ChannelOrder::Intensity,
+        ChannelOrder::Luminance,
+    ];
+
+    pub fn channels(self) -> usize {
+        match self {
+            ChannelOrder::R
+            | ChannelOrder::A
+            | ChannelOrder::Intensity
+            | ChannelOrder::Luminance => 1,
+            ChannelOrder::RG | ChannelOrder::RA => 2,
+            ChannelOrder::RGB => 3,
+            ChannelOrder::RGBA | ChannelOrder::BGRA | ChannelOrder::ARGB => 4,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChannelType {
+    SnormInt8 = 0x10D0,
+    SnormInt16 = 0x10D1,
+    UnormInt8 = 0x10D2,
+    UnormInt16 = 0x10D3,
+    UnormShort565 = 0x10D4,
+    UnormShort555 = 0x10D5,
+    UnormInt101010 = 0x10D6,
+    SignedInt8 = 0x10D7,
+    SignedInt16 = 0x10D8,
+    SignedInt32 = 0x10D9,
+    UnsignedInt8 = 0x10DA,
+    UnsignedInt16 = 0x10DB,
+    UnsignedInt32 = 0x10DC,
+    HalfFloat = 0x10DD,
+    Float = 0x10DE,
+}
+
+impl ChannelType {
+    const ALL: [ChannelType; 15] = [
+        ChannelType::SnormInt8,
+        ChannelType::SnormInt16,
+        ChannelType::UnormInt8,
+        ChannelType::UnormInt16,
+        ChannelType::UnormShort565,
+        ChannelType::UnormShort555,
+        ChannelType::UnormInt101010,
+        ChannelType::SignedInt8,
+        ChannelType::SignedInt16,
+        ChannelType::SignedInt32,
+        ChannelType::UnsignedInt8,
+        ChannelType::UnsignedInt16,
+        ChannelType::UnsignedInt32,
+        ChannelType::HalfFloat,
+        ChannelType::Float,
+    ];
+}
+
+/// The channel order and channel data type of the pixels of an image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ImageFormat {
+    pub order: ChannelOrder,
+    pub data_type: ChannelType,
+}
+
+impl ImageFormat {
+    pub fn new(order: ChannelOrder, data_type: ChannelType) -> ImageFormat {
+        ImageFormat { order, data_type }
+    }
+
+    /// Size of a pixel in bytes.
+    pub fn pixel_size(self) -> usize {
+        let channel_size = match self.data_type {
+            // packed formats store all channels of a pixel in one value
+            ChannelType::UnormShort565 | ChannelType::UnormShort555 => return 2,
+            ChannelType::UnormInt101010 => return 4,
+            ChannelType::SnormInt8 | ChannelType::UnormInt8 => 1,
+            ChannelType::SignedInt8 | ChannelType::UnsignedInt8 => 1,
+            ChannelType::SnormInt16 | ChannelType::UnormInt16 => 2,
+            ChannelType::SignedInt16 | ChannelType::UnsignedInt16 => 2,
+            ChannelType::HalfFloat => 2,
+            ChannelType::SignedInt32 | ChannelType::UnsignedInt32 => 4,
+            ChannelType::Float => 4,
+        };
+        self.order.channels() * channel_size
+    }
+
+    pub fn as_raw(self) -> cl_image_format {
+        cl_image_format {
+            image_channel_order: self.order as cl_channel_order,
+            image_channel_data_type: self.data_type as cl_channel_type,
+        }
+    }
+
+    /// Returns `None` for channel orders and types that are not known to min-cl, e.g. vendor extensions.
+    pub fn from_raw(format: cl_image_format) -> Option<ImageFormat> {
+        let order = ChannelOrder::ALL
+            .into_iter()
+            .find(|&order| order as cl_channel_order == format.image_channel_order)?;
+        let data_type = ChannelType::ALL
+            .into_iter()
+            .find(|&ty| ty as cl_channel_type == format.image_channel_data_type)?;
+        Some(ImageFormat { order, data_type })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ImageType {
+    Image2D = 0x10F1,
+    Image3D = 0x10F2,
+    Image2DArray = 0x10F3,
+}
+
+/// Returns the image formats supported by all devices of the context for the given `MemFlags` and image type.
+pub fn get_supported_image_formats(
+    context: &Context,
+    flags: u64,
+    image_type: ImageType,
+) -> Result<Vec<ImageFormat>, Error> {
+    let mut num_formats: cl_uint = 0;
+    let value = unsafe {
+        clGetSupportedImageFormats(
+            context.0,
+            flags,
+            image_type as cl_mem_object_type,
+            0,
+            std::ptr::null_mut(),
+            &mut num_formats,
+        )
+    };
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+
+    let mut formats = vec![
+        cl_image_format {
+            image_channel_order: 0,
+            image_channel_data_type: 0,
+        };
+        num_formats as usize
+    ];
+    let value = unsafe {
+        clGetSupportedImageFormats(
+            context.0,
+            flags,
+            image_type as cl_mem_object_type,
+            num_formats,
+            formats.as_mut_ptr(),
+            std::ptr::null_mut(),
+        )
+    };
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(formats
+        .into_iter()
+        .filter_map(ImageFormat::from_raw)
+        .collect())
+}
+
+/// Creates an image with tightly packed rows.
+/// `size` is the width, height and depth (3D) or array size (2D array) in pixels. The last value is `1` for 2D images.
+/// # Safety
+/// If `data` is `Some`, `flags` must contain `MemCopyHostPtr` or `MemUseHostPtr` and `data` must hold all pixels of the image.
+pub unsafe fn create_image<T>(
+    context: &Context,
+    flags: u64,
+    format: ImageFormat,
+    image_type: ImageType,
+    size: [usize; 3],
+    data: Option<&[T]>,
+) -> Result<*mut c_void, Error> {
+    let desc = cl_image_desc {
+        image_type: image_type as cl_mem_object_type,
+        image_width: size[0],
+        image_height: size[1],
+        image_depth: if image_type == ImageType::Image3D {
+            size[2]
+        } else {
+            0
+        },
+        image_array_size: if image_type == ImageType::Image2DArray {
+            size[2]
+        } else {
+            0
+        },
+        image_row_pitch: 0,
+        image_slice_pitch: 0,
+        num_mip_levels: 0,
+        num_samples: 0,
+        buffer: std::ptr::null_mut(),
+    };
+    let host_ptr = match data {
+        Some(d) => d.as_ptr() as *mut c_void,
+        None => std::ptr::null_mut(),
+    };
+
+    let mut err = 0;
+    let r = unsafe {
+        clCreateImage(
+            context.0,
+            flags,
+            &format.as_raw(),
+            &desc,
+            host_ptr,
+            &mut err,
+        )
+    };
+    if err != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(err)));
+    }
+    Ok(r)
+}
+
+/// Reads the `region` (width, height, depth in pixels) at `origin` of the image into the tightly packed `data`.
+/// # Safety
+/// valid image; `T` must have the size of a pixel and `data` must hold all pixels of the region.
+/// If `block` is false, `data` must stay valid until the returned event completes.
+#[allow(clippy::too_many_arguments)]
+pub unsafe fn enqueue_read_image<T>(
+    cq: &CommandQueue,
+    image: *mut c_void,
+    origin: [usize; 3],
+    region: [usize; 3],
+    data: &mut [T],
+    block: bool,
+    event_wait_list: Option<&[Event]>,
+) -> Result<Event, Error> {
+    let mut events = [std::ptr::null_mut(); 1];
+    let (num_events_in_wait_list, event_wait_list) = extract_event_wait_list(event_wait_list);
+
+    let value = clEnqueueReadImage(
+        cq.0,
+        image,
+        block as u32,
+        origin.as_ptr(),
+        region.as_ptr(),
+        0,
+        0,
+        data.as_mut_ptr() as *mut c_void,
+        num_events_in_wait_list,
+        event_wait_list,
+        events.as_mut_ptr() as *mut cl_event,
+    );
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(Event(events[0]))
+}
+
+/// Writes the tightly packed `data` to the `region` (width, height, depth in pixels) at `origin` of the image.
+/// # Safety
+/// valid image; `T` must have the size of a pixel and `data` must hold all pixels of the region.
+/// If `block` is false, `data` must stay valid until the returned event completes.
+#[allow(clippy::too_many_arguments)]
+pub unsafe fn enqueue_write_image<T>(
+    cq: &CommandQueue,
+    image: *mut c_void,
+    origin: [usize; 3],
+    region: [usize; 3],
+    data: &[T],
+    block: bool,
+    event_wait_list: Option<&[Event]>,
+) -> Result<Event, Error> {
+    let mut events = [std::ptr::null_mut(); 1];
+    let (num_events_in_wait_list, event_wait_list) = extract_event_wait_list(event_wait_list);
+
+    let value = clEnqueueWriteImage(
+        cq.0,
+        image,
+        block as u32,
+        origin.as_ptr(),
+        region.as_ptr(),
+        0,
+        0,
+        data.as_ptr() as *const c_void,
+        num_events_in_wait_list,
+        event_wait_list,
+        events.as_mut_ptr() as *mut cl_event,
+    );
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(Event(events[0]))
+}
+
+/// Copies the `region` (width, height, depth in pixels) between two images of the same format.
+/// # Safety
+/// valid images
+pub unsafe fn enqueue_copy_image(
+    cq: &CommandQueue,
+    src: *mut c_void,
+    src_origin: [usize; 3],
+    dst: *mut c_void,
+    dst_origin: [usize; 3],
+    region: [usize; 3],
+    event_wait_list: Option<&[Event]>,
+) -> Result<Event, Error> {
+    let mut events = [std::ptr::null_mut(); 1];
+    let (num_events_in_wait_list, event_wait_list) = extract_event_wait_list(event_wait_list);
+
+    let value = clEnqueueCopyImage(
+        cq.0,
+        src,
+        dst,
+        src_origin.as_ptr(),
+        dst_origin.as_ptr(),
+        region.as_ptr(),
+        num_events_in_wait_list,
+        event_wait_list,
+        events.as_mut_ptr() as *mut cl_event,
+    );
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(Event(events[0]))
+}
+
+/// Copies the `region` (width, height, depth in pixels) of the image to the buffer, starting at element `dst_offset`.
+/// The pixels are tightly packed in the buffer.
+/// # Safety
+/// valid image and mem object; `T` must have the size of a pixel
+pub unsafe fn enqueue_copy_image_to_buffer<T>(
+    cq: &CommandQueue,
+    src: *mut c_void,
+    src_origin: [usize; 3],
+    region: [usize; 3],
+    dst: *mut c_void,
+    dst_offset: usize,
+    event_wait_list: Option<&[Event]>,
+) -> Result<Event, Error> {
+    let mut events = [std::ptr::null_mut(); 1];
+    let (num_events_in_wait_list, event_wait_list) = extract_event_wait_list(event_wait_list);
+
+    let value = clEnqueueCopyImageToBuffer(
+        cq.0,
+        src,
+        dst,
+        src_origin.as_ptr(),
+        region.as_ptr(),
+        dst_offset * size_of::<T>(),
+        num_events_in_wait_list,
+        event_wait_list,
+        events.as_mut_ptr() as *mut cl_event,
+    );
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(Event(events[0]))
+}
+
+/// Copies tightly packed pixels, starting at element `src_offset` of the buffer, to the `region` (width, height, depth in pixels) of the image.
+/// # Safety
+/// valid image and mem object; `T` must have the size of a pixel
+pub unsafe fn enqueue_copy_buffer_to_image<T>(
+    cq: &CommandQueue,
+    src: *mut c_void,
+    src_offset: usize,
+    dst: *mut c_void,
+    dst_origin: [usize; 3],
+    region: [usize; 3],
+    event_wait_list: Option<&[Event]>,
+) -> Result<Event, Error> {
+    let mut events = [std::ptr::null_mut(); 1];
+    let (num_events_in_wait_list, event_wait_list) = extract_event_wait_list(event_wait_list);
+
+    let value = clEnqueueCopyBufferToImage(
+        cq.0,
+        src,
+        dst,
+        src_offset * size_of::<T>(),
+        dst_origin.as_ptr(),
+        region.as_ptr(),
+        num_events_in_wait_list,
+        event_wait_list,
+        events.as_mut_ptr() as *mut cl_event,
+    );
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(Event(events[0]))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AddressingMode {
+    None = 0x1130,
+    ClampToEdge = 0x1131,
+    Clamp = 0x1132,
+    /// Requires normalized coordinates.
+    Repeat = 0x1133,
+    /// Requires normalized coordinates.
+    MirroredRepeat = 0x1134,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FilterMode {
+    Nearest = 0x1140,
+    Linear = 0x1141,
+}
+
+/// Describes how a kernel reads an image. Passed to kernels like a buffer, via [`Sampler::as_ptr`].
+#[derive(Debug)]
+pub struct Sampler(pub cl_sampler);
+
+impl Sampler {
+    #[inline]
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+impl Clone for Sampler {
+    fn clone(&self) -> Self {
+        unsafe { retain_sampler(self).unwrap() };
+        Sampler(self.0)
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe { release_sampler(self).unwrap() };
+    }
+}
+
+unsafe impl Send for Sampler {}
+unsafe impl Sync for Sampler {}
+
+pub fn create_sampler(
+    context: &Context,
+    normalized_coords: bool,
+    addressing_mode: AddressingMode,
+    filter_mode: FilterMode,
+) -> Result<Sampler, Error> {
+    let mut err = 0;
+    let r = unsafe {
+        clCreateSampler(
+            context.0,
+            normalized_coords as cl_bool,
+            addressing_mode as cl_addressing_mode,
+            filter_mode as cl_filter_mode,
+            &mut err,
+        )
+    };
+    if err != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(err)));
+    }
+    Ok(Sampler(r))
+}
+
+/// # Safety
+/// valid sampler
+pub unsafe fn retain_sampler(sampler: &Sampler) -> Result<(), Error> {
+    let value = clRetainSampler(sampler.0);
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
     }
+    Ok(())
+}
 
+/// # Safety
+/// valid sampler
+pub unsafe fn release_sampler(sampler: &mut Sampler) -> Result<(), Error> {
+    let value = clReleaseSampler(sampler.0);
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
     Ok(())
 }
 
-#[inline]
-pub unsafe fn enqueue_full_copy_buffer<T>(
+/// Allocates shared virtual memory for `len` elements. Requires OpenCL 2.0.
+/// `flags` are `MemFlags`, `alignment` is in bytes (`0` for the default alignment).
+/// # Safety
+/// valid context; the allocation must be freed with [`svm_free`] in the same context
+pub unsafe fn svm_alloc<T>(
+    context: &Context,
+    flags: u64,
+    len: usize,
+    alignment: u32,
+) -> Result<*mut T, Error> {
+    let ptr = clSVMAlloc(context.0, flags, len * size_of::<T>(), alignment);
+    if ptr.is_null() {
+        return Err(OCLErrorKind::SvmAllocFailed.into());
+    }
+    Ok(ptr as *mut T)
+}
+
+/// # Safety
+/// `ptr` must be allocated by [`svm_alloc`] in `context`, commands using it must be finished
+pub unsafe fn svm_free(context: &Context, ptr: *mut c_void) {
+    clSVMFree(context.0, ptr)
+}
+
+/// Maps `len` elements of an SVM allocation for host access.
+/// # Safety
+/// `ptr` must point into an SVM allocation with at least `len` elements
+pub unsafe fn enqueue_svm_map<T>(
     cq: &CommandQueue,
-    src_mem: *mut c_void,
-    dst_mem: *mut c_void,
-    size: usize,
+    block: bool,
+    flags: u64,
+    ptr: *mut T,
+    len: usize,
     event_wait_list: Option<&[Event]>,
 ) -> Result<Event, Error> {
-    enqueue_copy_buffer::<T>(cq, src_mem, dst_mem, 0, 0, size, event_wait_list)
+    let mut events = [std::ptr::null_mut(); 1];
+    let (num_events_in_wait_list, event_wait_list) = extract_event_wait_list(event_wait_list);
+
+    let value = clEnqueueSVMMap(
+        cq.0,
+        block as u32,
+        flags,
+        ptr as *mut c_void,
+        len * size_of::<T>(),
+        num_events_in_wait_list,
+        event_wait_list,
+        events.as_mut_ptr() as *mut cl_event,
+    );
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(Event(events[0]))
 }
 
-pub unsafe fn unified_ptr<T>(
+/// # Safety
+/// `ptr` must be mapped by [`enqueue_svm_map`]
+pub unsafe fn enqueue_svm_unmap(
     cq: &CommandQueue,
     ptr: *mut c_void,
-    len: usize,
     event_wait_list: Option<&[Event]>,
-) -> Result<*mut T, Error> {
-    unsafe {
-        enqueue_map_buffer::<T>(cq, ptr, true, 2 | 1, 0, len, event_wait_list)
-            .map(|ptr| ptr as *mut T)
+) -> Result<Event, Error> {
+    let mut events = [std::ptr::null_mut(); 1];
+    let (num_events_in_wait_list, event_wait_list) = extract_event_wait_list(event_wait_list);
+
+    let value = clEnqueueSVMUnmap(
+        cq.0,
+        ptr,
+        num_events_in_wait_list,
+        event_wait_list,
+        events.as_mut_ptr() as *mut cl_event,
+    );
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
     }
+    Ok(Event(events[0]))
 }
 
-/// map_flags: Read: 1, Write: 2,
+/// Copies `len` elements from `src` to `dst`. Either pointer may be an SVM or a host pointer.
 /// # Safety
-/// valid mem object
-pub unsafe fn enqueue_map_buffer<T>(
+/// both pointers must be valid for `len` elements and must not overlap.
+/// If `block` is false, they must stay valid until the returned event completes.
+pub unsafe fn enqueue_svm_memcpy<T>(
     cq: &CommandQueue,
-    buffer: *mut c_void,
     block: bool,
-    map_flags: u64,
-    offset: usize,
+    dst: *mut T,
+    src: *const T,
     len: usize,
     event_wait_list: Option<&[Event]>,
-) -> Result<*mut c_void, Error> {
-    let offset = offset * core::mem::size_of::<T>();
-    let size = len * core::mem::size_of::<T>();
-
-    let mut event = [std::ptr::null_mut(); 1];
-
+) -> Result<Event, Error> {
+    let mut events = [std::ptr::null_mut(); 1];
     let (num_events_in_wait_list, event_wait_list) = extract_event_wait_list(event_wait_list);
 
-    let mut err = 0;
-
-    let ptr = clEnqueueMapBuffer(
+    let value = clEnqueueSVMMemcpy(
         cq.0,
-        buffer,
         block as u32,
-        map_flags,
-        offset,
-        size,
+        dst as *mut c_void,
+        src as *const c_void,
+        len * size_of::<T>(),
         num_events_in_wait_list,
         event_wait_list,
-        event.as_mut_ptr() as *mut cl_event,
-        &mut err,
+        events.as_mut_ptr() as *mut cl_event,
     );
-
-    if err != 0 {
-        return Err(Error::from(OCLErrorKind::from_value(err)));
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
     }
-
-    let e = Event(event[0]);
-    wait_for_event(e)?;
-    Ok(ptr)
+    Ok(Event(events[0]))
 }
-/*
-pub fn enqueue_fill_buffer<T>(cq: &CommandQueue, mem: &Mem, pattern: Vec<T>) -> Event {
-    let mut events = vec![std::ptr::null_mut();1];
-    let offset = 0;
-    let pattern_size = core::mem::size_of::<T>();
-    let size = pattern_size*pattern.len();
-    let err = unsafe {clEnqueueFillBuffer(cq.0, mem.0, pattern.as_ptr() as *mut c_void, pattern_size, offset, size, 0, std::ptr::null(), events.as_mut_ptr() as *mut cl_event)};
-    println!("err enq copy bff: {}", err);
-    Event(events[0])
+
+/// Fills `len` elements starting at `ptr` with `pattern`.
+/// The size of `T` must be a power of two and at most 128 bytes.
+/// # Safety
+/// `ptr` must point into an SVM allocation with at least `len` elements
+pub unsafe fn enqueue_svm_mem_fill<T>(
+    cq: &CommandQueue,
+    ptr: *mut T,
+    pattern: &T,
+    len: usize,
+    event_wait_list: Option<&[Event]>,
+) -> Result<Event, Error> {
+    let mut events = [std::ptr::null_mut(); 1];
+    let (num_events_in_wait_list, event_wait_list) = extract_event_wait_list(event_wait_list);
+
+    let value = clEnqueueSVMMemFill(
+        cq.0,
+        ptr as *mut c_void,
+        pattern as *const T as *const c_void,
+        size_of::<T>(),
+        len * size_of::<T>(),
+        num_events_in_wait_list,
+        event_wait_list,
+        events.as_mut_ptr() as *mut cl_event,
+    );
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(Event(events[0]))
 }
-*/
 
+#[derive(Debug)]
 pub struct Program(pub cl_program);
 
 impl Drop for Program {
@@ -599,7 +2412,12 @@ impl Drop for Program {
     }
 }
 
-enum ProgramInfo {
+unsafe impl Send for Program {}
+unsafe impl Sync for Program {}
+
+#[derive(Clone, Copy)]
+pub enum ProgramInfo {
+    Source = 0x1164,
     BinarySizes = 0x1165,
     Binaries = 0x1166,
 }
@@ -618,6 +2436,46 @@ pub unsafe fn release_program(program: &mut Program) -> Result<(), Error> {
     Ok(())
 }
 
+/// Returns the raw value of a program info parameter.
+/// `ProgramInfo::Binaries` expects an array of host pointers instead and cannot be queried with this function.
+pub fn get_program_info(program: &Program, param_name: ProgramInfo) -> Result<Vec<u8>, Error> {
+    let mut size: size_t = 0;
+    let value = unsafe {
+        clGetProgramInfo(
+            program.0,
+            param_name as cl_program_info,
+            0,
+            std::ptr::null_mut(),
+            &mut size,
+        )
+    };
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+
+    let mut param_value = vec![0u8; size];
+    let value = unsafe {
+        clGetProgramInfo(
+            program.0,
+            param_name as cl_program_info,
+            size,
+            param_value.as_mut_ptr() as *mut c_void,
+            std::ptr::null_mut(),
+        )
+    };
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(param_value)
+}
+
+/// Stable 64-bit FNV-1a hash of a program source.
+pub fn source_hash(src: &str) -> u64 {
+    src.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
 pub unsafe fn create_program_with_source(context: &Context, src: &str) -> Result<Program, Error> {
     let mut err = 0;
     let cs = CString::new(src).expect("No cstring for you!");
@@ -638,6 +2496,66 @@ pub unsafe fn create_program_with_source(context: &Context, src: &str) -> Result
     Ok(Program(r))
 }
 
+/// Creates a program for a single device from a binary that was previously obtained with [`get_program_binaries`].
+/// The program still needs to be built with [`build_program`].
+/// # Safety
+/// The binary is passed to the driver as is and must stem from the same device.
+pub unsafe fn create_program_with_binary(
+    context: &Context,
+    device: CLIntDevice,
+    binary: &[u8],
+) -> Result<Program, Error> {
+    let mut err = 0;
+    let mut binary_status = 0;
+    let r = unsafe {
+        clCreateProgramWithBinary(
+            context.0,
+            1,
+            &device.0,
+            &binary.len(),
+            &binary.as_ptr(),
+            &mut binary_status,
+            &mut err,
+        )
+    };
+    if err != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(err)));
+    }
+    if binary_status != 0 {
+        // the program object is still created, it would leak otherwise
+        drop(Program(r));
+        return Err(Error::from(OCLErrorKind::from_value(binary_status)));
+    }
+    Ok(Program(r))
+}
+
+/// Returns the binary of a built program for every device it was built for.
+pub fn get_program_binaries(program: &Program) -> Result<Vec<Vec<u8>>, Error> {
+    let sizes = get_program_info(program, ProgramInfo::BinarySizes)?;
+    let mut binaries = sizes
+        .chunks_exact(size_of::<usize>())
+        .map(|size| vec![0u8; usize::from_ne_bytes(size.try_into().unwrap())])
+        .collect::<Vec<_>>();
+    let mut ptrs = binaries
+        .iter_mut()
+        .map(|binary| binary.as_mut_ptr())
+        .collect::<Vec<_>>();
+
+    let value = unsafe {
+        clGetProgramInfo(
+            program.0,
+            ProgramInfo::Binaries as cl_program_info,
+            ptrs.len() * size_of::<*mut u8>(),
+            ptrs.as_mut_ptr() as *mut c_void,
+            std::ptr::null_mut(),
+        )
+    };
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(binaries)
+}
+
 pub unsafe fn build_program(
     program: &Program,
     devices: &[CLIntDevice],
@@ -671,7 +2589,9 @@ pub unsafe fn build_program(
     };
 
     if err != 0 {
-        return Err(Error::from(OCLErrorKind::from_value(err)));
+        return Err(
+            BuildError::new(OCLErrorKind::from_value(err), program, devices, options).into(),
+        );
     }
     Ok(())
 }
@@ -704,7 +2624,7 @@ pub fn get_program_build_info(
         let err = unsafe {
             clGetProgramBuildInfo(
                 program.0,
-                devices[0].0,
+                device.0,
                 program_build_info as u32,
                 log_size,
                 log.as_mut_ptr() as *mut c_void,
@@ -716,8 +2636,8 @@ pub fn get_program_build_info(
             return Err(Error::from(OCLErrorKind::from_value(err)));
         }
 
-        let log_cstr = unsafe { std::ffi::CStr::from_bytes_with_nul_unchecked(&log) };
-        results.push(log_cstr.to_string_lossy().into_owned());
+        let log = log.strip_suffix(&[0]).unwrap_or(&log);
+        results.push(String::from_utf8_lossy(log).into_owned());
     }
     Ok(results)
 }
@@ -731,8 +2651,149 @@ impl Drop for Kernel {
     }
 }
 
+// clSetKernelArg is the only OpenCL function that is not thread-safe, so a kernel
+// can be moved to another thread, but not shared between threads.
 unsafe impl Send for Kernel {}
-unsafe impl Sync for Kernel {}
+
+impl Kernel {
+    /// Returns the function name of the kernel.
+    pub fn name(&self) -> Result<String, Error> {
+        let mut name = get_kernel_info(self, KernelInfo::FunctionName)?;
+        if name.last() == Some(&0) {
+            name.pop();
+        }
+        Ok(String::from_utf8_lossy(&name).into_owned())
+    }
+
+    /// Returns the number of parameters of the kernel.
+    pub fn num_args(&self) -> Result<usize, Error> {
+        let num_args = get_kernel_info(self, KernelInfo::NumArgs)?;
+        Ok(u32::from_ne_bytes(num_args[..4].try_into().unwrap()) as usize)
+    }
+
+    /// Queries the work group limits and the memory usage of the kernel on `device`.
+    pub fn work_group_info(&self, device: CLIntDevice) -> Result<WorkGroupInfo, Error> {
+        // replies of an unexpected size are reported as `InvalidValue`
+        let usizes = |param_name, len| -> Result<Vec<usize>, Error> {
+            let value = get_kernel_work_group_info(self, device, param_name)?;
+            if value.len() != len * size_of::<usize>() {
+                return Err(OCLErrorKind::InvalidValue.into());
+            }
+            Ok(value
+                .chunks_exact(size_of::<usize>())
+                .map(|chunk| usize::from_ne_bytes(chunk.try_into().unwrap()))
+                .collect())
+        };
+        let ulong = |param_name| -> Result<u64, Error> {
+            let value = get_kernel_work_group_info(self, device, param_name)?;
+            let value = value.try_into().map_err(|_| OCLErrorKind::InvalidValue)?;
+            Ok(u64::from_ne_bytes(value))
+        };
+
+        let compile_work_group_size = usizes(CL_KERNEL_COMPILE_WORK_GROUP_SIZE, 3)?;
+        Ok(WorkGroupInfo {
+            work_group_size: usizes(CL_KERNEL_WORK_GROUP_SIZE, 1)?[0],
+            compile_work_group_size: [0, 1, 2].map(|dim| compile_work_group_size[dim]),
+            local_mem_size: ulong(CL_KERNEL_LOCAL_MEM_SIZE)?,
+            private_mem_size: ulong(CL_KERNEL_PRIVATE_MEM_SIZE)?,
+            preferred_work_group_size_multiple: usizes(
+                CL_KERNEL_PREFERRED_WORK_GROUP_SIZE_MULTIPLE,
+                1,
+            )?[0],
+        })
+    }
+}
+
+/// Work group limits and memory usage of a kernel on a device, see [`Kernel::work_group_info`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkGroupInfo {
+    /// Maximum work group size for this kernel, which can be lower than the device limit.
+    pub work_group_size: usize,
+    /// Required by `__attribute__((reqd_work_group_size(X, Y, Z)))`, `[0, 0, 0]` if not specified.
+    pub compile_work_group_size: [usize; 3],
+    /// Local memory in bytes, including `__local` arguments that are set.
+    pub local_mem_size: u64,
+    /// Private memory in bytes per work item. A high value indicates register spilling.
+    pub private_mem_size: u64,
+    /// Work group sizes should be a multiple of this value for performance, e.g. the warp or wavefront size.
+    pub preferred_work_group_size_multiple: usize,
+}
+
+#[derive(Clone, Copy)]
+pub enum KernelInfo {
+    FunctionName = 0x1190,
+    NumArgs = 0x1191,
+}
+
+/// Returns the raw value of a kernel info parameter.
+pub fn get_kernel_info(kernel: &Kernel, param_name: KernelInfo) -> Result<Vec<u8>, Error> {
+    let mut size: size_t = 0;
+    let value = unsafe {
+        clGetKernelInfo(
+            kernel.0,
+            param_name as cl_kernel_info,
+            0,
+            std::ptr::null_mut(),
+            &mut size,
+        )
+    };
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+
+    let mut param_value = vec![0u8; size];
+    let value = unsafe {
+        clGetKernelInfo(
+            kernel.0,
+            param_name as cl_kernel_info,
+            size,
+            param_value.as_mut_ptr() as *mut c_void,
+            std::ptr::null_mut(),
+        )
+    };
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(param_value)
+}
+
+/// Returns the raw value of a `CL_KERNEL_*` work-group info parameter of the kernel on `device`.
+pub fn get_kernel_work_group_info(
+    kernel: &Kernel,
+    device: CLIntDevice,
+    param_name: cl_kernel_work_group_info,
+) -> Result<Vec<u8>, Error> {
+    let mut size: size_t = 0;
+    let value = unsafe {
+        clGetKernelWorkGroupInfo(
+            kernel.0,
+            device.0,
+            param_name,
+            0,
+            std::ptr::null_mut(),
+            &mut size,
+        )
+    };
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+
+    let mut param_value = vec![0u8; size];
+    let value = unsafe {
+        clGetKernelWorkGroupInfo(
+            kernel.0,
+            device.0,
+            param_name,
+            size,
+            param_value.as_mut_ptr() as *mut c_void,
+            std::ptr::null_mut(),
+        )
+    };
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(param_value)
+}
 
 pub fn create_kernel(program: &Program, str: &str) -> Result<Kernel, Error> {
     let mut err = 0;
@@ -801,6 +2862,21 @@ pub unsafe fn set_kernel_arg(
     Ok(())
 }
 
+/// Passes an SVM pointer, or a pointer into an SVM allocation, as kernel argument.
+/// # Safety
+/// `ptr` must point into an SVM allocation of the context of the kernel
+pub unsafe fn set_kernel_arg_svm_pointer(
+    kernel: &Kernel,
+    index: usize,
+    ptr: *const c_void,
+) -> Result<(), Error> {
+    let value = clSetKernelArgSVMPointer(kernel.0, index as u32, ptr);
+    if value != 0 {
+        return Err(Error::from(OCLErrorKind::from_value(value)));
+    }
+    Ok(())
+}
+
 #[inline]
 fn extract_event_wait_list(event_wait_list: Option<&[Event]>) -> (u32, *const cl_event) {
     match event_wait_list {
@@ -1,8 +1,23 @@
+use super::{
+    get_program_build_info, get_program_info, source_hash, CLIntDevice, Program, ProgramBuildInfo,
+    ProgramInfo,
+};
+
 #[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum OCLErrorKind {
     GetPlatformIDs,
     GetDeviceIDs,
     InvalidDeviceIdx,
+    PlatformNotFound,
+    NoMatchingDevice,
+    BufferLengthMismatch,
+    TraceNotStarted,
+    InvalidBufferRange,
+    InvalidPatternSize,
+    InvalidRect,
+    InvalidImageRegion,
+    SvmNotSupported,
+    SvmAllocFailed,
     GetDeviceInfo,
     CreateContext,
     CreateCommandQueue,
@@ -143,6 +158,76 @@ impl OCLErrorKind {
             _ => OCLErrorKind::Unknown,
         }
     }
+
+    /// Returns the OpenCL error code of this error kind.
+    /// Errors that are specific to min-cl do not have a code.
+    pub fn value(&self) -> Option<i32> {
+        let value = match self {
+            OCLErrorKind::DeviceNotFound => -1,
+            OCLErrorKind::DeviceNotAvailable => -2,
+            OCLErrorKind::CompilerNotAvailable => -3,
+            OCLErrorKind::MemObjectAllocationFailure => -4,
+            OCLErrorKind::OutOfResources => -5,
+            OCLErrorKind::OutOfHostMemory => -6,
+            OCLErrorKind::ProfilingInfoNotAvailable => -7,
+            OCLErrorKind::MemCopyOverlap => -8,
+            OCLErrorKind::ImageFormatMismatch => -9,
+            OCLErrorKind::ImageFormatNotSupported => -10,
+            OCLErrorKind::BuildProgramFailures => -11,
+            OCLErrorKind::MapFailure => -12,
+            OCLErrorKind::MisalignedSubBufferOffset => -13,
+            OCLErrorKind::ExecStatusErrorForEventsInWaitList => -14,
+            OCLErrorKind::CompileProgramFailure => -15,
+            OCLErrorKind::LinkerNotAvailable => -16,
+            OCLErrorKind::LinkProgramFailure => -17,
+            OCLErrorKind::DevicePartitionFailed => -18,
+            OCLErrorKind::KernelArgInfoNotAvailable => -19,
+            OCLErrorKind::InvalidValue => -30,
+            OCLErrorKind::InvalidDeviceType => -31,
+            OCLErrorKind::InvalidPlatform => -32,
+            OCLErrorKind::InvalidDevice => -33,
+            OCLErrorKind::InvalidContext => -34,
+            OCLErrorKind::InvalidQueueProperties => -35,
+            OCLErrorKind::InvalidCommandQueue => -36,
+            OCLErrorKind::InvalidHostPtr => -37,
+            OCLErrorKind::InvalidMemObject => -38,
+            OCLErrorKind::InvalidImageFormatDescriptor => -39,
+            OCLErrorKind::InvalidImageSize => -40,
+            OCLErrorKind::InvalidSampler => -41,
+            OCLErrorKind::InvalidBinary => -42,
+            OCLErrorKind::InvalidBuildOptions => -43,
+            OCLErrorKind::InvalidProgram => -44,
+            OCLErrorKind::InvalidProgramExecutable => -45,
+            OCLErrorKind::InvalidKernelName => -46,
+            OCLErrorKind::InvalidKernelDefintion => -47,
+            OCLErrorKind::InvalidKernel => -48,
+            OCLErrorKind::InvalidArgIndex => -49,
+            OCLErrorKind::InvalidArgValue => -50,
+            OCLErrorKind::InvalidArgSize => -51,
+            OCLErrorKind::InvalidKernelArgs => -52,
+            OCLErrorKind::InvalidWorkDimension => -53,
+            OCLErrorKind::InvalidWorkGroupSize => -54,
+            OCLErrorKind::InvalidWorkItemSize => -55,
+            OCLErrorKind::InvalidGlobalOffset => -56,
+            OCLErrorKind::InvalidEventWaitList => -57,
+            OCLErrorKind::InvalidEvent => -58,
+            OCLErrorKind::InvalidOperation => -59,
+            OCLErrorKind::InvalidGlObject => -60,
+            OCLErrorKind::InvalidBufferSize => -61,
+            OCLErrorKind::InvalidMIPLevel => -62,
+            OCLErrorKind::InvalidGlobalWorkSize => -63,
+            OCLErrorKind::InvalidProperty => -64,
+            OCLErrorKind::InvalidImageDescriptor => -65,
+            OCLErrorKind::InvalidCompilerOptions => -66,
+            OCLErrorKind::InvalidLinkerOptions => -67,
+            OCLErrorKind::InvalidDevicePartitionCount => -68,
+            OCLErrorKind::InvalidPipeSize => -69,
+            OCLErrorKind::InvalidDeviceQueue => -70,
+            OCLErrorKind::PlatformNotFoundKHR => -1001,
+            _ => return None,
+        };
+        Some(value)
+    }
 }
 
 impl OCLErrorKind {
@@ -154,6 +239,24 @@ impl OCLErrorKind {
             OCLErrorKind::InvalidDeviceIdx => {
                 "Invalid device idx, specific OpenCL device not found"
             }
+            OCLErrorKind::PlatformNotFound => "No OpenCL platform matches the given name or vendor",
+            OCLErrorKind::NoMatchingDevice => "No OpenCL device matches the device selector",
+            OCLErrorKind::BufferLengthMismatch => {
+                "The length of the host slice does not match the length of the buffer"
+            }
+            OCLErrorKind::TraceNotStarted => "No trace was started on this device",
+            OCLErrorKind::InvalidBufferRange => "The range is out of the bounds of the buffer",
+            OCLErrorKind::InvalidRect => {
+                "Invalid region or pitch of a rectangular transfer, rows or slices overlap"
+            }
+            OCLErrorKind::InvalidImageRegion => "The region is out of the bounds of the image",
+            OCLErrorKind::SvmNotSupported => {
+                "The device does not support the requested kind of shared virtual memory"
+            }
+            OCLErrorKind::SvmAllocFailed => "clSVMAlloc failed to allocate shared virtual memory",
+            OCLErrorKind::InvalidPatternSize => {
+                "The size of a fill pattern must be a power of two and at most 128 bytes"
+            }
             OCLErrorKind::GetDeviceInfo => "",
             OCLErrorKind::CreateContext => "",
             OCLErrorKind::CreateCommandQueue => "",
@@ -253,3 +356,97 @@ impl core::fmt::Display for OCLErrorKind {
 }
 
 impl std::error::Error for OCLErrorKind {}
+
+/// Returned by [`build_program`](super::build_program) if a program could not be built.
+#[derive(Debug, Clone)]
+pub struct BuildError {
+    pub kind: OCLErrorKind,
+    /// Device name and compiler build log for every device the program was built for.
+    pub logs: Vec<(String, String)>,
+    pub options: Option<String>,
+    /// [`source_hash`] of the program source. `None` if the program was not created from source.
+    pub source_hash: Option<u64>,
+}
+
+impl BuildError {
+    pub(crate) fn new(
+        kind: OCLErrorKind,
+        program: &Program,
+        devices: &[CLIntDevice],
+        options: Option<&str>,
+    ) -> BuildError {
+        // the log is collected on a best-effort basis, failing to query it must not hide the build error
+        let logs = get_program_build_info(ProgramBuildInfo::BuildLog, program, devices)
+            .map(|logs| {
+                devices
+                    .iter()
+                    .map(|device| {
+                        let name = device.get_name().unwrap_or_default();
+                        name.trim_end_matches('\0').to_string()
+                    })
+                    .zip(logs)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let source_hash = get_program_info(program, ProgramInfo::Source)
+            .ok()
+            .map(|src| String::from_utf8_lossy(src.strip_suffix(&[0]).unwrap_or(&src)).into_owned())
+            .filter(|src| !src.is_empty())
+            .map(|src| source_hash(&src));
+
+        BuildError {
+            kind,
+            logs,
+            options: options.map(ToString::to_string),
+            source_hash,
+        }
+    }
+}
+
+impl core::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} Could not build program", self.kind)?;
+        if let Some(hash) = self.source_hash {
+            write!(f, " (source hash: {hash:016x})")?;
+        }
+        if let Some(options) = &self.options {
+            write!(f, " with options \"{options}\"")?;
+        }
+        for (device, log) in self.logs.iter().filter(|(_, log)| !log.trim().is_empty()) {
+            write!(f, "\nBuild log of {device}:\n{}", log.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for BuildError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.kind)
+    }
+}
+
+/// Returned by [`Kernel::set_arg`](super::Kernel::set_arg) and [`Kernel::set_args`](super::Kernel::set_args) if an argument was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelArgError {
+    pub kind: OCLErrorKind,
+    /// Function name of the kernel
+    pub kernel: String,
+    pub index: usize,
+}
+
+impl core::fmt::Display for KernelArgError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} Could not set argument {} of kernel \"{}\"",
+            self.kind, self.index, self.kernel
+        )
+    }
+}
+
+impl std::error::Error for KernelArgError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.kind)
+    }
+}
@@ -13,6 +13,7 @@ pub type cl_mem = *mut c_void;
 pub type cl_program = *mut c_void;
 pub type cl_kernel = *mut c_void;
 pub type cl_event = *mut c_void;
+pub type cl_sampler = *mut c_void;
 
 pub type cl_int = i32;
 pub type cl_uint = u32;
@@ -31,6 +32,58 @@ pub type cl_program_info = cl_uint;
 pub type cl_kernel_work_group_info = cl_uint;
 pub type cl_program_build_info = cl_uint;
 pub type cl_map_flags = cl_bitfield;
+pub type cl_profiling_info = cl_uint;
+pub type cl_event_info = cl_uint;
+pub type cl_kernel_info = cl_uint;
+pub type cl_mem_info = cl_uint;
+pub type cl_context_info = cl_uint;
+pub type cl_buffer_create_type = cl_uint;
+pub type cl_mem_object_type = cl_uint;
+pub type cl_channel_order = cl_uint;
+pub type cl_channel_type = cl_uint;
+pub type cl_addressing_mode = cl_uint;
+pub type cl_filter_mode = cl_uint;
+pub type cl_svm_mem_flags = cl_bitfield;
+
+/// Called by the OpenCL implementation when the command of `event` reaches the registered execution status.
+/// `event_command_status` is negative if the command terminated abnormally.
+pub type cl_event_callback = unsafe extern "system" fn(
+    event: cl_event,
+    event_command_status: cl_int,
+    user_data: *mut c_void,
+);
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct cl_buffer_region {
+    pub origin: size_t,
+    pub size: size_t,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct cl_image_format {
+    pub image_channel_order: cl_channel_order,
+    pub image_channel_data_type: cl_channel_type,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct cl_image_desc {
+    pub image_type: cl_mem_object_type,
+    pub image_width: size_t,
+    pub image_height: size_t,
+    pub image_depth: size_t,
+    pub image_array_size: size_t,
+    pub image_row_pitch: size_t,
+    pub image_slice_pitch: size_t,
+    pub num_mip_levels: cl_uint,
+    pub num_samples: cl_uint,
+    pub buffer: cl_mem,
+}
+
+// cl_buffer_create_type:
+pub const CL_BUFFER_CREATE_TYPE_REGION: cl_buffer_create_type = 0x1220;
 
 // cl_kernel_work_group_info:
 pub const CL_KERNEL_WORK_GROUP_SIZE: cl_uint = 0x11B0;
@@ -40,10 +93,81 @@ pub const CL_KERNEL_PREFERRED_WORK_GROUP_SIZE_MULTIPLE: cl_uint = 0x11B3;
 pub const CL_KERNEL_PRIVATE_MEM_SIZE: cl_uint = 0x11B4;
 pub const CL_KERNEL_GLOBAL_WORK_SIZE: cl_uint = 0x11B5;
 
-#[cfg_attr(target_os = "macos", link(name = "OpenCL", kind = "framework"))]
-#[cfg_attr(target_os = "windows", link(name = "OpenCL"))]
-#[cfg_attr(not(target_os = "macos"), link(name = "OpenCL"))]
-extern "system" {
+// cl_command_queue_properties:
+pub const CL_QUEUE_OUT_OF_ORDER_EXEC_MODE_ENABLE: cl_command_queue_properties = 1 << 0;
+pub const CL_QUEUE_PROFILING_ENABLE: cl_command_queue_properties = 1 << 1;
+
+// cl_event_info:
+pub const CL_EVENT_COMMAND_QUEUE: cl_event_info = 0x11D0;
+pub const CL_EVENT_COMMAND_EXECUTION_STATUS: cl_event_info = 0x11D3;
+
+// command execution status:
+pub const CL_COMPLETE: cl_int = 0x0;
+pub const CL_RUNNING: cl_int = 0x1;
+pub const CL_SUBMITTED: cl_int = 0x2;
+pub const CL_QUEUED: cl_int = 0x3;
+
+// cl_profiling_info:
+pub const CL_PROFILING_COMMAND_QUEUED: cl_uint = 0x1280;
+pub const CL_PROFILING_COMMAND_SUBMIT: cl_uint = 0x1281;
+pub const CL_PROFILING_COMMAND_START: cl_uint = 0x1282;
+pub const CL_PROFILING_COMMAND_END: cl_uint = 0x1283;
+
+/// Declares the OpenCL entry points.
+///
+/// Without the `dynamic` feature, the functions are linked against the OpenCL library at build time.
+/// With `dynamic` (and always in unit tests), a [`Functions`] table is generated instead, which is filled at runtime
+/// by [`crate::api::load_library`] or the mock backend, and every `cl*` function dispatches through it.
+macro_rules! cl_functions {
+    ($(
+        pub fn $name:ident($($arg:ident: $ty:ty),* $(,)?) -> $ret:ty;
+    )*) => {
+        #[cfg(not(any(test, feature = "dynamic")))]
+        #[cfg_attr(target_os = "macos", link(name = "OpenCL", kind = "framework"))]
+        #[cfg_attr(target_os = "windows", link(name = "OpenCL"))]
+        #[cfg_attr(not(target_os = "macos"), link(name = "OpenCL"))]
+        #[allow(non_snake_case)]
+        extern "system" {
+            $(pub fn $name($($arg: $ty),*) -> $ret;)*
+        }
+
+        /// Table of OpenCL entry points resolved at runtime.
+        /// A field is `None` if the loaded library does not export the symbol.
+        #[cfg(any(test, feature = "dynamic"))]
+        #[allow(non_snake_case)]
+        pub struct Functions {
+            $(pub $name: Option<unsafe extern "system" fn($($ty),*) -> $ret>,)*
+        }
+
+        #[cfg(any(test, feature = "dynamic"))]
+        impl Functions {
+            /// # Safety
+            /// The library must be an OpenCL library whose exported symbols match the declared signatures.
+            pub(crate) unsafe fn load(library: &crate::api::Library) -> Functions {
+                Functions {
+                    $($name: core::mem::transmute::<
+                        *mut c_void,
+                        Option<unsafe extern "system" fn($($ty),*) -> $ret>,
+                    >(library.symbol(concat!(stringify!($name), "\0"))),)*
+                }
+            }
+        }
+
+        $(
+            #[cfg(any(test, feature = "dynamic"))]
+            #[allow(non_snake_case, clippy::too_many_arguments, clippy::missing_safety_doc)]
+            #[inline]
+            pub unsafe fn $name($($arg: $ty),*) -> $ret {
+                let function = crate::api::functions().$name.unwrap_or_else(|| {
+                    panic!("{} is not exported by the loaded OpenCL library", stringify!($name))
+                });
+                function($($arg),*)
+            }
+        )*
+    };
+}
+
+cl_functions! {
     pub fn clGetPlatformIDs(
         num_entries: cl_uint,
         platforms: *mut cl_platform_id,
@@ -83,8 +207,18 @@ extern "system" {
         errcode_ret: *mut cl_int,
     ) -> cl_context;
 
+    pub fn clRetainContext(context: cl_context) -> cl_int;
+
     pub fn clReleaseContext(context: cl_context) -> cl_int;
 
+    pub fn clGetContextInfo(
+        context: cl_context,
+        param_name: cl_context_info,
+        param_value_size: size_t,
+        param_value: *mut c_void,
+        param_value_size_ret: *mut size_t,
+    ) -> cl_int;
+
     pub fn clCreateCommandQueue(
         context: cl_context,
         device: cl_device_id,
@@ -92,14 +226,43 @@ extern "system" {
         errcode_ret: *mut cl_int,
     ) -> cl_command_queue;
 
+    pub fn clFlush(command_queue: cl_command_queue) -> cl_int;
+
     pub fn clFinish(command_queue: cl_command_queue) -> cl_int;
 
+    pub fn clRetainCommandQueue(command_queue: cl_command_queue) -> cl_int;
+
     pub fn clReleaseCommandQueue(command_queue: cl_command_queue) -> cl_int;
 
     pub fn clWaitForEvents(num_events: cl_uint, event_list: *const cl_event) -> cl_int;
 
+    pub fn clRetainEvent(event: cl_event) -> cl_int;
+
     pub fn clReleaseEvent(event: cl_event) -> cl_int;
 
+    pub fn clGetEventInfo(
+        event: cl_event,
+        param_name: cl_event_info,
+        param_value_size: size_t,
+        param_value: *mut c_void,
+        param_value_size_ret: *mut size_t,
+    ) -> cl_int;
+
+    pub fn clSetEventCallback(
+        event: cl_event,
+        command_exec_callback_type: cl_int,
+        pfn_notify: Option<cl_event_callback>,
+        user_data: *mut c_void,
+    ) -> cl_int;
+
+    pub fn clGetEventProfilingInfo(
+        event: cl_event,
+        param_name: cl_profiling_info,
+        param_value_size: size_t,
+        param_value: *mut c_void,
+        param_value_size_ret: *mut size_t,
+    ) -> cl_int;
+
     pub fn clCreateBuffer(
         context: cl_context,
         flags: cl_mem_flags,
@@ -108,6 +271,22 @@ extern "system" {
         errcode_ret: *mut cl_int,
     ) -> cl_mem;
 
+    pub fn clCreateSubBuffer(
+        buffer: cl_mem,
+        flags: cl_mem_flags,
+        buffer_create_type: cl_buffer_create_type,
+        buffer_create_info: *const c_void,
+        errcode_ret: *mut cl_int,
+    ) -> cl_mem;
+
+    pub fn clGetMemObjectInfo(
+        memobj: cl_mem,
+        param_name: cl_mem_info,
+        param_value_size: size_t,
+        param_value: *mut c_void,
+        param_value_size_ret: *mut size_t,
+    ) -> cl_int;
+
     pub fn clRetainMemObject(memobj: cl_mem) -> cl_int;
 
     pub fn clReleaseMemObject(memobj: cl_mem) -> cl_int;
@@ -161,6 +340,72 @@ extern "system" {
         errorcode_ret: *mut cl_int,
     ) -> *mut c_void;
 
+    pub fn clEnqueueReadBufferRect(
+        command_queue: cl_command_queue,
+        buffer: cl_mem,
+        blocking_read: cl_bool,
+        buffer_origin: *const size_t,
+        host_origin: *const size_t,
+        region: *const size_t,
+        buffer_row_pitch: size_t,
+        buffer_slice_pitch: size_t,
+        host_row_pitch: size_t,
+        host_slice_pitch: size_t,
+        ptr: *mut c_void,
+        num_events_in_wait_list: cl_uint,
+        event_wait_list: *const cl_event,
+        event: *mut cl_event,
+    ) -> cl_int;
+
+    pub fn clEnqueueWriteBufferRect(
+        command_queue: cl_command_queue,
+        buffer: cl_mem,
+        blocking_write: cl_bool,
+        buffer_origin: *const size_t,
+        host_origin: *const size_t,
+        region: *const size_t,
+        buffer_row_pitch: size_t,
+        buffer_slice_pitch: size_t,
+        host_row_pitch: size_t,
+        host_slice_pitch: size_t,
+        ptr: *const c_void,
+        num_events_in_wait_list: cl_uint,
+        event_wait_list: *const cl_event,
+        event: *mut cl_event,
+    ) -> cl_int;
+
+    pub fn clEnqueueCopyBufferRect(
+        command_queue: cl_command_queue,
+        src_buffer: cl_mem,
+        dst_buffer: cl_mem,
+        src_origin: *const size_t,
+        dst_origin: *const size_t,
+        region: *const size_t,
+        src_row_pitch: size_t,
+        src_slice_pitch: size_t,
+        dst_row_pitch: size_t,
+        dst_slice_pitch: size_t,
+        num_events_in_wait_list: cl_uint,
+        event_wait_list: *const cl_event,
+        event: *mut cl_event,
+    ) -> cl_int;
+
+    pub fn clEnqueueUnmapMemObject(
+        command_queue: cl_command_queue,
+        memobj: cl_mem,
+        mapped_ptr: *mut c_void,
+        num_events_in_wait_list: cl_uint,
+        event_wait_list: *const cl_event,
+        event: *mut cl_event,
+    ) -> cl_int;
+
+    pub fn clEnqueueBarrierWithWaitList(
+        command_queue: cl_command_queue,
+        num_events_in_wait_list: cl_uint,
+        event_wait_list: *const cl_event,
+        event: *mut cl_event,
+    ) -> cl_int;
+
     pub fn clEnqueueFillBuffer(
         command_queue: cl_command_queue,
         buffer: cl_mem,
@@ -173,6 +418,150 @@ extern "system" {
         event: *mut cl_event,
     ) -> cl_int;
 
+    pub fn clGetSupportedImageFormats(
+        context: cl_context,
+        flags: cl_mem_flags,
+        image_type: cl_mem_object_type,
+        num_entries: cl_uint,
+        image_formats: *mut cl_image_format,
+        num_image_formats: *mut cl_uint,
+    ) -> cl_int;
+
+    pub fn clCreateImage(
+        context: cl_context,
+        flags: cl_mem_flags,
+        image_format: *const cl_image_format,
+        image_desc: *const cl_image_desc,
+        host_ptr: *mut c_void,
+        errcode_ret: *mut cl_int,
+    ) -> cl_mem;
+
+    pub fn clEnqueueReadImage(
+        command_queue: cl_command_queue,
+        image: cl_mem,
+        blocking_read: cl_bool,
+        origin: *const size_t,
+        region: *const size_t,
+        row_pitch: size_t,
+        slice_pitch: size_t,
+        ptr: *mut c_void,
+        num_events_in_wait_list: cl_uint,
+        event_wait_list: *const cl_event,
+        event: *mut cl_event,
+    ) -> cl_int;
+
+    pub fn clEnqueueWriteImage(
+        command_queue: cl_command_queue,
+        image: cl_mem,
+        blocking_write: cl_bool,
+        origin: *const size_t,
+        region: *const size_t,
+        input_row_pitch: size_t,
+        input_slice_pitch: size_t,
+        ptr: *const c_void,
+        num_events_in_wait_list: cl_uint,
+        event_wait_list: *const cl_event,
+        event: *mut cl_event,
+    ) -> cl_int;
+
+    pub fn clEnqueueCopyImage(
+        command_queue: cl_command_queue,
+        src_image: cl_mem,
+        dst_image: cl_mem,
+        src_origin: *const size_t,
+        dst_origin: *const size_t,
+        region: *const size_t,
+        num_events_in_wait_list: cl_uint,
+        event_wait_list: *const cl_event,
+        event: *mut cl_event,
+    ) -> cl_int;
+
+    pub fn clEnqueueCopyImageToBuffer(
+        command_queue: cl_command_queue,
+        src_image: cl_mem,
+        dst_buffer: cl_mem,
+        src_origin: *const size_t,
+        region: *const size_t,
+        dst_offset: size_t,
+        num_events_in_wait_list: cl_uint,
+        event_wait_list: *const cl_event,
+        event: *mut cl_event,
+    ) -> cl_int;
+
+    pub fn clEnqueueCopyBufferToImage(
+        command_queue: cl_command_queue,
+        src_buffer: cl_mem,
+        dst_image: cl_mem,
+        src_offset: size_t,
+        dst_origin: *const size_t,
+        region: *const size_t,
+        num_events_in_wait_list: cl_uint,
+        event_wait_list: *const cl_event,
+        event: *mut cl_event,
+    ) -> cl_int;
+
+    pub fn clCreateSampler(
+        context: cl_context,
+        normalized_coords: cl_bool,
+        addressing_mode: cl_addressing_mode,
+        filter_mode: cl_filter_mode,
+        errcode_ret: *mut cl_int,
+    ) -> cl_sampler;
+
+    pub fn clRetainSampler(sampler: cl_sampler) -> cl_int;
+
+    pub fn clReleaseSampler(sampler: cl_sampler) -> cl_int;
+
+    pub fn clSVMAlloc(
+        context: cl_context,
+        flags: cl_svm_mem_flags,
+        size: size_t,
+        alignment: cl_uint,
+    ) -> *mut c_void;
+
+    pub fn clSVMFree(context: cl_context, svm_pointer: *mut c_void) -> ();
+
+    pub fn clEnqueueSVMMap(
+        command_queue: cl_command_queue,
+        blocking_map: cl_bool,
+        flags: cl_map_flags,
+        svm_ptr: *mut c_void,
+        size: size_t,
+        num_events_in_wait_list: cl_uint,
+        event_wait_list: *const cl_event,
+        event: *mut cl_event,
+    ) -> cl_int;
+
+    pub fn clEnqueueSVMUnmap(
+        command_queue: cl_command_queue,
+        svm_ptr: *mut c_void,
+        num_events_in_wait_list: cl_uint,
+        event_wait_list: *const cl_event,
+        event: *mut cl_event,
+    ) -> cl_int;
+
+    pub fn clEnqueueSVMMemcpy(
+        command_queue: cl_command_queue,
+        blocking_copy: cl_bool,
+        dst_ptr: *mut c_void,
+        src_ptr: *const c_void,
+        size: size_t,
+        num_events_in_wait_list: cl_uint,
+        event_wait_list: *const cl_event,
+        event: *mut cl_event,
+    ) -> cl_int;
+
+    pub fn clEnqueueSVMMemFill(
+        command_queue: cl_command_queue,
+        svm_ptr: *mut c_void,
+        pattern: *const c_void,
+        pattern_size: size_t,
+        size: size_t,
+        num_events_in_wait_list: cl_uint,
+        event_wait_list: *const cl_event,
+        event: *mut cl_event,
+    ) -> cl_int;
+
     pub fn clReleaseProgram(program: cl_program) -> cl_int;
 
     pub fn clGetProgramInfo(
@@ -223,6 +612,14 @@ extern "system" {
     ) -> cl_int;
     pub fn clReleaseKernel(kernel: cl_kernel) -> cl_int;
 
+    pub fn clGetKernelInfo(
+        kernel: cl_kernel,
+        param_name: cl_kernel_info,
+        param_value_size: size_t,
+        param_value: *mut c_void,
+        param_value_size_ret: *mut size_t,
+    ) -> cl_int;
+
     pub fn clSetKernelArg(
         kernel: cl_kernel,
         arg_index: cl_uint,
@@ -230,6 +627,12 @@ extern "system" {
         arg_value: *const c_void,
     ) -> cl_int;
 
+    pub fn clSetKernelArgSVMPointer(
+        kernel: cl_kernel,
+        arg_index: cl_uint,
+        arg_value: *const c_void,
+    ) -> cl_int;
+
     pub fn clGetKernelWorkGroupInfo(
         kernel: cl_kernel,
         device: cl_device_id,
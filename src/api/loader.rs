@@ -0,0 +1,222 @@
+use std::{
+    ffi::{c_void, CString},
+    fmt::Display,
+    os::raw::c_char,
+    sync::{Mutex, OnceLock},
+};
+
+use crate::Error;
+
+use super::ffi::Functions;
+
+/// Environment variable that, if set, is used as the path of the OpenCL library instead of the default names.
+pub const LIBRARY_ENV_VAR: &str = "MIN_CL_LIBRARY";
+
+#[cfg(target_os = "windows")]
+const DEFAULT_LIBRARY_NAMES: &[&str] = &["OpenCL.dll"];
+
+#[cfg(target_os = "macos")]
+const DEFAULT_LIBRARY_NAMES: &[&str] = &["/System/Library/Frameworks/OpenCL.framework/OpenCL"];
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const DEFAULT_LIBRARY_NAMES: &[&str] = &["libOpenCL.so.1", "libOpenCL.so"];
+
+static FUNCTIONS: OnceLock<Functions> = OnceLock::new();
+static LOAD_LOCK: Mutex<()> = Mutex::new(());
+
+/// Handle of a dynamically loaded library.
+/// The library is never unloaded, as the resolved OpenCL entry points are stored globally.
+pub(crate) struct Library(*mut c_void);
+
+impl Library {
+    pub fn open(path: &str) -> Result<Library, String> {
+        let c_path = CString::new(path).map_err(|err| err.to_string())?;
+        let handle = unsafe { sys::open(c_path.as_ptr()) };
+
+        if handle.is_null() {
+            return Err(unsafe { sys::last_error() });
+        }
+        Ok(Library(handle))
+    }
+
+    /// Returns a null pointer if the symbol is not found.
+    /// `name` must be nul-terminated.
+    pub fn symbol(&self, name: &str) -> *mut c_void {
+        debug_assert!(name.ends_with('\0'));
+        unsafe { sys::symbol(self.0, name.as_ptr() as *const c_char) }
+    }
+}
+
+/// Returned if no OpenCL library could be loaded.
+/// Contains every library path that was tried together with the reason it failed.
+#[derive(Debug, Clone)]
+pub struct LibraryLoadError {
+    pub tried: Vec<(String, String)>,
+}
+
+impl Display for LibraryLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Could not load the OpenCL library")?;
+        for (path, reason) in &self.tried {
+            write!(f, "\n    {path}: {reason}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for LibraryLoadError {}
+
+fn load_from(paths: &[&str]) -> Result<Functions, Error> {
+    let mut tried = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        match Library::open(path) {
+            Ok(library) => {
+                let functions = unsafe { Functions::load(&library) };
+
+                // a library without clGetPlatformIDs is not an OpenCL library
+                if functions.clGetPlatformIDs.is_some() {
+                    return Ok(functions);
+                }
+                tried.push((path.to_string(), "clGetPlatformIDs not found".into()));
+            }
+            Err(reason) => tried.push((path.to_string(), reason)),
+        }
+    }
+
+    Err(LibraryLoadError { tried }.into())
+}
+
+fn init(paths: &[&str]) -> Result<&'static Functions, Error> {
+    if let Some(functions) = FUNCTIONS.get() {
+        return Ok(functions);
+    }
+
+    let _guard = LOAD_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    if let Some(functions) = FUNCTIONS.get() {
+        return Ok(functions);
+    }
+    let functions = load_from(paths)?;
+    Ok(FUNCTIONS.get_or_init(|| functions))
+}
+
+/// Installs a custom function table, e.g. the mock backend.
+/// Returns `false` if another table was already installed or loaded.
+#[cfg(any(test, feature = "mock"))]
+pub(crate) fn install(functions: Functions) -> bool {
+    let _guard = LOAD_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    FUNCTIONS.set(functions).is_ok()
+}
+
+/// Loads the OpenCL library from the given path.
+/// Has no effect if an OpenCL library was already loaded.
+pub fn load_library(path: &str) -> Result<(), Error> {
+    init(&[path]).map(|_| ())
+}
+
+/// Loads the OpenCL library from the path in [`LIBRARY_ENV_VAR`] or, if unset, from the default ICD loader names.
+/// Has no effect if an OpenCL library was already loaded.
+pub fn load_default_library() -> Result<(), Error> {
+    try_functions().map(|_| ())
+}
+
+/// Checks whether an OpenCL library is loaded or can be loaded from the default location.
+pub fn is_available() -> bool {
+    load_default_library().is_ok()
+}
+
+pub(crate) fn try_functions() -> Result<&'static Functions, Error> {
+    if let Some(functions) = FUNCTIONS.get() {
+        return Ok(functions);
+    }
+
+    match std::env::var(LIBRARY_ENV_VAR) {
+        Ok(path) => init(&[&path]),
+        Err(_) => init(DEFAULT_LIBRARY_NAMES),
+    }
+}
+
+pub(crate) fn functions() -> &'static Functions {
+    try_functions().unwrap_or_else(|err| panic!("{err}"))
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::{
+        ffi::{c_void, CStr},
+        os::raw::{c_char, c_int},
+    };
+
+    const RTLD_NOW: c_int = 2;
+
+    #[cfg_attr(any(target_os = "linux", target_os = "android"), link(name = "dl"))]
+    extern "C" {
+        fn dlopen(filename: *const c_char, flag: c_int) -> *mut c_void;
+        fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+        fn dlerror() -> *const c_char;
+    }
+
+    pub unsafe fn open(path: *const c_char) -> *mut c_void {
+        dlopen(path, RTLD_NOW)
+    }
+
+    pub unsafe fn symbol(handle: *mut c_void, name: *const c_char) -> *mut c_void {
+        dlsym(handle, name)
+    }
+
+    pub unsafe fn last_error() -> String {
+        let err = dlerror();
+        if err.is_null() {
+            return "unknown error".into();
+        }
+        CStr::from_ptr(err).to_string_lossy().into_owned()
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::{ffi::c_void, os::raw::c_char};
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn LoadLibraryA(filename: *const c_char) -> *mut c_void;
+        fn GetProcAddress(module: *mut c_void, name: *const c_char) -> *mut c_void;
+    }
+
+    pub unsafe fn open(path: *const c_char) -> *mut c_void {
+        LoadLibraryA(path)
+    }
+
+    pub unsafe fn symbol(handle: *mut c_void, name: *const c_char) -> *mut c_void {
+        GetProcAddress(handle, name)
+    }
+
+    pub unsafe fn last_error() -> String {
+        std::io::Error::last_os_error().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load_from, Library};
+
+    #[test]
+    fn test_open_missing_library() {
+        assert!(Library::open("libmin-cl-does-not-exist.so").is_err());
+    }
+
+    #[test]
+    fn test_load_error_lists_tried_paths() {
+        let err = load_from(&["libmin-cl-missing-a.so", "libmin-cl-missing-b.so"])
+            .err()
+            .unwrap();
+        let msg = err.to_string();
+        assert!(msg.contains("libmin-cl-missing-a.so"));
+        assert!(msg.contains("libmin-cl-missing-b.so"));
+    }
+}
@@ -0,0 +1,2939 @@
+//! In-process software implementation of the OpenCL API for testing without a device.
+//!
+//! After [`install`], every `cl*` call of this crate is served by the mock backend:
+//! buffers live in host memory, enqueued commands complete immediately and are recorded per command queue,
+//! and errors can be injected with [`fail_next`].
+//! Kernels are not compiled; a host implementation can be attached by name with [`register_kernel`].
+
+#![allow(non_snake_case)]
+
+use std::{
+    alloc::Layout,
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{c_void, CStr},
+    os::raw::c_char,
+    sync::{Arc, Mutex, MutexGuard, OnceLock},
+};
+
+use crate::Error;
+
+use super::{ffi::*, loader, OCLErrorKind};
+
+const PLATFORM: usize = 0x10;
+const DEVICE: usize = 0x20;
+
+/// Prefix of program binaries produced by the mock backend. The remainder of the binary is the program source.
+pub const MOCK_BINARY_MAGIC: &[u8] = b"min-cl mock binary\n";
+
+const MAX_MEM_ALLOC_SIZE: u64 = 256 * 1024 * 1024;
+const GLOBAL_MEM_SIZE: u64 = 1024 * 1024 * 1024;
+const MAX_WORK_GROUP_SIZE: usize = 256;
+const PREFERRED_WORK_GROUP_SIZE_MULTIPLE: usize = 32;
+const LOCAL_MEM_SIZE: u64 = 32 * 1024;
+/// in bits
+const MEM_BASE_ADDR_ALIGN: cl_uint = 1024;
+const IMAGE2D_MAX_SIZE: usize = 8192;
+const IMAGE3D_MAX_SIZE: usize = 2048;
+const IMAGE_MAX_ARRAY_SIZE: usize = 2048;
+/// Coarse-grain and fine-grain buffers with atomics
+const SVM_CAPABILITIES: u64 = 0b1011;
+/// in bytes
+const SVM_DEFAULT_ALIGNMENT: usize = 128;
+
+/// Channel order, channel type and pixel size of the supported image formats
+const IMAGE_FORMATS: [(cl_channel_order, cl_channel_type, usize); 7] = [
+    // RGBA: unorm int8, unsigned int8, half float, float
+    (0x10B5, 0x10D2, 4),
+    (0x10B5, 0x10DA, 4),
+    (0x10B5, 0x10DD, 8),
+    (0x10B5, 0x10DE, 16),
+    // BGRA: unorm int8
+    (0x10B6, 0x10D2, 4),
+    // R: unsigned int8, float
+    (0x10B0, 0x10DA, 1),
+    (0x10B0, 0x10DE, 4),
+];
+
+const SUCCESS: cl_int = 0;
+const DEVICE_NOT_FOUND: cl_int = -1;
+const PROFILING_INFO_NOT_AVAILABLE: cl_int = -7;
+const MEM_COPY_OVERLAP: cl_int = -8;
+const IMAGE_FORMAT_MISMATCH: cl_int = -9;
+const IMAGE_FORMAT_NOT_SUPPORTED: cl_int = -10;
+const MISALIGNED_SUB_BUFFER_OFFSET: cl_int = -13;
+const BUILD_PROGRAM_FAILURE: cl_int = -11;
+const INVALID_VALUE: cl_int = -30;
+const INVALID_PLATFORM: cl_int = -32;
+const INVALID_DEVICE: cl_int = -33;
+const INVALID_CONTEXT: cl_int = -34;
+const INVALID_QUEUE_PROPERTIES: cl_int = -35;
+const INVALID_COMMAND_QUEUE: cl_int = -36;
+const INVALID_HOST_PTR: cl_int = -37;
+const INVALID_MEM_OBJECT: cl_int = -38;
+const INVALID_IMAGE_FORMAT_DESCRIPTOR: cl_int = -39;
+const INVALID_IMAGE_SIZE: cl_int = -40;
+const INVALID_SAMPLER: cl_int = -41;
+const INVALID_BINARY: cl_int = -42;
+const INVALID_PROGRAM: cl_int = -44;
+const INVALID_PROGRAM_EXECUTABLE: cl_int = -45;
+const INVALID_KERNEL_NAME: cl_int = -46;
+const INVALID_KERNEL: cl_int = -48;
+const INVALID_ARG_INDEX: cl_int = -49;
+const INVALID_ARG_VALUE: cl_int = -50;
+const INVALID_ARG_SIZE: cl_int = -51;
+const INVALID_KERNEL_ARGS: cl_int = -52;
+const INVALID_WORK_DIMENSION: cl_int = -53;
+const INVALID_WORK_GROUP_SIZE: cl_int = -54;
+const INVALID_EVENT_WAIT_LIST: cl_int = -57;
+const INVALID_EVENT: cl_int = -58;
+const INVALID_BUFFER_SIZE: cl_int = -61;
+const INVALID_GLOBAL_WORK_SIZE: cl_int = -63;
+const INVALID_IMAGE_DESCRIPTOR: cl_int = -65;
+
+const BUILD_SUCCESS: cl_int = 0;
+const BUILD_NONE: cl_int = -1;
+const BUILD_ERROR: cl_int = -2;
+
+const MEM_USE_HOST_PTR: cl_mem_flags = 1 << 3;
+const MEM_COPY_HOST_PTR: cl_mem_flags = 1 << 5;
+const MEM_SVM_FINE_GRAIN_BUFFER: cl_svm_mem_flags = 1 << 10;
+const MEM_SVM_ATOMICS: cl_svm_mem_flags = 1 << 11;
+
+const MEM_OBJECT_IMAGE2D: cl_mem_object_type = 0x10F1;
+const MEM_OBJECT_IMAGE3D: cl_mem_object_type = 0x10F2;
+const MEM_OBJECT_IMAGE2D_ARRAY: cl_mem_object_type = 0x10F3;
+
+/// A command that was enqueued on a mock command queue.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockCommand {
+    ReadBuffer {
+        buffer: cl_mem,
+        offset: usize,
+        size: usize,
+    },
+    WriteBuffer {
+        buffer: cl_mem,
+        offset: usize,
+        size: usize,
+    },
+    CopyBuffer {
+        src: cl_mem,
+        dst: cl_mem,
+        src_offset: usize,
+        dst_offset: usize,
+        size: usize,
+    },
+    FillBuffer {
+        buffer: cl_mem,
+        pattern: Vec<u8>,
+        offset: usize,
+        size: usize,
+    },
+    ReadBufferRect {
+        buffer: cl_mem,
+        buffer_origin: [usize; 3],
+        region: [usize; 3],
+    },
+    WriteBufferRect {
+        buffer: cl_mem,
+        buffer_origin: [usize; 3],
+        region: [usize; 3],
+    },
+    CopyBufferRect {
+        src: cl_mem,
+        dst: cl_mem,
+        src_origin: [usize; 3],
+        dst_origin: [usize; 3],
+        region: [usize; 3],
+    },
+    /// Image origins and regions are in pixels.
+    ReadImage {
+        image: cl_mem,
+        origin: [usize; 3],
+        region: [usize; 3],
+    },
+    WriteImage {
+        image: cl_mem,
+        origin: [usize; 3],
+        region: [usize; 3],
+    },
+    CopyImage {
+        src: cl_mem,
+        dst: cl_mem,
+        src_origin: [usize; 3],
+        dst_origin: [usize; 3],
+        region: [usize; 3],
+    },
+    CopyImageToBuffer {
+        src: cl_mem,
+        dst: cl_mem,
+        src_origin: [usize; 3],
+        region: [usize; 3],
+        dst_offset: usize,
+    },
+    CopyBufferToImage {
+        src: cl_mem,
+        dst: cl_mem,
+        src_offset: usize,
+        dst_origin: [usize; 3],
+        region: [usize; 3],
+    },
+    /// SVM pointers are host addresses.
+    SvmMap {
+        ptr: *mut c_void,
+        flags: cl_map_flags,
+        size: usize,
+    },
+    SvmUnmap {
+        ptr: *mut c_void,
+    },
+    SvmMemcpy {
+        dst: *mut c_void,
+        src: *const c_void,
+        size: usize,
+    },
+    SvmMemFill {
+        ptr: *mut c_void,
+        pattern: Vec<u8>,
+        size: usize,
+    },
+    MapBuffer {
+        buffer: cl_mem,
+        flags: cl_map_flags,
+        offset: usize,
+        size: usize,
+    },
+    UnmapMemObject {
+        buffer: cl_mem,
+        offset: usize,
+    },
+    /// Waits for the commands in the wait list, or for all previous commands if the wait list is empty.
+    Barrier,
+    NDRangeKernel {
+        kernel: String,
+        global_work_size: Vec<usize>,
+        local_work_size: Option<Vec<usize>>,
+        global_work_offset: Option<Vec<usize>>,
+    },
+}
+
+/// An enqueued command together with the event it signaled and the events it waited for.
+/// All sizes and offsets are in bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockRecord {
+    pub command: MockCommand,
+    pub event: cl_event,
+    pub wait_list: Vec<cl_event>,
+}
+
+type KernelFn = Arc<dyn Fn(&mut MockLaunch) + Send + Sync>;
+
+struct MockProgram {
+    context: usize,
+    source: String,
+    options: String,
+    status: cl_int,
+    log: String,
+    kernels: Vec<KernelDecl>,
+}
+
+/// A kernel as declared in the program source.
+#[derive(Clone)]
+struct KernelDecl {
+    name: String,
+    params: Vec<MockParam>,
+    /// `__attribute__((reqd_work_group_size(X, Y, Z)))`, `[0; 3]` if not specified
+    reqd_work_group_size: [usize; 3],
+}
+
+struct MockKernel {
+    name: String,
+    params: Vec<MockParam>,
+    reqd_work_group_size: [usize; 3],
+    args: Vec<Option<Vec<u8>>>,
+    /// Size of the `__local` arguments in bytes, `0` for other parameters.
+    local_mem: Vec<usize>,
+}
+
+impl MockKernel {
+    fn new(decl: KernelDecl) -> MockKernel {
+        MockKernel {
+            name: decl.name,
+            args: vec![None; decl.params.len()],
+            local_mem: vec![0; decl.params.len()],
+            params: decl.params,
+            reqd_work_group_size: decl.reqd_work_group_size,
+        }
+    }
+}
+
+/// A kernel parameter as declared in the program source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct MockParam {
+    local: bool,
+    /// Expected argument size in bytes, `None` if the type is unknown to the mock backend.
+    size: Option<usize>,
+}
+
+struct MockImage {
+    context: usize,
+    format: cl_image_format,
+    pixel_size: usize,
+    /// Width, height and depth or array size in pixels, rows and slices are tightly packed.
+    size: [usize; 3],
+    data: Box<[u8]>,
+}
+
+struct MockQueue {
+    properties: cl_command_queue_properties,
+    records: Vec<MockRecord>,
+    /// Number of `clFlush` calls
+    flushes: usize,
+    /// Commands complete when the queue is flushed instead of when they are enqueued, see [`defer_completion`].
+    deferred: bool,
+}
+
+enum Object {
+    Context,
+    Queue(MockQueue),
+    Mem {
+        context: usize,
+        data: Box<[u8]>,
+    },
+    /// A region of a `Mem` object, which is retained by the sub-buffer.
+    SubBuffer {
+        context: usize,
+        parent: usize,
+        offset: usize,
+        size: usize,
+    },
+    Image(MockImage),
+    Sampler,
+    Program(MockProgram),
+    Kernel(MockKernel),
+    /// Holds the queued, submit, start and end timestamps if the queue has profiling enabled.
+    Event {
+        queue: usize,
+        profiling: Option<[u64; 4]>,
+        complete: bool,
+        /// Callbacks registered with `clSetEventCallback` before the command completed
+        callbacks: Vec<(cl_event_callback, usize)>,
+    },
+}
+
+struct Entry {
+    refs: usize,
+    object: Object,
+}
+
+#[derive(Default)]
+struct State {
+    next: usize,
+    /// Device time in nanoseconds, advanced by every command.
+    clock: u64,
+    objects: HashMap<usize, Entry>,
+    /// SVM allocations by address, with their context and layout.
+    svm: HashMap<usize, (usize, Layout)>,
+    kernel_fns: HashMap<String, KernelFn>,
+    /// Callbacks of completed commands with their event and user data, called by [`run_callbacks`].
+    ready_callbacks: Vec<(cl_event_callback, cl_event, usize)>,
+}
+
+// the raw handles stored in records are ids, they are never dereferenced
+unsafe impl Send for State {}
+
+impl State {
+    fn insert(&mut self, object: Object) -> usize {
+        self.next += 1;
+        let handle = 0x1000 + self.next * 0x10;
+        self.objects.insert(handle, Entry { refs: 1, object });
+        handle
+    }
+
+    fn get(&mut self, handle: *mut c_void) -> Option<&mut Object> {
+        self.objects
+            .get_mut(&(handle as usize))
+            .map(|entry| &mut entry.object)
+    }
+
+    fn retain(&mut self, handle: *mut c_void) {
+        if let Some(entry) = self.objects.get_mut(&(handle as usize)) {
+            entry.refs += 1;
+        }
+    }
+
+    fn release(&mut self, handle: *mut c_void) {
+        if let Some(entry) = self.objects.get_mut(&(handle as usize)) {
+            entry.refs -= 1;
+            if entry.refs == 0 {
+                if let Some(Entry {
+                    object: Object::SubBuffer { parent, .. },
+                    ..
+                }) = self.objects.remove(&(handle as usize))
+                {
+                    self.release(parent as *mut c_void);
+                }
+            }
+        }
+    }
+
+    /// Completes the commands of `queue`. Their callbacks are called by [`run_callbacks`].
+    fn complete(&mut self, queue: usize) {
+        for (&handle, entry) in &mut self.objects {
+            if let Object::Event {
+                queue: event_queue,
+                complete,
+                callbacks,
+                ..
+            } = &mut entry.object
+            {
+                if *event_queue == queue && !*complete {
+                    *complete = true;
+                    self.ready_callbacks.extend(
+                        callbacks
+                            .drain(..)
+                            .map(|(callback, user_data)| (callback, handle as cl_event, user_data)),
+                    );
+                }
+            }
+        }
+    }
+
+    fn check(
+        &mut self,
+        handle: *mut c_void,
+        is: fn(&Object) -> bool,
+        err: cl_int,
+    ) -> Result<(), cl_int> {
+        match self.get(handle) {
+            Some(object) if is(object) => Ok(()),
+            _ => Err(err),
+        }
+    }
+
+    fn context(&mut self, context: cl_context) -> Result<(), cl_int> {
+        self.check(context, |o| matches!(o, Object::Context), INVALID_CONTEXT)
+    }
+
+    fn queue(&mut self, queue: cl_command_queue) -> Result<&mut MockQueue, cl_int> {
+        match self.get(queue) {
+            Some(Object::Queue(queue)) => Ok(queue),
+            _ => Err(INVALID_COMMAND_QUEUE),
+        }
+    }
+
+    fn mem(&mut self, mem: cl_mem) -> Result<&mut [u8], cl_int> {
+        if let Some(&mut Object::SubBuffer {
+            parent,
+            offset,
+            size,
+            ..
+        }) = self.get(mem)
+        {
+            return Ok(&mut self.mem(parent as cl_mem)?[offset..offset + size]);
+        }
+        match self.get(mem) {
+            Some(Object::Mem { data, .. }) => Ok(data),
+            Some(Object::Image(image)) => Ok(&mut image.data),
+            _ => Err(INVALID_MEM_OBJECT),
+        }
+    }
+
+    fn image(&mut self, image: cl_mem) -> Result<&mut MockImage, cl_int> {
+        match self.get(image) {
+            Some(Object::Image(image)) => Ok(image),
+            _ => Err(INVALID_MEM_OBJECT),
+        }
+    }
+
+    /// Checks that `size` bytes at `ptr` lie within one SVM allocation.
+    fn svm_range(&self, ptr: *const c_void, size: usize) -> Result<(), cl_int> {
+        let ptr = ptr as usize;
+        let inside = self.svm.iter().any(|(&start, (_, layout))| {
+            ptr >= start && check_range(layout.size(), ptr - start, size).is_ok()
+        });
+        if !inside {
+            return Err(INVALID_VALUE);
+        }
+        Ok(())
+    }
+
+    fn program(&mut self, program: cl_program) -> Result<&mut MockProgram, cl_int> {
+        match self.get(program) {
+            Some(Object::Program(program)) => Ok(program),
+            _ => Err(INVALID_PROGRAM),
+        }
+    }
+
+    fn kernel(&mut self, kernel: cl_kernel) -> Result<&mut MockKernel, cl_int> {
+        match self.get(kernel) {
+            Some(Object::Kernel(kernel)) => Ok(kernel),
+            _ => Err(INVALID_KERNEL),
+        }
+    }
+
+    unsafe fn wait_list(
+        &mut self,
+        num_events: cl_uint,
+        events: *const cl_event,
+    ) -> Result<Vec<cl_event>, cl_int> {
+        if (num_events == 0) != events.is_null() {
+            return Err(INVALID_EVENT_WAIT_LIST);
+        }
+        if num_events == 0 {
+            return Ok(Vec::new());
+        }
+        let events = std::slice::from_raw_parts(events, num_events as usize).to_vec();
+        for &event in &events {
+            self.check(
+                event,
+                |o| matches!(o, Object::Event { .. }),
+                INVALID_EVENT_WAIT_LIST,
+            )?;
+        }
+        Ok(events)
+    }
+
+    /// Records a (completed) command on the queue and hands out its event.
+    unsafe fn record(
+        &mut self,
+        queue: cl_command_queue,
+        wait_list: Vec<cl_event>,
+        command: MockCommand,
+        event: *mut cl_event,
+    ) -> Result<(), cl_int> {
+        let profiling = self.queue(queue)?.properties & CL_QUEUE_PROFILING_ENABLE != 0;
+        let deferred = self.queue(queue)?.deferred;
+        let start = self.clock;
+        self.clock += 1000;
+        let timestamps = [start, start + 10, start + 20, self.clock];
+
+        let handle = self.insert(Object::Event {
+            queue: queue as usize,
+            profiling: profiling.then_some(timestamps),
+            complete: !deferred,
+            callbacks: Vec::new(),
+        }) as cl_event;
+        self.queue(queue)?.records.push(MockRecord {
+            command,
+            event: handle,
+            wait_list,
+        });
+
+        if event.is_null() {
+            self.release(handle);
+        } else {
+            *event = handle;
+        }
+        Ok(())
+    }
+}
+
+fn state() -> MutexGuard<'static, State> {
+    static STATE: OnceLock<Mutex<State>> = OnceLock::new();
+    STATE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+thread_local! {
+    static INJECTED: RefCell<Vec<(&'static str, cl_int)>> = const { RefCell::new(Vec::new()) };
+    static TRUNCATED: RefCell<Vec<(&'static str, usize)>> = const { RefCell::new(Vec::new()) };
+}
+
+fn take_injected(function: &str) -> Option<cl_int> {
+    INJECTED.with(|injected| {
+        let mut injected = injected.borrow_mut();
+        let idx = injected.iter().position(|(name, _)| *name == function)?;
+        Some(injected.remove(idx).1)
+    })
+}
+
+/// Routes all OpenCL calls of this crate to the mock backend.
+/// Fails if a real OpenCL library was loaded before.
+pub fn install() -> Result<(), Error> {
+    static INSTALLED: OnceLock<bool> = OnceLock::new();
+
+    if *INSTALLED.get_or_init(|| loader::install(functions())) {
+        Ok(())
+    } else {
+        Err("Cannot install the mock backend, an OpenCL library is already loaded".into())
+    }
+}
+
+/// The next call of `function` (e.g. `"clCreateBuffer"`) on the current thread fails with `error`.
+///
+/// # Panics
+/// If `error` does not correspond to an OpenCL error code.
+pub fn fail_next(function: &'static str, error: OCLErrorKind) {
+    let code = error
+        .value()
+        .unwrap_or_else(|| panic!("{error:?} is not an OpenCL error code"));
+    INJECTED.with(|injected| injected.borrow_mut().push((function, code)));
+}
+
+/// Replies of `function` (e.g. `"clGetKernelWorkGroupInfo"`) on the current thread are cut to `len` bytes,
+/// like the replies of a broken driver. `None` restores the complete replies.
+pub fn truncate_info(function: &'static str, len: Option<usize>) {
+    TRUNCATED.with(|truncated| {
+        let mut truncated = truncated.borrow_mut();
+        truncated.retain(|(name, _)| *name != function);
+        truncated.extend(len.map(|len| (function, len)));
+    });
+}
+
+fn truncated<'a>(function: &str, value: &'a [u8]) -> &'a [u8] {
+    TRUNCATED.with(|truncated| {
+        match truncated
+            .borrow()
+            .iter()
+            .find(|(name, _)| *name == function)
+        {
+            Some(&(_, len)) => &value[..len.min(value.len())],
+            None => value,
+        }
+    })
+}
+
+/// Returns all commands that were enqueued on `queue` so far.
+pub fn records(queue: cl_command_queue) -> Vec<MockRecord> {
+    state()
+        .queue(queue)
+        .map(|queue| queue.records.clone())
+        .unwrap_or_default()
+}
+
+/// Commands enqueued on `queue` from now on complete when the queue is flushed or finished, or when their event is waited for,
+/// instead of when they are enqueued. Their results are still written immediately.
+pub fn defer_completion(queue: cl_command_queue) {
+    if let Ok(queue) = state().queue(queue) {
+        queue.deferred = true;
+    }
+}
+
+/// Returns how often `queue` was flushed.
+pub fn flush_count(queue: cl_command_queue) -> usize {
+    state()
+        .queue(queue)
+        .map(|queue| queue.flushes)
+        .unwrap_or_default()
+}
+
+/// Returns the number of memory objects of `context` that are not released yet.
+pub fn mem_object_count(context: cl_context) -> usize {
+    state()
+        .objects
+        .values()
+        .filter(|entry| match entry.object {
+            Object::Mem { context: ctx, .. }
+            | Object::SubBuffer { context: ctx, .. }
+            | Object::Image(MockImage { context: ctx, .. }) => ctx == context as usize,
+            _ => false,
+        })
+        .count()
+}
+
+/// Returns the number of SVM allocations of `context` that are not freed yet.
+pub fn svm_allocation_count(context: cl_context) -> usize {
+    state()
+        .svm
+        .values()
+        .filter(|(ctx, _)| *ctx == context as usize)
+        .count()
+}
+
+/// Runs `f` whenever a kernel called `name` is enqueued.
+/// `f` must not call into the OpenCL API.
+pub fn register_kernel(name: &str, f: impl Fn(&mut MockLaunch) + Send + Sync + 'static) {
+    state().kernel_fns.insert(name.to_string(), Arc::new(f));
+}
+
+/// Gives a host implementation of a kernel access to its arguments.
+pub struct MockLaunch<'a> {
+    state: &'a mut State,
+    args: Vec<Option<Vec<u8>>>,
+    global_work_size: Vec<usize>,
+}
+
+impl MockLaunch<'_> {
+    pub fn global_work_size(&self) -> &[usize] {
+        &self.global_work_size
+    }
+
+    fn arg(&self, index: usize) -> &[u8] {
+        self.args[index]
+            .as_deref()
+            .unwrap_or_else(|| panic!("kernel arg {index} is not set or local memory"))
+    }
+
+    fn buffer(&mut self, index: usize) -> &mut [u8] {
+        let mem = self.scalar::<cl_mem>(index);
+        self.state
+            .mem(mem)
+            .unwrap_or_else(|_| panic!("kernel arg {index} is not a buffer"))
+    }
+
+    /// Reads a scalar kernel argument.
+    pub fn scalar<T: Copy>(&self, index: usize) -> T {
+        let arg = self.arg(index);
+        assert_eq!(
+            arg.len(),
+            std::mem::size_of::<T>(),
+            "size of kernel arg {index}"
+        );
+        unsafe { std::ptr::read_unaligned(arg.as_ptr() as *const T) }
+    }
+
+    /// Reads the contents of the buffer passed as kernel argument.
+    pub fn read<T: Copy>(&mut self, index: usize) -> Vec<T> {
+        let data = self.buffer(index);
+        let len = data.len() / std::mem::size_of::<T>();
+        (0..len)
+            .map(|i| unsafe { std::ptr::read_unaligned((data.as_ptr() as *const T).add(i)) })
+            .collect()
+    }
+
+    /// Writes `values` to the start of the buffer passed as kernel argument.
+    pub fn write<T: Copy>(&mut self, index: usize, values: &[T]) {
+        let data = self.buffer(index);
+        let bytes = std::mem::size_of_val(values);
+        assert!(bytes <= data.len(), "write exceeds kernel arg {index}");
+        unsafe {
+            std::ptr::copy_nonoverlapping(values.as_ptr() as *const u8, data.as_mut_ptr(), bytes)
+        };
+    }
+}
+
+/// Finds every `__kernel void name(..)` in the source, together with its parameters and
+/// `__attribute__((reqd_work_group_size(X, Y, Z)))` between `__kernel` and `void`.
+fn parse_kernels(src: &str) -> Vec<KernelDecl> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let mut kernels = Vec::new();
+    let mut search = 0;
+
+    while let Some(found) = src[search..].find("kernel") {
+        let start = search + found;
+        search = start + "kernel".len();
+
+        let before = src[..start].trim_end_matches("__");
+        if before.ends_with(is_ident) || src[search..].starts_with(is_ident) {
+            continue;
+        }
+        let mut rest = src[search..].trim_start();
+        let mut reqd_work_group_size = [0; 3];
+        while let Some(attribute) = rest.strip_prefix("__attribute__((") {
+            // the index of the closing parentheses of `__attribute__((`
+            let mut depth = 2;
+            let Some(end) = attribute.find(|c| {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+                depth == 0
+            }) else {
+                break;
+            };
+            if let Some(size) = attribute[..end - 1]
+                .trim()
+                .strip_prefix("reqd_work_group_size(")
+                .and_then(|size| size.strip_suffix(')'))
+            {
+                for (dim, value) in reqd_work_group_size.iter_mut().zip(size.split(',')) {
+                    *dim = value.trim().parse().unwrap_or(0);
+                }
+            }
+            rest = attribute[end + 1..].trim_start();
+        }
+        let Some(rest) = rest.strip_prefix("void") else {
+            continue;
+        };
+        let rest = rest.trim_start();
+        let name_len = rest.find(|c| !is_ident(c)).unwrap_or(rest.len());
+        let Some(params) = rest[name_len..].trim_start().strip_prefix('(') else {
+            continue;
+        };
+        let params = params[..params.find(')').unwrap_or(params.len())].trim();
+        let params = match params {
+            "" | "void" => Vec::new(),
+            params => params.split(',').map(parse_param).collect(),
+        };
+        kernels.push(KernelDecl {
+            name: rest[..name_len].to_string(),
+            params,
+            reqd_work_group_size,
+        });
+    }
+    kernels
+}
+
+/// Derives the expected argument size from a parameter declaration like `__global const float* x` or `int4 v`.
+fn parse_param(param: &str) -> MockParam {
+    const QUALIFIERS: [&str; 17] = [
+        "const",
+        "volatile",
+        "restrict",
+        "unsigned",
+        "__global",
+        "global",
+        "__constant",
+        "constant",
+        "__private",
+        "private",
+        "__read_only",
+        "read_only",
+        "__write_only",
+        "write_only",
+        "__read_write",
+        "read_write",
+        "__local",
+    ];
+    let local = param.contains("__local") || param.split_whitespace().any(|t| t == "local");
+    let pointer = param.contains('*');
+    let tokens = param
+        .split(|c: char| c.is_whitespace() || c == '*')
+        .filter(|token| !token.is_empty() && !QUALIFIERS.contains(token) && *token != "local")
+        .collect::<Vec<_>>();
+
+    let size = if local {
+        None
+    } else if pointer {
+        Some(std::mem::size_of::<cl_mem>())
+    } else {
+        tokens
+            .len()
+            .checked_sub(2)
+            .and_then(|idx| type_size(tokens[idx]))
+    };
+    MockParam { local, size }
+}
+
+/// Size of an OpenCL C scalar, vector, image or sampler type.
+fn type_size(ty: &str) -> Option<usize> {
+    if ty.starts_with("image") || ty == "sampler_t" {
+        return Some(std::mem::size_of::<cl_mem>());
+    }
+    let base = ty.trim_end_matches(|c: char| c.is_ascii_digit());
+    let scalar = match base {
+        "char" | "uchar" => 1,
+        "short" | "ushort" | "half" => 2,
+        "int" | "uint" | "float" => 4,
+        "long" | "ulong" | "double" => 8,
+        "size_t" | "ptrdiff_t" | "intptr_t" | "uintptr_t" => std::mem::size_of::<usize>(),
+        _ => return None,
+    };
+    // 3-component vectors have the size of 4-component vectors
+    let width = match &ty[base.len()..] {
+        "" => 1,
+        "3" => 4,
+        width @ ("2" | "4" | "8" | "16") => width.parse().unwrap(),
+        _ => return None,
+    };
+    Some(scalar * width)
+}
+
+fn string_info(value: &str) -> Vec<u8> {
+    let mut bytes = value.as_bytes().to_vec();
+    bytes.push(0);
+    bytes
+}
+
+fn bytes_of<T: Copy>(values: &[T]) -> Vec<u8> {
+    unsafe {
+        std::slice::from_raw_parts(values.as_ptr() as *const u8, std::mem::size_of_val(values))
+    }
+    .to_vec()
+}
+
+unsafe fn write_info(
+    value: &[u8],
+    param_value_size: size_t,
+    param_value: *mut c_void,
+    param_value_size_ret: *mut size_t,
+) -> Result<(), cl_int> {
+    if !param_value.is_null() {
+        if param_value_size < value.len() {
+            return Err(INVALID_VALUE);
+        }
+        std::ptr::copy_nonoverlapping(value.as_ptr(), param_value as *mut u8, value.len());
+    }
+    if !param_value_size_ret.is_null() {
+        *param_value_size_ret = value.len();
+    }
+    Ok(())
+}
+
+fn check_range(len: usize, offset: usize, size: usize) -> Result<(), cl_int> {
+    match offset.checked_add(size) {
+        Some(end) if end <= len => Ok(()),
+        _ => Err(INVALID_VALUE),
+    }
+}
+
+unsafe fn read_triple(ptr: *const size_t) -> Result<[usize; 3], cl_int> {
+    if ptr.is_null() {
+        return Err(INVALID_VALUE);
+    }
+    Ok([*ptr, *ptr.add(1), *ptr.add(2)])
+}
+
+/// Returns the byte offset of every row of a rectangular region. `origin[0]` and `region[0]` are in bytes.
+fn rect_rows(
+    origin: [usize; 3],
+    region: [usize; 3],
+    row_pitch: usize,
+    slice_pitch: usize,
+) -> Result<Vec<usize>, cl_int> {
+    let row_pitch = if row_pitch == 0 { region[0] } else { row_pitch };
+    let slice_pitch = if slice_pitch == 0 {
+        row_pitch * region[1]
+    } else {
+        slice_pitch
+    };
+    if region.contains(&0) || row_pitch < region[0] || slice_pitch < row_pitch * region[1] {
+        return Err(INVALID_VALUE);
+    }
+    let mut rows = Vec::with_capacity(region[1] * region[2]);
+    for z in 0..region[2] {
+        for y in 0..region[1] {
+            rows.push(origin[0] + (origin[1] + y) * row_pitch + (origin[2] + z) * slice_pitch);
+        }
+    }
+    Ok(rows)
+}
+
+/// Returns the byte offset of every row of the `region` at `origin` of the image. Both are in pixels.
+fn image_rows(
+    image: &MockImage,
+    origin: [usize; 3],
+    region: [usize; 3],
+) -> Result<Vec<usize>, cl_int> {
+    for axis in 0..3 {
+        check_range(image.size[axis], origin[axis], region[axis])?;
+    }
+    let row_pitch = image.size[0] * image.pixel_size;
+    rect_rows(
+        [origin[0] * image.pixel_size, origin[1], origin[2]],
+        [region[0] * image.pixel_size, region[1], region[2]],
+        row_pitch,
+        row_pitch * image.size[1],
+    )
+}
+
+/// Calls the callbacks of completed commands without holding the state, as they may call other OpenCL functions.
+unsafe fn run_callbacks() {
+    let callbacks = std::mem::take(&mut state().ready_callbacks);
+    for (callback, event, user_data) in callbacks {
+        callback(event, CL_COMPLETE, user_data as *mut c_void);
+    }
+}
+
+/// Runs a mock function returning a status code.
+fn call(function: &str, f: impl FnOnce(&mut State) -> Result<(), cl_int>) -> cl_int {
+    if let Some(code) = take_injected(function) {
+        return code;
+    }
+    match f(&mut state()) {
+        Ok(()) => SUCCESS,
+        Err(code) => code,
+    }
+}
+
+/// Runs a mock function returning an object, reporting the status through `errcode_ret`.
+unsafe fn create(
+    function: &str,
+    errcode_ret: *mut cl_int,
+    f: impl FnOnce(&mut State) -> Result<usize, cl_int>,
+) -> *mut c_void {
+    let result = match take_injected(function) {
+        Some(code) => Err(code),
+        None => f(&mut state()),
+    };
+    let (handle, code) = match result {
+        Ok(handle) => (handle, SUCCESS),
+        Err(code) => (0, code),
+    };
+    if !errcode_ret.is_null() {
+        *errcode_ret = code;
+    }
+    handle as *mut c_void
+}
+
+fn device_info(param_name: cl_device_info) -> Option<Vec<u8>> {
+    Some(match param_name {
+        // CL_DEVICE_TYPE: GPU
+        0x1000 => bytes_of(&[1u64 << 2]),
+        0x1001 => bytes_of(&[0x1234 as cl_uint]),
+        0x1002 => bytes_of(&[8 as cl_uint]),
+        0x1003 => bytes_of(&[3 as cl_uint]),
+        0x1004 => bytes_of(&[MAX_WORK_GROUP_SIZE]),
+        0x1005 => bytes_of(&[MAX_WORK_GROUP_SIZE, MAX_WORK_GROUP_SIZE, 64]),
+        // preferred vector widths: char, short, int, long, float, double
+        0x1006..=0x100B => {
+            bytes_of(&[[16, 8, 4, 2, 4, 2][param_name as usize - 0x1006] as cl_uint])
+        }
+        0x100C => bytes_of(&[1000 as cl_uint]),
+        0x100D => bytes_of(&[64 as cl_uint]),
+        0x1010 => bytes_of(&[MAX_MEM_ALLOC_SIZE]),
+        0x100E => bytes_of(&[128 as cl_uint]),
+        0x100F => bytes_of(&[8 as cl_uint]),
+        0x1011 | 0x1012 => bytes_of(&[IMAGE2D_MAX_SIZE]),
+        0x1013..=0x1015 => bytes_of(&[IMAGE3D_MAX_SIZE]),
+        0x1016 => bytes_of(&[1 as cl_bool]),
+        0x1018 => bytes_of(&[16 as cl_uint]),
+        0x1019 => bytes_of(&[MEM_BASE_ADDR_ALIGN]),
+        // CL_DEVICE_SINGLE_FP_CONFIG: denorm, inf/nan, round to nearest, fma
+        0x101B => bytes_of(&[0b10_0111u64]),
+        0x101C => bytes_of(&[2 as cl_uint]),
+        0x101D => bytes_of(&[64 as cl_uint]),
+        0x101E => bytes_of(&[1024u64 * 1024]),
+        0x101F => bytes_of(&[GLOBAL_MEM_SIZE]),
+        0x1020 => bytes_of(&[64u64 * 1024]),
+        0x1022 => bytes_of(&[1 as cl_uint]),
+        0x1023 => bytes_of(&[LOCAL_MEM_SIZE]),
+        0x1025 => bytes_of(&[1usize]),
+        0x1026 => bytes_of(&[1 as cl_bool]),
+        0x1027 | 0x1028 | 0x103E => bytes_of(&[1 as cl_bool]),
+        0x102A => bytes_of(&[CL_QUEUE_OUT_OF_ORDER_EXEC_MODE_ENABLE | CL_QUEUE_PROFILING_ENABLE]),
+        0x102B => string_info("min-cl mock device"),
+        0x102C => string_info("min-cl"),
+        0x102D => string_info(env!("CARGO_PKG_VERSION")),
+        0x102E => string_info("FULL_PROFILE"),
+        0x102F => string_info("OpenCL 1.2 min-cl mock"),
+        0x1030 => string_info("cl_khr_fp64 cl_khr_global_int32_base_atomics"),
+        0x1031 => bytes_of(&[PLATFORM]),
+        0x1032 => bytes_of(&[0b11_1111u64]),
+        // CL_DEVICE_HALF_FP_CONFIG is only valid with cl_khr_fp16
+        0x1033 => return None,
+        0x1034 => bytes_of(&[0 as cl_uint]),
+        0x1035 => bytes_of(&[1 as cl_bool]),
+        // native vector widths: char, short, int, long, float, double, half
+        0x1036..=0x103C => {
+            bytes_of(&[[4, 2, 1, 1, 1, 1, 0][param_name as usize - 0x1036] as cl_uint])
+        }
+        0x103D => string_info("OpenCL C 1.2"),
+        0x1041 => bytes_of(&[IMAGE_MAX_ARRAY_SIZE]),
+        0x1053 => bytes_of(&[SVM_CAPABILITIES]),
+        _ => return None,
+    })
+}
+
+fn platform_info(param_name: cl_platform_info) -> Option<Vec<u8>> {
+    Some(match param_name {
+        0x0900 => string_info("FULL_PROFILE"),
+        0x0901 => string_info("OpenCL 1.2 min-cl mock"),
+        0x0902 => string_info("min-cl mock platform"),
+        0x0903 => string_info("min-cl"),
+        0x0904 => string_info("cl_khr_icd"),
+        0x0905 => bytes_of(&[1u64]),
+        _ => return None,
+    })
+}
+
+fn build(program: &mut MockProgram) -> Result<(), cl_int> {
+    // `#error` directives are the only compile errors the mock backend knows about
+    let errors = program
+        .source
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.trim_start().starts_with("#error"))
+        .map(|(idx, line)| format!("<source>:{}: error: {}", idx + 1, line.trim()))
+        .collect::<Vec<_>>();
+
+    if errors.is_empty() {
+        program.status = BUILD_SUCCESS;
+        program.log.clear();
+        Ok(())
+    } else {
+        program.status = BUILD_ERROR;
+        program.log = errors.join("\n");
+        Err(BUILD_PROGRAM_FAILURE)
+    }
+}
+
+fn binary(program: &MockProgram) -> Vec<u8> {
+    if program.status != BUILD_SUCCESS {
+        return Vec::new();
+    }
+    [MOCK_BINARY_MAGIC, program.source.as_bytes()].concat()
+}
+
+unsafe extern "system" fn clGetPlatformIDs(
+    num_entries: cl_uint,
+    platforms: *mut cl_platform_id,
+    num_platforms: *mut cl_uint,
+) -> cl_int {
+    call("clGetPlatformIDs", |_| {
+        if !platforms.is_null() {
+            if num_entries == 0 {
+                return Err(INVALID_VALUE);
+            }
+            *platforms = PLATFORM as cl_platform_id;
+        }
+        if !num_platforms.is_null() {
+            *num_platforms = 1;
+        }
+        Ok(())
+    })
+}
+
+unsafe extern "system" fn clGetPlatformInfo(
+    platform: cl_platform_id,
+    param_name: cl_platform_info,
+    param_value_size: size_t,
+    param_value: *mut c_void,
+    param_value_size_ret: *mut size_t,
+) -> cl_int {
+    call("clGetPlatformInfo", |_| {
+        if platform as usize != PLATFORM {
+            return Err(INVALID_PLATFORM);
+        }
+        let value = platform_info(param_name).ok_or(INVALID_VALUE)?;
+        write_info(&value, param_value_size, param_value, param_value_size_ret)
+    })
+}
+
+unsafe extern "system" fn clGetDeviceIDs(
+    platform: cl_platform_id,
+    device_type: cl_device_type,
+    num_entries: cl_uint,
+    devices: *mut cl_device_id,
+    num_devices: *mut cl_uint,
+) -> cl_int {
+    call("clGetDeviceIDs", |_| {
+        if !platform.is_null() && platform as usize != PLATFORM {
+            return Err(INVALID_PLATFORM);
+        }
+        // default or GPU; 0xFFFFFFFF is CL_DEVICE_TYPE_ALL
+        if device_type & (1 | 1 << 2) == 0 && device_type != 0xFFFFFFFF {
+            return Err(DEVICE_NOT_FOUND);
+        }
+        if !devices.is_null() {
+            if num_entries == 0 {
+                return Err(INVALID_VALUE);
+            }
+            *devices = DEVICE as cl_device_id;
+        }
+        if !num_devices.is_null() {
+            *num_devices = 1;
+        }
+        Ok(())
+    })
+}
+
+unsafe extern "system" fn clGetDeviceInfo(
+    device: cl_device_id,
+    param_name: cl_device_info,
+    param_value_size: size_t,
+    param_value: *mut c_void,
+    param_value_size_ret: *mut size_t,
+) -> cl_int {
+    call("clGetDeviceInfo", |_| {
+        if device as usize != DEVICE {
+            return Err(INVALID_DEVICE);
+        }
+        let value = device_info(param_name).ok_or(INVALID_VALUE)?;
+        write_info(&value, param_value_size, param_value, param_value_size_ret)
+    })
+}
+
+unsafe extern "system" fn clCreateContext(
+    _properties: *const cl_context_properties,
+    num_devices: cl_uint,
+    devices: *const cl_device_id,
+    _pfn_notify: *mut c_void,
+    _user_data: *mut c_void,
+    errcode_ret: *mut cl_int,
+) -> cl_context {
+    create("clCreateContext", errcode_ret, |state| {
+        if num_devices == 0 || devices.is_null() {
+            return Err(INVALID_VALUE);
+        }
+        let devices = std::slice::from_raw_parts(devices, num_devices as usize);
+        if devices.iter().any(|&device| device as usize != DEVICE) {
+            return Err(INVALID_DEVICE);
+        }
+        Ok(state.insert(Object::Context))
+    })
+}
+
+unsafe extern "system" fn clRetainContext(context: cl_context) -> cl_int {
+    call("clRetainContext", |state| {
+        state.context(context)?;
+        state.retain(context);
+        Ok(())
+    })
+}
+
+unsafe extern "system" fn clReleaseContext(context: cl_context) -> cl_int {
+    call("clReleaseContext", |state| {
+        state.context(context)?;
+        state.release(context);
+        Ok(())
+    })
+}
+
+unsafe extern "system" fn clGetContextInfo(
+    context: cl_context,
+    param_name: cl_context_info,
+    param_value_size: size_t,
+    param_value: *mut c_void,
+    param_value_size_ret: *mut size_t,
+) -> cl_int {
+    call("clGetContextInfo", |state| {
+        state.context(context)?;
+        let value = match param_name {
+            // CL_CONTEXT_DEVICES
+            0x1081 => bytes_of(&[DEVICE]),
+            // CL_CONTEXT_NUM_DEVICES
+            0x1083 => bytes_of(&[1 as cl_uint]),
+            _ => return Err(INVALID_VALUE),
+        };
+        write_info(&value, param_value_size, param_value, param_value_size_ret)
+    })
+}
+
+unsafe extern "system" fn clCreateCommandQueue(
+    context: cl_context,
+    device: cl_device_id,
+    properties: cl_command_queue_properties,
+    errcode_ret: *mut cl_int,
+) -> cl_command_queue {
+    create("clCreateCommandQueue", errcode_ret, |state| {
+        state.context(context)?;
+        if device as usize != DEVICE {
+            return Err(INVALID_DEVICE);
+        }
+        if properties & !(CL_QUEUE_OUT_OF_ORDER_EXEC_MODE_ENABLE | CL_QUEUE_PROFILING_ENABLE) != 0 {
+            return Err(INVALID_QUEUE_PROPERTIES);
+        }
+        Ok(state.insert(Object::Queue(MockQueue {
+            properties,
+            records: Vec::new(),
+            flushes: 0,
+            deferred: false,
+        })))
+    })
+}
+
+unsafe extern "system" fn clFlush(command_queue: cl_command_queue) -> cl_int {
+    let code = call("clFlush", |state| {
+        state.queue(command_queue)?.flushes += 1;
+        state.complete(command_queue as usize);
+        Ok(())
+    });
+    run_callbacks();
+    code
+}
+
+unsafe extern "system" fn clFinish(command_queue: cl_command_queue) -> cl_int {
+    let code = call("clFinish", |state| {
+        state.queue(command_queue)?;
+        state.complete(command_queue as usize);
+        Ok(())
+    });
+    run_callbacks();
+    code
+}
+
+unsafe extern "system" fn clRetainCommandQueue(command_queue: cl_command_queue) -> cl_int {
+    call("clRetainCommandQueue", |state| {
+        state.queue(command_queue)?;
+        state.retain(command_queue);
+        Ok(())
+    })
+}
+
+unsafe extern "system" fn clReleaseCommandQueue(command_queue: cl_command_queue) -> cl_int {
+    call("clReleaseCommandQueue", |state| {
+        state.queue(command_queue)?;
+        state.release(command_queue);
+        Ok(())
+    })
+}
+
+unsafe extern "system" fn clWaitForEvents(
+    num_events: cl_uint,
+    event_list: *const cl_event,
+) -> cl_int {
+    let code = call("clWaitForEvents", |state| {
+        if num_events == 0 || event_list.is_null() {
+            return Err(INVALID_VALUE);
+        }
+        for &event in std::slice::from_raw_parts(event_list, num_events as usize) {
+            // waiting flushes the queue of the event
+            match state.get(event) {
+                Some(&mut Object::Event { queue, .. }) => state.complete(queue),
+                _ => return Err(INVALID_EVENT),
+            }
+        }
+        Ok(())
+    });
+    run_callbacks();
+    code
+}
+
+unsafe extern "system" fn clRetainEvent(event: cl_event) -> cl_int {
+    call("clRetainEvent", |state| {
+        state.check(event, |o| matches!(o, Object::Event { .. }), INVALID_EVENT)?;
+        state.retain(event);
+        Ok(())
+    })
+}
+
+unsafe extern "system" fn clReleaseEvent(event: cl_event) -> cl_int {
+    call("clReleaseEvent", |state| {
+        state.check(event, |o| matches!(o, Object::Event { .. }), INVALID_EVENT)?;
+        state.release(event);
+        Ok(())
+    })
+}
+
+unsafe extern "system" fn clGetEventInfo(
+    event: cl_event,
+    param_name: cl_event_info,
+    param_value_size: size_t,
+    param_value: *mut c_void,
+    param_value_size_ret: *mut size_t,
+) -> cl_int {
+    call("clGetEventInfo", |state| {
+        let (queue, complete) = match state.get(event) {
+            Some(Object::Event {
+                queue, complete, ..
+            }) => (*queue, *complete),
+            _ => return Err(INVALID_EVENT),
+        };
+        let value = match param_name {
+            CL_EVENT_COMMAND_QUEUE => bytes_of(&[queue]),
+            // commands are executed when they are enqueued, completion may be deferred until a flush
+            CL_EVENT_COMMAND_EXECUTION_STATUS => {
+                bytes_of(&[if complete { CL_COMPLETE } else { CL_QUEUED }])
+            }
+            _ => return Err(INVALID_VALUE),
+        };
+        write_info(&value, param_value_size, param_value, param_value_size_ret)
+    })
+}
+
+unsafe extern "system" fn clSetEventCallback(
+    event: cl_event,
+    command_exec_callback_type: cl_int,
+    pfn_notify: Option<cl_event_callback>,
+    user_data: *mut c_void,
+) -> cl_int {
+    let code = call("clSetEventCallback", |state| {
+        let Some(callback) = pfn_notify else {
+            return Err(INVALID_VALUE);
+        };
+        if !matches!(
+            command_exec_callback_type,
+            CL_COMPLETE | CL_RUNNING | CL_SUBMITTED
+        ) {
+            return Err(INVALID_VALUE);
+        }
+        let user_data = user_data as usize;
+        match state.get(event) {
+            Some(Object::Event { complete: true, .. }) => {
+                state.ready_callbacks.push((callback, event, user_data));
+            }
+            Some(Object::Event { callbacks, .. }) => callbacks.push((callback, user_data)),
+            _ => return Err(INVALID_EVENT),
+        }
+        Ok(())
+    });
+    // the callback of a completed command is called immediately
+    run_callbacks();
+    code
+}
+
+unsafe extern "system" fn clGetEventProfilingInfo(
+    event: cl_event,
+    param_name: cl_profiling_info,
+    param_value_size: size_t,
+    param_value: *mut c_void,
+    param_value_size_ret: *mut size_t,
+) -> cl_int {
+    call("clGetEventProfilingInfo", |state| {
+        let timestamps = match state.get(event) {
+            Some(Object::Event { profiling, .. }) => {
+                profiling.ok_or(PROFILING_INFO_NOT_AVAILABLE)?
+            }
+            _ => return Err(INVALID_EVENT),
+        };
+        let value = match param_name {
+            CL_PROFILING_COMMAND_QUEUED => timestamps[0],
+            CL_PROFILING_COMMAND_SUBMIT => timestamps[1],
+            CL_PROFILING_COMMAND_START => timestamps[2],
+            CL_PROFILING_COMMAND_END => timestamps[3],
+            _ => return Err(INVALID_VALUE),
+        };
+        write_info(
+            &bytes_of(&[value]),
+            param_value_size,
+            param_value,
+            param_value_size_ret,
+        )
+    })
+}
+
+unsafe extern "system" fn clCreateBuffer(
+    context: cl_context,
+    flags: cl_mem_flags,
+    size: size_t,
+    host_ptr: *mut c_void,
+    errcode_ret: *mut cl_int,
+) -> cl_mem {
+    create("clCreateBuffer", errcode_ret, |state| {
+        state.context(context)?;
+        if size == 0 || size as u64 > MAX_MEM_ALLOC_SIZE {
+            return Err(INVALID_BUFFER_SIZE);
+        }
+        let uses_host_ptr = flags & (MEM_USE_HOST_PTR | MEM_COPY_HOST_PTR) != 0;
+        if uses_host_ptr == host_ptr.is_null() {
+            return Err(INVALID_HOST_PTR);
+        }
+
+        let mut data = vec![0u8; size].into_boxed_slice();
+        if uses_host_ptr {
+            std::ptr::copy_nonoverlapping(host_ptr as *const u8, data.as_mut_ptr(), size);
+        }
+        Ok(state.insert(Object::Mem {
+            context: context as usize,
+            data,
+        }))
+    })
+}
+
+unsafe extern "system" fn clCreateSubBuffer(
+    buffer: cl_mem,
+    _flags: cl_mem_flags,
+    buffer_create_type: cl_buffer_create_type,
+    buffer_create_info: *const c_void,
+    errcode_ret: *mut cl_int,
+) -> cl_mem {
+    create("clCreateSubBuffer", errcode_ret, |state| {
+        let context = match state.get(buffer) {
+            Some(Object::Mem { context, .. }) => *context,
+            _ => return Err(INVALID_MEM_OBJECT),
+        };
+        if buffer_create_type != CL_BUFFER_CREATE_TYPE_REGION || buffer_create_info.is_null() {
+            return Err(INVALID_VALUE);
+        }
+        let region = *(buffer_create_info as *const cl_buffer_region);
+        if region.size == 0 {
+            return Err(INVALID_BUFFER_SIZE);
+        }
+        check_range(state.mem(buffer)?.len(), region.origin, region.size)?;
+        if !region
+            .origin
+            .is_multiple_of(MEM_BASE_ADDR_ALIGN as usize / 8)
+        {
+            return Err(MISALIGNED_SUB_BUFFER_OFFSET);
+        }
+
+        state.retain(buffer);
+        Ok(state.insert(Object::SubBuffer {
+            context,
+            parent: buffer as usize,
+            offset: region.origin,
+            size: region.size,
+        }))
+    })
+}
+
+unsafe extern "system" fn clGetMemObjectInfo(
+    memobj: cl_mem,
+    param_name: cl_mem_info,
+    param_value_size: size_t,
+    param_value: *mut c_void,
+    param_value_size_ret: *mut size_t,
+) -> cl_int {
+    call("clGetMemObjectInfo", |state| {
+        let size = state.mem(memobj)?.len();
+        let (context, parent, offset) = match state.get(memobj) {
+            Some(Object::Mem { context, .. }) => (*context, 0, 0),
+            Some(Object::Image(image)) => (image.context, 0, 0),
+            Some(Object::SubBuffer {
+                context,
+                parent,
+                offset,
+                ..
+            }) => (*context, *parent, *offset),
+            _ => return Err(INVALID_MEM_OBJECT),
+        };
+        let value = match param_name {
+            // CL_MEM_SIZE
+            0x1102 => bytes_of(&[size]),
+            // CL_MEM_CONTEXT
+            0x1106 => bytes_of(&[context]),
+            // CL_MEM_ASSOCIATED_MEMOBJECT
+            0x1107 => bytes_of(&[parent]),
+            // CL_MEM_OFFSET
+            0x1108 => bytes_of(&[offset]),
+            _ => return Err(INVALID_VALUE),
+        };
+        write_info(&value, param_value_size, param_value, param_value_size_ret)
+    })
+}
+
+unsafe extern "system" fn clRetainMemObject(memobj: cl_mem) -> cl_int {
+    call("clRetainMemObject", |state| {
+        state.mem(memobj)?;
+        state.retain(memobj);
+        Ok(())
+    })
+}
+
+unsafe extern "system" fn clReleaseMemObject(memobj: cl_mem) -> cl_int {
+    call("clReleaseMemObject", |state| {
+        state.mem(memobj)?;
+        state.release(memobj);
+        Ok(())
+    })
+}
+
+unsafe extern "system" fn clEnqueueReadBuffer(
+    command_queue: cl_command_queue,
+    buffer: cl_mem,
+    _blocking_read: cl_bool,
+    offset: size_t,
+    cb: size_t,
+    ptr: *mut c_void,
+    num_events_in_wait_list: cl_uint,
+    event_wait_list: *const cl_event,
+    event: *mut cl_event,
+) -> cl_int {
+    call("clEnqueueReadBuffer", |state| {
+        state.queue(command_queue)?;
+        let wait_list = state.wait_list(num_events_in_wait_list, event_wait_list)?;
+        let data = state.mem(buffer)?;
+        check_range(data.len(), offset, cb)?;
+        if ptr.is_null() {
+            return Err(INVALID_VALUE);
+        }
+        std::ptr::copy_nonoverlapping(data.as_ptr().add(offset), ptr as *mut u8, cb);
+
+        let command = MockCommand::ReadBuffer {
+            buffer,
+            offset,
+            size: cb,
+        };
+        state.record(command_queue, wait_list, command, event)
+    })
+}
+
+unsafe extern "system" fn clEnqueueWriteBuffer(
+    command_queue: cl_command_queue,
+    buffer: cl_mem,
+    _blocking_write: cl_bool,
+    offset: size_t,
+    cb: size_t,
+    ptr: *const c_void,
+    num_events_in_wait_list: cl_uint,
+    event_wait_list: *const cl_event,
+    event: *mut cl_event,
+) -> cl_int {
+    call("clEnqueueWriteBuffer", |state| {
+        state.queue(command_queue)?;
+        let wait_list = state.wait_list(num_events_in_wait_list, event_wait_list)?;
+        let data = state.mem(buffer)?;
+        check_range(data.len(), offset, cb)?;
+        if ptr.is_null() {
+            return Err(INVALID_VALUE);
+        }
+        std::ptr::copy_nonoverlapping(ptr as *const u8, data.as_mut_ptr().add(offset), cb);
+
+        let command = MockCommand::WriteBuffer {
+            buffer,
+            offset,
+            size: cb,
+        };
+        state.record(command_queue, wait_list, command, event)
+    })
+}
+
+unsafe extern "system" fn clEnqueueCopyBuffer(
+    command_queue: cl_command_queue,
+    src_buffer: cl_mem,
+    dst_buffer: cl_mem,
+    src_offset: size_t,
+    dst_offset: size_t,
+    cb: size_t,
+    num_events_in_wait_list: cl_uint,
+    event_wait_list: *const cl_event,
+    event: *mut cl_event,
+) -> cl_int {
+    call("clEnqueueCopyBuffer", |state| {
+        state.queue(command_queue)?;
+        let wait_list = state.wait_list(num_events_in_wait_list, event_wait_list)?;
+
+        let src = state.mem(src_buffer)?;
+        check_range(src.len(), src_offset, cb)?;
+        let copied = src[src_offset..src_offset + cb].to_vec();
+
+        let dst = state.mem(dst_buffer)?;
+        check_range(dst.len(), dst_offset, cb)?;
+        if src_buffer == dst_buffer && src_offset < dst_offset + cb && dst_offset < src_offset + cb
+        {
+            return Err(MEM_COPY_OVERLAP);
+        }
+        dst[dst_offset..dst_offset + cb].copy_from_slice(&copied);
+
+        let command = MockCommand::CopyBuffer {
+            src: src_buffer,
+            dst: dst_buffer,
+            src_offset,
+            dst_offset,
+            size: cb,
+        };
+        state.record(command_queue, wait_list, command, event)
+    })
+}
+
+unsafe extern "system" fn clEnqueueReadBufferRect(
+    command_queue: cl_command_queue,
+    buffer: cl_mem,
+    _blocking_read: cl_bool,
+    buffer_origin: *const size_t,
+    host_origin: *const size_t,
+    region: *const size_t,
+    buffer_row_pitch: size_t,
+    buffer_slice_pitch: size_t,
+    host_row_pitch: size_t,
+    host_slice_pitch: size_t,
+    ptr: *mut c_void,
+    num_events_in_wait_list: cl_uint,
+    event_wait_list: *const cl_event,
+    event: *mut cl_event,
+) -> cl_int {
+    call("clEnqueueReadBufferRect", |state| {
+        state.queue(command_queue)?;
+        let wait_list = state.wait_list(num_events_in_wait_list, event_wait_list)?;
+        let (buffer_origin, region) = (read_triple(buffer_origin)?, read_triple(region)?);
+        let buffer_rows = rect_rows(buffer_origin, region, buffer_row_pitch, buffer_slice_pitch)?;
+        let host_rows = rect_rows(
+            read_triple(host_origin)?,
+            region,
+            host_row_pitch,
+            host_slice_pitch,
+        )?;
+        if ptr.is_null() {
+            return Err(INVALID_VALUE);
+        }
+
+        let data = state.mem(buffer)?;
+        for (buffer_row, host_row) in buffer_rows.into_iter().zip(host_rows) {
+            check_range(data.len(), buffer_row, region[0])?;
+            let dst = (ptr as *mut u8).add(host_row);
+            std::ptr::copy_nonoverlapping(data.as_ptr().add(buffer_row), dst, region[0]);
+        }
+
+        let command = MockCommand::ReadBufferRect {
+            buffer,
+            buffer_origin,
+            region,
+        };
+        state.record(command_queue, wait_list, command, event)
+    })
+}
+
+unsafe extern "system" fn clEnqueueWriteBufferRect(
+    command_queue: cl_command_queue,
+    buffer: cl_mem,
+    _blocking_write: cl_bool,
+    buffer_origin: *const size_t,
+    host_origin: *const size_t,
+    region: *const size_t,
+    buffer_row_pitch: size_t,
+    buffer_slice_pitch: size_t,
+    host_row_pitch: size_t,
+    host_slice_pitch: size_t,
+    ptr: *const c_void,
+    num_events_in_wait_list: cl_uint,
+    event_wait_list: *const cl_event,
+    event: *mut cl_event,
+) -> cl_int {
+    call("clEnqueueWriteBufferRect", |state| {
+        state.queue(command_queue)?;
+        let wait_list = state.wait_list(num_events_in_wait_list, event_wait_list)?;
+        let (buffer_origin, region) = (read_triple(buffer_origin)?, read_triple(region)?);
+        let buffer_rows = rect_rows(buffer_origin, region, buffer_row_pitch, buffer_slice_pitch)?;
+        let host_rows = rect_rows(
+            read_triple(host_origin)?,
+            region,
+            host_row_pitch,
+            host_slice_pitch,
+        )?;
+        if ptr.is_null() {
+            return Err(INVALID_VALUE);
+        }
+
+        let data = state.mem(buffer)?;
+        for (buffer_row, host_row) in buffer_rows.into_iter().zip(host_rows) {
+            check_range(data.len(), buffer_row, region[0])?;
+            let src = (ptr as *const u8).add(host_row);
+            std::ptr::copy_nonoverlapping(src, data.as_mut_ptr().add(buffer_row), region[0]);
+        }
+
+        let command = MockCommand::WriteBufferRect {
+            buffer,
+            buffer_origin,
+            region,
+        };
+        state.record(command_queue, wait_list, command, event)
+    })
+}
+
+unsafe extern "system" fn clEnqueueCopyBufferRect(
+    command_queue: cl_command_queue,
+    src_buffer: cl_mem,
+    dst_buffer: cl_mem,
+    src_origin: *const size_t,
+    dst_origin: *const size_t,
+    region: *const size_t,
+    src_row_pitch: size_t,
+    src_slice_pitch: size_t,
+    dst_row_pitch: size_t,
+    dst_slice_pitch: size_t,
+    num_events_in_wait_list: cl_uint,
+    event_wait_list: *const cl_event,
+    event: *mut cl_event,
+) -> cl_int {
+    call("clEnqueueCopyBufferRect", |state| {
+        state.queue(command_queue)?;
+        let wait_list = state.wait_list(num_events_in_wait_list, event_wait_list)?;
+        let (src_origin, dst_origin) = (read_triple(src_origin)?, read_triple(dst_origin)?);
+        let region = read_triple(region)?;
+        let src_rows = rect_rows(src_origin, region, src_row_pitch, src_slice_pitch)?;
+        let dst_rows = rect_rows(dst_origin, region, dst_row_pitch, dst_slice_pitch)?;
+
+        let src = state.mem(src_buffer)?;
+        let mut copied = Vec::with_capacity(src_rows.len());
+        for src_row in src_rows {
+            check_range(src.len(), src_row, region[0])?;
+            copied.push(src[src_row..src_row + region[0]].to_vec());
+        }
+
+        let dst = state.mem(dst_buffer)?;
+        for (dst_row, row) in dst_rows.into_iter().zip(copied) {
+            check_range(dst.len(), dst_row, region[0])?;
+            dst[dst_row..dst_row + region[0]].copy_from_slice(&row);
+        }
+
+        let command = MockCommand::CopyBufferRect {
+            src: src_buffer,
+            dst: dst_buffer,
+            src_origin,
+            dst_origin,
+            region,
+        };
+        state.record(command_queue, wait_list, command, event)
+    })
+}
+
+unsafe extern "system" fn clEnqueueMapBuffer(
+    command_queue: cl_command_queue,
+    buffer: cl_mem,
+    _blocking_map: cl_bool,
+    map_flags: cl_map_flags,
+    offset: size_t,
+    size: size_t,
+    num_events_in_wait_list: cl_uint,
+    event_wait_list: *const cl_event,
+    event: *mut cl_event,
+    errorcode_ret: *mut cl_int,
+) -> *mut c_void {
+    create("clEnqueueMapBuffer", errorcode_ret, |state| {
+        state.queue(command_queue)?;
+        let wait_list = state.wait_list(num_events_in_wait_list, event_wait_list)?;
+        let data = state.mem(buffer)?;
+        check_range(data.len(), offset, size)?;
+
+        // the host storage of a mock buffer never moves, hence it can be handed out directly
+        let ptr = data.as_mut_ptr().add(offset);
+
+        let command = MockCommand::MapBuffer {
+            buffer,
+            flags: map_flags,
+            offset,
+            size,
+        };
+        state.record(command_queue, wait_list, command, event)?;
+        Ok(ptr as usize)
+    })
+}
+
+unsafe extern "system" fn clEnqueueUnmapMemObject(
+    command_queue: cl_command_queue,
+    memobj: cl_mem,
+    mapped_ptr: *mut c_void,
+    num_events_in_wait_list: cl_uint,
+    event_wait_list: *const cl_event,
+    event: *mut cl_event,
+) -> cl_int {
+    call("clEnqueueUnmapMemObject", |state| {
+        state.queue(command_queue)?;
+        let wait_list = state.wait_list(num_events_in_wait_list, event_wait_list)?;
+        let data = state.mem(memobj)?;
+
+        let offset = (mapped_ptr as usize).wrapping_sub(data.as_ptr() as usize);
+        if offset > data.len() {
+            return Err(INVALID_VALUE);
+        }
+
+        let command = MockCommand::UnmapMemObject {
+            buffer: memobj,
+            offset,
+        };
+        state.record(command_queue, wait_list, command, event)
+    })
+}
+
+unsafe extern "system" fn clEnqueueBarrierWithWaitList(
+    command_queue: cl_command_queue,
+    num_events_in_wait_list: cl_uint,
+    event_wait_list: *const cl_event,
+    event: *mut cl_event,
+) -> cl_int {
+    call("clEnqueueBarrierWithWaitList", |state| {
+        state.queue(command_queue)?;
+        let wait_list = state.wait_list(num_events_in_wait_list, event_wait_list)?;
+        state.record(command_queue, wait_list, MockCommand::Barrier, event)
+    })
+}
+
+unsafe extern "system" fn clEnqueueFillBuffer(
+    command_queue: cl_command_queue,
+    buffer: cl_mem,
+    pattern: *const c_void,
+    pattern_size: size_t,
+    offset: size_t,
+    size: size_t,
+    num_events_in_wait_list: cl_uint,
+    event_wait_list: *const cl_event,
+    event: *mut cl_event,
+) -> cl_int {
+    call("clEnqueueFillBuffer", |state| {
+        state.queue(command_queue)?;
+        let wait_list = state.wait_list(num_events_in_wait_list, event_wait_list)?;
+        if pattern.is_null()
+            || !pattern_size.is_power_of_two()
+            || pattern_size > 128
+            || !offset.is_multiple_of(pattern_size)
+            || !size.is_multiple_of(pattern_size)
+        {
+            return Err(INVALID_VALUE);
+        }
+        let data = state.mem(buffer)?;
+        check_range(data.len(), offset, size)?;
+
+        let pattern = std::slice::from_raw_parts(pattern as *const u8, pattern_size).to_vec();
+        for chunk in data[offset..offset + size].chunks_exact_mut(pattern_size) {
+            chunk.copy_from_slice(&pattern);
+        }
+
+        let command = MockCommand::FillBuffer {
+            buffer,
+            pattern,
+            offset,
+            size,
+        };
+        state.record(command_queue, wait_list, command, event)
+    })
+}
+
+unsafe extern "system" fn clGetSupportedImageFormats(
+    context: cl_context,
+    _flags: cl_mem_flags,
+    image_type: cl_mem_object_type,
+    num_entries: cl_uint,
+    image_formats: *mut cl_image_format,
+    num_image_formats: *mut cl_uint,
+) -> cl_int {
+    call("clGetSupportedImageFormats", |state| {
+        state.context(context)?;
+        if !matches!(
+            image_type,
+            MEM_OBJECT_IMAGE2D | MEM_OBJECT_IMAGE3D | MEM_OBJECT_IMAGE2D_ARRAY
+        ) || (num_entries == 0 && !image_formats.is_null())
+        {
+            return Err(INVALID_VALUE);
+        }
+        if !image_formats.is_null() {
+            for (idx, &(order, data_type, _)) in
+                IMAGE_FORMATS.iter().take(num_entries as usize).enumerate()
+            {
+                *image_formats.add(idx) = cl_image_format {
+                    image_channel_order: order,
+                    image_channel_data_type: data_type,
+                };
+            }
+        }
+        if !num_image_formats.is_null() {
+            *num_image_formats = IMAGE_FORMATS.len() as cl_uint;
+        }
+        Ok(())
+    })
+}
+
+unsafe extern "system" fn clCreateImage(
+    context: cl_context,
+    flags: cl_mem_flags,
+    image_format: *const cl_image_format,
+    image_desc: *const cl_image_desc,
+    host_ptr: *mut c_void,
+    errcode_ret: *mut cl_int,
+) -> cl_mem {
+    create("clCreateImage", errcode_ret, |state| {
+        state.context(context)?;
+        if image_format.is_null() {
+            return Err(INVALID_IMAGE_FORMAT_DESCRIPTOR);
+        }
+        if image_desc.is_null() {
+            return Err(INVALID_IMAGE_DESCRIPTOR);
+        }
+        let (format, desc) = (*image_format, *image_desc);
+        let pixel_size = IMAGE_FORMATS
+            .iter()
+            .find(|(order, data_type, _)| {
+                *order == format.image_channel_order && *data_type == format.image_channel_data_type
+            })
+            .map(|&(_, _, pixel_size)| pixel_size)
+            .ok_or(IMAGE_FORMAT_NOT_SUPPORTED)?;
+
+        let (width, height) = (desc.image_width, desc.image_height);
+        let (size, max_size) = match desc.image_type {
+            MEM_OBJECT_IMAGE2D => ([width, height, 1], [IMAGE2D_MAX_SIZE, IMAGE2D_MAX_SIZE, 1]),
+            MEM_OBJECT_IMAGE3D => ([width, height, desc.image_depth], [IMAGE3D_MAX_SIZE; 3]),
+            MEM_OBJECT_IMAGE2D_ARRAY => (
+                [width, height, desc.image_array_size],
+                [IMAGE2D_MAX_SIZE, IMAGE2D_MAX_SIZE, IMAGE_MAX_ARRAY_SIZE],
+            ),
+            _ => return Err(INVALID_IMAGE_DESCRIPTOR),
+        };
+        if size
+            .iter()
+            .zip(max_size)
+            .any(|(&len, max)| len == 0 || len > max)
+        {
+            return Err(INVALID_IMAGE_SIZE);
+        }
+        let uses_host_ptr = flags & (MEM_USE_HOST_PTR | MEM_COPY_HOST_PTR) != 0;
+        if uses_host_ptr == host_ptr.is_null() {
+            return Err(INVALID_HOST_PTR);
+        }
+
+        let row_size = size[0] * pixel_size;
+        let mut data = vec![0u8; row_size * size[1] * size[2]].into_boxed_slice();
+        if uses_host_ptr {
+            let host_rows = rect_rows(
+                [0; 3],
+                [row_size, size[1], size[2]],
+                desc.image_row_pitch,
+                desc.image_slice_pitch,
+            )?;
+            for (row, host_row) in data.chunks_exact_mut(row_size).zip(host_rows) {
+                std::ptr::copy_nonoverlapping(
+                    (host_ptr as *const u8).add(host_row),
+                    row.as_mut_ptr(),
+                    row_size,
+                );
+            }
+        }
+        Ok(state.insert(Object::Image(MockImage {
+            context: context as usize,
+            format,
+            pixel_size,
+            size,
+            data,
+        })))
+    })
+}
+
+unsafe extern "system" fn clEnqueueReadImage(
+    command_queue: cl_command_queue,
+    image: cl_mem,
+    _blocking_read: cl_bool,
+    origin: *const size_t,
+    region: *const size_t,
+    row_pitch: size_t,
+    slice_pitch: size_t,
+    ptr: *mut c_void,
+    num_events_in_wait_list: cl_uint,
+    event_wait_list: *const cl_event,
+    event: *mut cl_event,
+) -> cl_int {
+    call("clEnqueueReadImage", |state| {
+        state.queue(command_queue)?;
+        let wait_list = state.wait_list(num_events_in_wait_list, event_wait_list)?;
+        let (origin, region) = (read_triple(origin)?, read_triple(region)?);
+        if ptr.is_null() {
+            return Err(INVALID_VALUE);
+        }
+
+        let img = state.image(image)?;
+        let image_rows = image_rows(img, origin, region)?;
+        let row_size = region[0] * img.pixel_size;
+        let host_rows = rect_rows(
+            [0; 3],
+            [row_size, region[1], region[2]],
+            row_pitch,
+            slice_pitch,
+        )?;
+        for (image_row, host_row) in image_rows.into_iter().zip(host_rows) {
+            let dst = (ptr as *mut u8).add(host_row);
+            std::ptr::copy_nonoverlapping(img.data.as_ptr().add(image_row), dst, row_size);
+        }
+
+        let command = MockCommand::ReadImage {
+            image,
+            origin,
+            region,
+        };
+        state.record(command_queue, wait_list, command, event)
+    })
+}
+
+unsafe extern "system" fn clEnqueueWriteImage(
+    command_queue: cl_command_queue,
+    image: cl_mem,
+    _blocking_write: cl_bool,
+    origin: *const size_t,
+    region: *const size_t,
+    input_row_pitch: size_t,
+    input_slice_pitch: size_t,
+    ptr: *const c_void,
+    num_events_in_wait_list: cl_uint,
+    event_wait_list: *const cl_event,
+    event: *mut cl_event,
+) -> cl_int {
+    call("clEnqueueWriteImage", |state| {
+        state.queue(command_queue)?;
+        let wait_list = state.wait_list(num_events_in_wait_list, event_wait_list)?;
+        let (origin, region) = (read_triple(origin)?, read_triple(region)?);
+        if ptr.is_null() {
+            return Err(INVALID_VALUE);
+        }
+
+        let img = state.image(image)?;
+        let image_rows = image_rows(img, origin, region)?;
+        let row_size = region[0] * img.pixel_size;
+        let host_rows = rect_rows(
+            [0; 3],
+            [row_size, region[1], region[2]],
+            input_row_pitch,
+            input_slice_pitch,
+        )?;
+        for (image_row, host_row) in image_rows.into_iter().zip(host_rows) {
+            let src = (ptr as *const u8).add(host_row);
+            std::ptr::copy_nonoverlapping(src, img.data.as_mut_ptr().add(image_row), row_size);
+        }
+
+        let command = MockCommand::WriteImage {
+            image,
+            origin,
+            region,
+        };
+        state.record(command_queue, wait_list, command, event)
+    })
+}
+
+unsafe extern "system" fn clEnqueueCopyImage(
+    command_queue: cl_command_queue,
+    src_image: cl_mem,
+    dst_image: cl_mem,
+    src_origin: *const size_t,
+    dst_origin: *const size_t,
+    region: *const size_t,
+    num_events_in_wait_list: cl_uint,
+    event_wait_list: *const cl_event,
+    event: *mut cl_event,
+) -> cl_int {
+    call("clEnqueueCopyImage", |state| {
+        state.queue(command_queue)?;
+        let wait_list = state.wait_list(num_events_in_wait_list, event_wait_list)?;
+        let (src_origin, dst_origin) = (read_triple(src_origin)?, read_triple(dst_origin)?);
+        let region = read_triple(region)?;
+
+        let src = state.image(src_image)?;
+        let (format, row_size) = (src.format, region[0] * src.pixel_size);
+        let copied = image_rows(src, src_origin, region)?
+            .into_iter()
+            .map(|row| src.data[row..row + row_size].to_vec())
+            .collect::<Vec<_>>();
+
+        let dst = state.image(dst_image)?;
+        if dst.format != format {
+            return Err(IMAGE_FORMAT_MISMATCH);
+        }
+        let dst_rows = image_rows(dst, dst_origin, region)?;
+        let overlaps = (0..3).all(|axis| {
+            src_origin[axis] < dst_origin[axis] + region[axis]
+                && dst_origin[axis] < src_origin[axis] + region[axis]
+        });
+        if src_image == dst_image && overlaps {
+            return Err(MEM_COPY_OVERLAP);
+        }
+        for (dst_row, row) in dst_rows.into_iter().zip(copied) {
+            dst.data[dst_row..dst_row + row_size].copy_from_slice(&row);
+        }
+
+        let command = MockCommand::CopyImage {
+            src: src_image,
+            dst: dst_image,
+            src_origin,
+            dst_origin,
+            region,
+        };
+        state.record(command_queue, wait_list, command, event)
+    })
+}
+
+unsafe extern "system" fn clEnqueueCopyImageToBuffer(
+    command_queue: cl_command_queue,
+    src_image: cl_mem,
+    dst_buffer: cl_mem,
+    src_origin: *const size_t,
+    region: *const size_t,
+    dst_offset: size_t,
+    num_events_in_wait_list: cl_uint,
+    event_wait_list: *const cl_event,
+    event: *mut cl_event,
+) -> cl_int {
+    call("clEnqueueCopyImageToBuffer", |state| {
+        state.queue(command_queue)?;
+        let wait_list = state.wait_list(num_events_in_wait_list, event_wait_list)?;
+        let (src_origin, region) = (read_triple(src_origin)?, read_triple(region)?);
+
+        let src = state.image(src_image)?;
+        let row_size = region[0] * src.pixel_size;
+        let copied = image_rows(src, src_origin, region)?
+            .into_iter()
+            .flat_map(|row| src.data[row..row + row_size].to_vec())
+            .collect::<Vec<_>>();
+
+        let dst = state.mem(dst_buffer)?;
+        check_range(dst.len(), dst_offset, copied.len())?;
+        dst[dst_offset..dst_offset + copied.len()].copy_from_slice(&copied);
+
+        let command = MockCommand::CopyImageToBuffer {
+            src: src_image,
+            dst: dst_buffer,
+            src_origin,
+            region,
+            dst_offset,
+        };
+        state.record(command_queue, wait_list, command, event)
+    })
+}
+
+unsafe extern "system" fn clEnqueueCopyBufferToImage(
+    command_queue: cl_command_queue,
+    src_buffer: cl_mem,
+    dst_image: cl_mem,
+    src_offset: size_t,
+    dst_origin: *const size_t,
+    region: *const size_t,
+    num_events_in_wait_list: cl_uint,
+    event_wait_list: *const cl_event,
+    event: *mut cl_event,
+) -> cl_int {
+    call("clEnqueueCopyBufferToImage", |state| {
+        state.queue(command_queue)?;
+        let wait_list = state.wait_list(num_events_in_wait_list, event_wait_list)?;
+        let (dst_origin, region) = (read_triple(dst_origin)?, read_triple(region)?);
+
+        let dst = state.image(dst_image)?;
+        let row_size = region[0] * dst.pixel_size;
+        let dst_rows = image_rows(dst, dst_origin, region)?;
+
+        let size = row_size * dst_rows.len();
+        let src = state.mem(src_buffer)?;
+        check_range(src.len(), src_offset, size)?;
+        let copied = src[src_offset..src_offset + size].to_vec();
+
+        let dst = state.image(dst_image)?;
+        for (dst_row, row) in dst_rows.into_iter().zip(copied.chunks_exact(row_size)) {
+            dst.data[dst_row..dst_row + row_size].copy_from_slice(row);
+        }
+
+        let command = MockCommand::CopyBufferToImage {
+            src: src_buffer,
+            dst: dst_image,
+            src_offset,
+            dst_origin,
+            region,
+        };
+        state.record(command_queue, wait_list, command, event)
+    })
+}
+
+unsafe extern "system" fn clCreateSampler(
+    context: cl_context,
+    _normalized_coords: cl_bool,
+    addressing_mode: cl_addressing_mode,
+    filter_mode: cl_filter_mode,
+    errcode_ret: *mut cl_int,
+) -> cl_sampler {
+    create("clCreateSampler", errcode_ret, |state| {
+        state.context(context)?;
+        if !(0x1130..=0x1134).contains(&addressing_mode)
+            || !(0x1140..=0x1141).contains(&filter_mode)
+        {
+            return Err(INVALID_VALUE);
+        }
+        Ok(state.insert(Object::Sampler))
+    })
+}
+
+unsafe extern "system" fn clRetainSampler(sampler: cl_sampler) -> cl_int {
+    call("clRetainSampler", |state| {
+        state.check(sampler, |o| matches!(o, Object::Sampler), INVALID_SAMPLER)?;
+        state.retain(sampler);
+        Ok(())
+    })
+}
+
+unsafe extern "system" fn clReleaseSampler(sampler: cl_sampler) -> cl_int {
+    call("clReleaseSampler", |state| {
+        state.check(sampler, |o| matches!(o, Object::Sampler), INVALID_SAMPLER)?;
+        state.release(sampler);
+        Ok(())
+    })
+}
+
+unsafe extern "system" fn clSVMAlloc(
+    context: cl_context,
+    flags: cl_svm_mem_flags,
+    size: size_t,
+    alignment: cl_uint,
+) -> *mut c_void {
+    // clSVMAlloc reports every error with a null pointer
+    if take_injected("clSVMAlloc").is_some() {
+        return std::ptr::null_mut();
+    }
+    let mut state = state();
+    let alignment = (alignment as usize).max(SVM_DEFAULT_ALIGNMENT);
+    if state.context(context).is_err()
+        || size == 0
+        || size as u64 > MAX_MEM_ALLOC_SIZE
+        || !alignment.is_power_of_two()
+        || (flags & MEM_SVM_ATOMICS != 0 && flags & MEM_SVM_FINE_GRAIN_BUFFER == 0)
+    {
+        return std::ptr::null_mut();
+    }
+
+    let layout = Layout::from_size_align(size, alignment).unwrap();
+    let ptr = std::alloc::alloc_zeroed(layout);
+    if !ptr.is_null() {
+        state.svm.insert(ptr as usize, (context as usize, layout));
+    }
+    ptr as *mut c_void
+}
+
+unsafe extern "system" fn clSVMFree(_context: cl_context, svm_pointer: *mut c_void) {
+    if let Some((_, layout)) = state().svm.remove(&(svm_pointer as usize)) {
+        std::alloc::dealloc(svm_pointer as *mut u8, layout);
+    }
+}
+
+unsafe extern "system" fn clEnqueueSVMMap(
+    command_queue: cl_command_queue,
+    _blocking_map: cl_bool,
+    flags: cl_map_flags,
+    svm_ptr: *mut c_void,
+    size: size_t,
+    num_events_in_wait_list: cl_uint,
+    event_wait_list: *const cl_event,
+    event: *mut cl_event,
+) -> cl_int {
+    call("clEnqueueSVMMap", |state| {
+        state.queue(command_queue)?;
+        let wait_list = state.wait_list(num_events_in_wait_list, event_wait_list)?;
+        if size == 0 {
+            return Err(INVALID_VALUE);
+        }
+        state.svm_range(svm_ptr, size)?;
+
+        let command = MockCommand::SvmMap {
+            ptr: svm_ptr,
+            flags,
+            size,
+        };
+        state.record(command_queue, wait_list, command, event)
+    })
+}
+
+unsafe extern "system" fn clEnqueueSVMUnmap(
+    command_queue: cl_command_queue,
+    svm_ptr: *mut c_void,
+    num_events_in_wait_list: cl_uint,
+    event_wait_list: *const cl_event,
+    event: *mut cl_event,
+) -> cl_int {
+    call("clEnqueueSVMUnmap", |state| {
+        state.queue(command_queue)?;
+        let wait_list = state.wait_list(num_events_in_wait_list, event_wait_list)?;
+        state.svm_range(svm_ptr, 0)?;
+
+        let command = MockCommand::SvmUnmap { ptr: svm_ptr };
+        state.record(command_queue, wait_list, command, event)
+    })
+}
+
+unsafe extern "system" fn clEnqueueSVMMemcpy(
+    command_queue: cl_command_queue,
+    _blocking_copy: cl_bool,
+    dst_ptr: *mut c_void,
+    src_ptr: *const c_void,
+    size: size_t,
+    num_events_in_wait_list: cl_uint,
+    event_wait_list: *const cl_event,
+    event: *mut cl_event,
+) -> cl_int {
+    call("clEnqueueSVMMemcpy", |state| {
+        state.queue(command_queue)?;
+        let wait_list = state.wait_list(num_events_in_wait_list, event_wait_list)?;
+        if dst_ptr.is_null() || src_ptr.is_null() {
+            return Err(INVALID_VALUE);
+        }
+        let (dst, src) = (dst_ptr as usize, src_ptr as usize);
+        if src < dst + size && dst < src + size {
+            return Err(MEM_COPY_OVERLAP);
+        }
+        // either side may be host memory, hence only the pointers are checked
+        std::ptr::copy_nonoverlapping(src_ptr as *const u8, dst_ptr as *mut u8, size);
+
+        let command = MockCommand::SvmMemcpy {
+            dst: dst_ptr,
+            src: src_ptr,
+            size,
+        };
+        state.record(command_queue, wait_list, command, event)
+    })
+}
+
+unsafe extern "system" fn clEnqueueSVMMemFill(
+    command_queue: cl_command_queue,
+    svm_ptr: *mut c_void,
+    pattern: *const c_void,
+    pattern_size: size_t,
+    size: size_t,
+    num_events_in_wait_list: cl_uint,
+    event_wait_list: *const cl_event,
+    event: *mut cl_event,
+) -> cl_int {
+    call("clEnqueueSVMMemFill", |state| {
+        state.queue(command_queue)?;
+        let wait_list = state.wait_list(num_events_in_wait_list, event_wait_list)?;
+        if pattern.is_null()
+            || !pattern_size.is_power_of_two()
+            || pattern_size > 128
+            || !(svm_ptr as usize).is_multiple_of(pattern_size)
+            || !size.is_multiple_of(pattern_size)
+        {
+            return Err(INVALID_VALUE);
+        }
+        state.svm_range(svm_ptr, size)?;
+
+        let pattern = std::slice::from_raw_parts(pattern as *const u8, pattern_size).to_vec();
+        let data = std::slice::from_raw_parts_mut(svm_ptr as *mut u8, size);
+        for chunk in data.chunks_exact_mut(pattern_size) {
+            chunk.copy_from_slice(&pattern);
+        }
+
+        let command = MockCommand::SvmMemFill {
+            ptr: svm_ptr,
+            pattern,
+            size,
+        };
+        state.record(command_queue, wait_list, command, event)
+    })
+}
+
+unsafe extern "system" fn clReleaseProgram(program: cl_program) -> cl_int {
+    call("clReleaseProgram", |state| {
+        state.program(program)?;
+        state.release(program);
+        Ok(())
+    })
+}
+
+unsafe extern "system" fn clGetProgramInfo(
+    program: cl_program,
+    param_name: cl_program_info,
+    param_value_size: size_t,
+    param_value: *mut c_void,
+    param_value_size_ret: *mut size_t,
+) -> cl_int {
+    call("clGetProgramInfo", |state| {
+        let program = state.program(program)?;
+        let value = match param_name {
+            // CL_PROGRAM_CONTEXT
+            0x1161 => bytes_of(&[program.context]),
+            // CL_PROGRAM_NUM_DEVICES
+            0x1162 => bytes_of(&[1 as cl_uint]),
+            // CL_PROGRAM_DEVICES
+            0x1163 => bytes_of(&[DEVICE]),
+            // CL_PROGRAM_SOURCE
+            0x1164 => string_info(&program.source),
+            // CL_PROGRAM_BINARY_SIZES
+            0x1165 => bytes_of(&[binary(program).len()]),
+            // CL_PROGRAM_BINARIES: param_value is an array of pointers to host memory
+            0x1166 => {
+                let binary = binary(program);
+                if !param_value.is_null() {
+                    if param_value_size < std::mem::size_of::<*mut u8>() {
+                        return Err(INVALID_VALUE);
+                    }
+                    let dst = *(param_value as *const *mut u8);
+                    if !dst.is_null() {
+                        std::ptr::copy_nonoverlapping(binary.as_ptr(), dst, binary.len());
+                    }
+                }
+                if !param_value_size_ret.is_null() {
+                    *param_value_size_ret = std::mem::size_of::<*mut u8>();
+                }
+                return Ok(());
+            }
+            // CL_PROGRAM_NUM_KERNELS
+            0x1167 => bytes_of(&[program.kernels.len()]),
+            // CL_PROGRAM_KERNEL_NAMES
+            0x1168 => string_info(
+                &program
+                    .kernels
+                    .iter()
+                    .map(|decl| decl.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(";"),
+            ),
+            _ => return Err(INVALID_VALUE),
+        };
+        write_info(&value, param_value_size, param_value, param_value_size_ret)
+    })
+}
+
+unsafe extern "system" fn clCreateProgramWithSource(
+    context: cl_context,
+    count: cl_uint,
+    strings: *const *const c_char,
+    lengths: *const size_t,
+    errcode_ret: *mut cl_int,
+) -> cl_program {
+    create("clCreateProgramWithSource", errcode_ret, |state| {
+        state.context(context)?;
+        if count == 0 || strings.is_null() {
+            return Err(INVALID_VALUE);
+        }
+
+        let mut source = String::new();
+        for idx in 0..count as usize {
+            let string = *strings.add(idx);
+            let len = if lengths.is_null() {
+                0
+            } else {
+                *lengths.add(idx)
+            };
+            let bytes = if len == 0 {
+                CStr::from_ptr(string).to_bytes()
+            } else {
+                std::slice::from_raw_parts(string as *const u8, len)
+            };
+            source.push_str(&String::from_utf8_lossy(bytes));
+        }
+
+        Ok(state.insert(Object::Program(MockProgram {
+            context: context as usize,
+            kernels: parse_kernels(&source),
+            source,
+            options: String::new(),
+            status: BUILD_NONE,
+            log: String::new(),
+        })))
+    })
+}
+
+unsafe extern "system" fn clGetProgramBuildInfo(
+    program: cl_program,
+    device: cl_device_id,
+    param_name: cl_program_build_info,
+    param_value_size: size_t,
+    param_value: *mut c_void,
+    param_value_size_ret: *mut size_t,
+) -> cl_int {
+    call("clGetProgramBuildInfo", |state| {
+        let program = state.program(program)?;
+        if device as usize != DEVICE {
+            return Err(INVALID_DEVICE);
+        }
+        let value = match param_name {
+            0x1181 => bytes_of(&[program.status]),
+            0x1182 => string_info(&program.options),
+            0x1183 => string_info(&program.log),
+            _ => return Err(INVALID_VALUE),
+        };
+        write_info(&value, param_value_size, param_value, param_value_size_ret)
+    })
+}
+
+unsafe extern "system" fn clBuildProgram(
+    program: cl_program,
+    num_devices: cl_uint,
+    device_list: *const cl_device_id,
+    options: *const c_char,
+    _pfn_notify: *mut c_void,
+    _user_data: *mut c_void,
+) -> cl_int {
+    call("clBuildProgram", |state| {
+        let program = state.program(program)?;
+        if (num_devices == 0) != device_list.is_null() {
+            return Err(INVALID_VALUE);
+        }
+        if num_devices != 0
+            && std::slice::from_raw_parts(device_list, num_devices as usize)
+                .iter()
+                .any(|&device| device as usize != DEVICE)
+        {
+            return Err(INVALID_DEVICE);
+        }
+        program.options = if options.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(options).to_string_lossy().into_owned()
+        };
+        build(program)
+    })
+}
+
+unsafe extern "system" fn clCreateKernel(
+    program: cl_program,
+    kernel_name: *const c_char,
+    errcode_ret: *mut cl_int,
+) -> cl_kernel {
+    create("clCreateKernel", errcode_ret, |state| {
+        let program = state.program(program)?;
+        if program.status != BUILD_SUCCESS {
+            return Err(INVALID_PROGRAM_EXECUTABLE);
+        }
+        if kernel_name.is_null() {
+            return Err(INVALID_VALUE);
+        }
+        let name = CStr::from_ptr(kernel_name).to_string_lossy();
+        let decl = program
+            .kernels
+            .iter()
+            .find(|decl| decl.name == name)
+            .cloned()
+            .ok_or(INVALID_KERNEL_NAME)?;
+        Ok(state.insert(Object::Kernel(MockKernel::new(decl))))
+    })
+}
+
+unsafe extern "system" fn clCreateKernelsInProgram(
+    program: cl_program,
+    num_kernels: cl_uint,
+    kernels: *mut cl_kernel,
+    num_kernels_ret: *mut cl_uint,
+) -> cl_int {
+    call("clCreateKernelsInProgram", |state| {
+        let program = state.program(program)?;
+        if program.status != BUILD_SUCCESS {
+            return Err(INVALID_PROGRAM_EXECUTABLE);
+        }
+        let program_kernels = program.kernels.clone();
+
+        if !kernels.is_null() {
+            if (num_kernels as usize) < program_kernels.len() {
+                return Err(INVALID_VALUE);
+            }
+            for (idx, decl) in program_kernels.iter().enumerate() {
+                let kernel = state.insert(Object::Kernel(MockKernel::new(decl.clone())));
+                *kernels.add(idx) = kernel as cl_kernel;
+            }
+        }
+        if !num_kernels_ret.is_null() {
+            *num_kernels_ret = program_kernels.len() as cl_uint;
+        }
+        Ok(())
+    })
+}
+
+unsafe extern "system" fn clReleaseKernel(kernel: cl_kernel) -> cl_int {
+    call("clReleaseKernel", |state| {
+        state.kernel(kernel)?;
+        state.release(kernel);
+        Ok(())
+    })
+}
+
+unsafe extern "system" fn clGetKernelInfo(
+    kernel: cl_kernel,
+    param_name: cl_kernel_info,
+    param_value_size: size_t,
+    param_value: *mut c_void,
+    param_value_size_ret: *mut size_t,
+) -> cl_int {
+    call("clGetKernelInfo", |state| {
+        let kernel = state.kernel(kernel)?;
+        let value = match param_name {
+            // CL_KERNEL_FUNCTION_NAME
+            0x1190 => string_info(&kernel.name),
+            // CL_KERNEL_NUM_ARGS
+            0x1191 => (kernel.args.len() as cl_uint).to_ne_bytes().to_vec(),
+            _ => return Err(INVALID_VALUE),
+        };
+        write_info(&value, param_value_size, param_value, param_value_size_ret)
+    })
+}
+
+unsafe extern "system" fn clSetKernelArg(
+    kernel: cl_kernel,
+    arg_index: cl_uint,
+    arg_size: size_t,
+    arg_value: *const c_void,
+) -> cl_int {
+    call("clSetKernelArg", |state| {
+        let kernel = state.kernel(kernel)?;
+        let param = *kernel
+            .params
+            .get(arg_index as usize)
+            .ok_or(INVALID_ARG_INDEX)?;
+        if param.local != arg_value.is_null() {
+            return Err(INVALID_ARG_VALUE);
+        }
+        if param.size.is_some_and(|size| size != arg_size) || arg_size == 0 {
+            return Err(INVALID_ARG_SIZE);
+        }
+
+        kernel.local_mem[arg_index as usize] = if param.local { arg_size } else { 0 };
+        // a null value with a non-zero size declares local memory, which has no host contents
+        kernel.args[arg_index as usize] = Some(if arg_value.is_null() {
+            Vec::new()
+        } else {
+            std::slice::from_raw_parts(arg_value as *const u8, arg_size).to_vec()
+        });
+        Ok(())
+    })
+}
+
+unsafe extern "system" fn clSetKernelArgSVMPointer(
+    kernel: cl_kernel,
+    arg_index: cl_uint,
+    arg_value: *const c_void,
+) -> cl_int {
+    call("clSetKernelArgSVMPointer", |state| {
+        state.svm_range(arg_value, 0)?;
+        let kernel = state.kernel(kernel)?;
+        let arg = kernel
+            .args
+            .get_mut(arg_index as usize)
+            .ok_or(INVALID_ARG_INDEX)?;
+        // host implementations read the pointer with `MockLaunch::scalar`
+        *arg = Some(bytes_of(&[arg_value]));
+        Ok(())
+    })
+}
+
+unsafe extern "system" fn clGetKernelWorkGroupInfo(
+    kernel: cl_kernel,
+    device: cl_device_id,
+    param_name: cl_kernel_work_group_info,
+    param_value_size: size_t,
+    param_value: *mut c_void,
+    param_value_size_ret: *mut size_t,
+) -> cl_int {
+    call("clGetKernelWorkGroupInfo", |state| {
+        let kernel = state.kernel(kernel)?;
+        if device as usize != DEVICE {
+            return Err(INVALID_DEVICE);
+        }
+        let value = match param_name {
+            CL_KERNEL_WORK_GROUP_SIZE => bytes_of(&[MAX_WORK_GROUP_SIZE]),
+            CL_KERNEL_COMPILE_WORK_GROUP_SIZE => bytes_of(&kernel.reqd_work_group_size),
+            CL_KERNEL_LOCAL_MEM_SIZE => bytes_of(&[kernel.local_mem.iter().sum::<usize>() as u64]),
+            CL_KERNEL_PREFERRED_WORK_GROUP_SIZE_MULTIPLE => {
+                bytes_of(&[PREFERRED_WORK_GROUP_SIZE_MULTIPLE])
+            }
+            CL_KERNEL_PRIVATE_MEM_SIZE => bytes_of(&[0u64]),
+            _ => return Err(INVALID_VALUE),
+        };
+        let value = truncated("clGetKernelWorkGroupInfo", &value);
+        write_info(value, param_value_size, param_value, param_value_size_ret)
+    })
+}
+
+unsafe extern "system" fn clEnqueueNDRangeKernel(
+    command_queue: cl_command_queue,
+    kernel: cl_kernel,
+    work_dim: cl_uint,
+    global_work_offset: *const size_t,
+    global_work_dims: *const size_t,
+    local_work_dims: *const size_t,
+    num_events_in_wait_list: cl_uint,
+    event_wait_list: *const cl_event,
+    event: *mut cl_event,
+) -> cl_int {
+    call("clEnqueueNDRangeKernel", |state| {
+        state.queue(command_queue)?;
+        let wait_list = state.wait_list(num_events_in_wait_list, event_wait_list)?;
+        let mock_kernel = state.kernel(kernel)?;
+
+        if !(1..=3).contains(&work_dim) {
+            return Err(INVALID_WORK_DIMENSION);
+        }
+        let dims = |ptr: *const size_t| {
+            (!ptr.is_null()).then(|| std::slice::from_raw_parts(ptr, work_dim as usize).to_vec())
+        };
+        let global_work_size = dims(global_work_dims).ok_or(INVALID_GLOBAL_WORK_SIZE)?;
+        if global_work_size.contains(&0) {
+            return Err(INVALID_GLOBAL_WORK_SIZE);
+        }
+        let local_work_size = dims(local_work_dims);
+        if let Some(lws) = &local_work_size {
+            let divides = global_work_size
+                .iter()
+                .zip(lws)
+                .all(|(g, l)| *l != 0 && g % l == 0);
+            if !divides || lws.iter().product::<usize>() > MAX_WORK_GROUP_SIZE {
+                return Err(INVALID_WORK_GROUP_SIZE);
+            }
+        }
+        let reqd = mock_kernel.reqd_work_group_size;
+        if reqd != [0; 3] && local_work_size.as_deref() != Some(&reqd[..work_dim as usize]) {
+            return Err(INVALID_WORK_GROUP_SIZE);
+        }
+        if mock_kernel.args.iter().any(Option::is_none) {
+            return Err(INVALID_KERNEL_ARGS);
+        }
+
+        let name = mock_kernel.name.clone();
+        let args = mock_kernel.args.clone();
+
+        if let Some(f) = state.kernel_fns.get(&name).cloned() {
+            f(&mut MockLaunch {
+                state,
+                args,
+                global_work_size: global_work_size.clone(),
+            });
+        }
+
+        let command = MockCommand::NDRangeKernel {
+            kernel: name,
+            global_work_size,
+            local_work_size,
+            global_work_offset: dims(global_work_offset),
+        };
+        state.record(command_queue, wait_list, command, event)
+    })
+}
+
+unsafe extern "system" fn clCreateProgramWithBinary(
+    context: cl_context,
+    num_devices: cl_uint,
+    device_list: *const cl_device_id,
+    lenghts: *const usize,
+    binaries: *const *const u8,
+    binary_status: &mut cl_int,
+    errcode_ret: &mut cl_int,
+) -> cl_program {
+    create("clCreateProgramWithBinary", errcode_ret, |state| {
+        state.context(context)?;
+        if num_devices != 1 || device_list.is_null() || lenghts.is_null() || binaries.is_null() {
+            return Err(INVALID_VALUE);
+        }
+        if *device_list as usize != DEVICE {
+            return Err(INVALID_DEVICE);
+        }
+
+        let binary = std::slice::from_raw_parts(*binaries, *lenghts);
+        let Some(source) = binary.strip_prefix(MOCK_BINARY_MAGIC) else {
+            *binary_status = INVALID_BINARY;
+            return Err(INVALID_BINARY);
+        };
+        *binary_status = SUCCESS;
+
+        let source = String::from_utf8_lossy(source).into_owned();
+        Ok(state.insert(Object::Program(MockProgram {
+            context: context as usize,
+            kernels: parse_kernels(&source),
+            source,
+            options: String::new(),
+            status: BUILD_NONE,
+            log: String::new(),
+        })))
+    })
+}
+
+fn functions() -> Functions {
+    Functions {
+        clGetPlatformIDs: Some(clGetPlatformIDs),
+        clGetPlatformInfo: Some(clGetPlatformInfo),
+        clGetDeviceIDs: Some(clGetDeviceIDs),
+        clGetDeviceInfo: Some(clGetDeviceInfo),
+        clCreateContext: Some(clCreateContext),
+        clRetainContext: Some(clRetainContext),
+        clReleaseContext: Some(clReleaseContext),
+        clGetContextInfo: Some(clGetContextInfo),
+        clCreateCommandQueue: Some(clCreateCommandQueue),
+        clFlush: Some(clFlush),
+        clFinish: Some(clFinish),
+        clRetainCommandQueue: Some(clRetainCommandQueue),
+        clReleaseCommandQueue: Some(clReleaseCommandQueue),
+        clWaitForEvents: Some(clWaitForEvents),
+        clRetainEvent: Some(clRetainEvent),
+        clReleaseEvent: Some(clReleaseEvent),
+        clGetEventInfo: Some(clGetEventInfo),
+        clSetEventCallback: Some(clSetEventCallback),
+        clGetEventProfilingInfo: Some(clGetEventProfilingInfo),
+        clCreateBuffer: Some(clCreateBuffer),
+        clCreateSubBuffer: Some(clCreateSubBuffer),
+        clGetMemObjectInfo: Some(clGetMemObjectInfo),
+        clRetainMemObject: Some(clRetainMemObject),
+        clReleaseMemObject: Some(clReleaseMemObject),
+        clEnqueueReadBuffer: Some(clEnqueueReadBuffer),
+        clEnqueueWriteBuffer: Some(clEnqueueWriteBuffer),
+        clEnqueueCopyBuffer: Some(clEnqueueCopyBuffer),
+        clEnqueueReadBufferRect: Some(clEnqueueReadBufferRect),
+        clEnqueueWriteBufferRect: Some(clEnqueueWriteBufferRect),
+        clEnqueueCopyBufferRect: Some(clEnqueueCopyBufferRect),
+        clEnqueueMapBuffer: Some(clEnqueueMapBuffer),
+        clEnqueueUnmapMemObject: Some(clEnqueueUnmapMemObject),
+        clEnqueueBarrierWithWaitList: Some(clEnqueueBarrierWithWaitList),
+        clEnqueueFillBuffer: Some(clEnqueueFillBuffer),
+        clGetSupportedImageFormats: Some(clGetSupportedImageFormats),
+        clCreateImage: Some(clCreateImage),
+        clEnqueueReadImage: Some(clEnqueueReadImage),
+        clEnqueueWriteImage: Some(clEnqueueWriteImage),
+        clEnqueueCopyImage: Some(clEnqueueCopyImage),
+        clEnqueueCopyImageToBuffer: Some(clEnqueueCopyImageToBuffer),
+        clEnqueueCopyBufferToImage: Some(clEnqueueCopyBufferToImage),
+        clCreateSampler: Some(clCreateSampler),
+        clRetainSampler: Some(clRetainSampler),
+        clReleaseSampler: Some(clReleaseSampler),
+        clSVMAlloc: Some(clSVMAlloc),
+        clSVMFree: Some(clSVMFree),
+        clEnqueueSVMMap: Some(clEnqueueSVMMap),
+        clEnqueueSVMUnmap: Some(clEnqueueSVMUnmap),
+        clEnqueueSVMMemcpy: Some(clEnqueueSVMMemcpy),
+        clEnqueueSVMMemFill: Some(clEnqueueSVMMemFill),
+        clReleaseProgram: Some(clReleaseProgram),
+        clGetProgramInfo: Some(clGetProgramInfo),
+        clCreateProgramWithSource: Some(clCreateProgramWithSource),
+        clGetProgramBuildInfo: Some(clGetProgramBuildInfo),
+        clBuildProgram: Some(clBuildProgram),
+        clCreateKernel: Some(clCreateKernel),
+        clCreateKernelsInProgram: Some(clCreateKernelsInProgram),
+        clReleaseKernel: Some(clReleaseKernel),
+        clGetKernelInfo: Some(clGetKernelInfo),
+        clSetKernelArg: Some(clSetKernelArg),
+        clSetKernelArgSVMPointer: Some(clSetKernelArgSVMPointer),
+        clGetKernelWorkGroupInfo: Some(clGetKernelWorkGroupInfo),
+        clEnqueueNDRangeKernel: Some(clEnqueueNDRangeKernel),
+        clCreateProgramWithBinary: Some(clCreateProgramWithBinary),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        api::{
+            build_program, create_buffer, create_kernel, create_program_with_source,
+            enqueue_read_buffer, enqueue_write_buffer, set_kernel_arg, MemFlags, OCLErrorKind,
+        },
+        CLDevice, Error,
+    };
+
+    use super::{fail_next, install, mem_object_count, records, register_kernel, MockCommand};
+
+    #[test]
+    fn test_mock_write_read() -> Result<(), Error> {
+        install()?;
+        let device = CLDevice::new(0)?;
+
+        let buf =
+            unsafe { create_buffer::<i32>(&device.ctx, MemFlags::MemReadWrite as u64, 4, None)? };
+        unsafe { enqueue_write_buffer(&device.queue, buf, &[1, 2, 3, 4], true, None)? };
+
+        let mut out = [0i32; 4];
+        unsafe { enqueue_read_buffer(&device.queue, buf, &mut out, true, None)? };
+        assert_eq!(out, [1, 2, 3, 4]);
+
+        let records = records(device.queue.0);
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[1].command,
+            MockCommand::ReadBuffer {
+                buffer: buf,
+                offset: 0,
+                size: 16
+            }
+        );
+
+        assert_eq!(mem_object_count(device.ctx.0), 1);
+        unsafe { crate::api::release_mem_object(buf)? };
+        assert_eq!(mem_object_count(device.ctx.0), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mock_injected_error() -> Result<(), Error> {
+        install()?;
+        let device = CLDevice::new(0)?;
+
+        fail_next("clCreateBuffer", OCLErrorKind::OutOfResources);
+        let err =
+            unsafe { create_buffer::<f32>(&device.ctx, MemFlags::MemReadWrite as u64, 10, None) }
+                .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<OCLErrorKind>(),
+            Some(&OCLErrorKind::OutOfResources)
+        );
+
+        // only the next call fails
+        let buf =
+            unsafe { create_buffer::<f32>(&device.ctx, MemFlags::MemReadWrite as u64, 10, None)? };
+        unsafe { crate::api::release_mem_object(buf)? };
+        Ok(())
+    }
+
+    #[test]
+    fn test_mock_kernel() -> Result<(), Error> {
+        install()?;
+        let device = CLDevice::new(0)?;
+
+        register_kernel("mock_double", |launch| {
+            let values = launch.read::<f32>(0);
+            let doubled = values.iter().map(|x| x * 2.).collect::<Vec<_>>();
+            launch.write(0, &doubled);
+        });
+
+        let program = unsafe {
+            create_program_with_source(
+                &device.ctx,
+                "__kernel void mock_double(__global float* values) {}",
+            )?
+        };
+        unsafe { build_program(&program, &[device.device], None)? };
+        let kernel = create_kernel(&program, "mock_double")?;
+
+        let buf = unsafe {
+            create_buffer(
+                &device.ctx,
+                MemFlags::MemReadWrite | MemFlags::MemCopyHostPtr,
+                3,
+                Some(&[1f32, 2., 3.]),
+            )?
+        };
+        unsafe { set_kernel_arg(&kernel, 0, buf, std::mem::size_of::<*mut u8>(), false)? };
+        device.enqueue_nd_range_kernel(&kernel, 1, &[3, 0, 0], None, None)?;
+
+        let mut out = [0f32; 3];
+        unsafe { device.enqueue_read_buffer(buf, &mut out, true)? };
+        assert_eq!(out, [2., 4., 6.]);
+
+        unsafe { crate::api::release_mem_object(buf)? };
+        Ok(())
+    }
+}
@@ -1,7 +1,13 @@
 pub use cl::*;
 pub use error::*;
 pub use ffi::*;
+#[cfg(any(test, feature = "dynamic"))]
+pub use loader::*;
 
 mod cl;
 mod error;
 pub mod ffi;
+#[cfg(any(test, feature = "dynamic"))]
+mod loader;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
@@ -0,0 +1,458 @@
+use std::{
+    ffi::c_void,
+    fmt::Debug,
+    future::{Future, IntoFuture},
+    marker::PhantomData,
+    ops::{Bound, Range, RangeBounds},
+    pin::Pin,
+    task::{self, ready, Poll},
+};
+
+use crate::{
+    api::{
+        cl_mem, create_buffer, create_sub_buffer, get_mem_object_devices, release_mem_object,
+        retain_mem_object, Context, Event, EventFuture, MemFlags, OCLErrorKind,
+    },
+    CLDevice, Dependencies, Error,
+};
+
+/// Types that can be copied to and from device memory byte by byte.
+///
+/// # Safety
+/// Every bit pattern must be a valid value of the type and the type must not contain padding or pointers.
+pub unsafe trait Pod: Copy + Send + Sync + 'static {}
+
+macro_rules! impl_pod {
+    ($($t:ty),*) => {
+        $(unsafe impl Pod for $t {})*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64, usize, isize);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// An OpenCL buffer that holds `len` elements of type `T`.
+///
+/// The buffer is released on drop. Cloning a buffer retains the memory object, hence the clone refers to the same device memory.
+pub struct Buffer<T> {
+    ptr: cl_mem,
+    len: usize,
+    /// The buffer this sub-buffer was created from, retained for the lifetime of the sub-buffer.
+    parent: Option<cl_mem>,
+    /// Element offset into `parent`
+    offset: usize,
+    /// Shared with clones and sub-buffers, as they alias the same allocation.
+    deps: Dependencies,
+    _p: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for Buffer<T> {}
+unsafe impl<T: Sync> Sync for Buffer<T> {}
+
+impl<T> Buffer<T> {
+    /// Allocates an uninitialized buffer with `len` elements.
+    pub fn new(device: &CLDevice, len: usize) -> Result<Buffer<T>, Error> {
+        Buffer::with_flags(device.ctx(), MemFlags::MemReadWrite as u64, len, None)
+    }
+
+    /// Allocates a buffer that is initialized with the contents of `data`.
+    pub fn from_slice(device: &CLDevice, data: &[T]) -> Result<Buffer<T>, Error>
+    where
+        T: Pod,
+    {
+        Buffer::with_flags(
+            device.ctx(),
+            MemFlags::MemReadWrite | MemFlags::MemCopyHostPtr,
+            data.len(),
+            Some(data),
+        )
+    }
+
+    /// Allocates a buffer with the given `MemFlags`.
+    /// If `data` is `Some`, `flags` must contain `MemCopyHostPtr` and `data` must hold `len` elements.
+    ///
+    /// `MemUseHostPtr` is rejected with `OCLErrorKind::InvalidHostPtr`, as the buffer would outlive the borrow of `data`.
+    pub fn with_flags(
+        ctx: &Context,
+        flags: u64,
+        len: usize,
+        data: Option<&[T]>,
+    ) -> Result<Buffer<T>, Error> {
+        check_host_ptr_flags(flags, data.is_some())?;
+        if let Some(data) = data {
+            if data.len() != len {
+                return Err(OCLErrorKind::BufferLengthMismatch.into());
+            }
+        }
+        let ptr = unsafe { create_buffer(ctx, flags, len, data)? };
+        Ok(Buffer {
+            ptr,
+            len,
+            parent: None,
+            offset: 0,
+            deps: Dependencies::default(),
+            _p: PhantomData,
+        })
+    }
+
+    /// Creates a sub-buffer that aliases the elements of `range` without copying them.
+    /// The sub-buffer keeps the parent allocation alive.
+    ///
+    /// The byte offset of the range must be a multiple of `CL_DEVICE_MEM_BASE_ADDR_ALIGN` of every device of the context,
+    /// otherwise `OCLErrorKind::MisalignedSubBufferOffset` is returned.
+    pub fn sub_buffer(&self, range: impl RangeBounds<usize>) -> Result<Buffer<T>, Error> {
+        let range = self.range(range)?;
+        if range.is_empty() {
+            return Err(OCLErrorKind::InvalidBufferRange.into());
+        }
+
+        // sub-buffers cannot be created from sub-buffers, hence the root allocation is used
+        let parent = self.parent.unwrap_or(self.ptr);
+        let offset = self.offset + range.start;
+
+        let mut align_bits = 0;
+        for device in unsafe { get_mem_object_devices(parent)? } {
+            align_bits = align_bits.max(device.get_mem_base_addr_align()? as usize);
+        }
+        let align = (align_bits / 8).max(1);
+        if !(offset * std::mem::size_of::<T>()).is_multiple_of(align) {
+            return Err(OCLErrorKind::MisalignedSubBufferOffset.into());
+        }
+
+        let ptr = unsafe { create_sub_buffer::<T>(parent, 0, offset, range.len())? };
+        if let Err(err) = unsafe { retain_mem_object(parent) } {
+            unsafe { release_mem_object(ptr)? };
+            return Err(err);
+        }
+        Ok(Buffer {
+            ptr,
+            len: range.len(),
+            parent: Some(parent),
+            offset,
+            deps: self.deps.clone(),
+            _p: PhantomData,
+        })
+    }
+
+    /// Element offset into the parent allocation, `0` if this is not a sub-buffer.
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    #[inline]
+    pub fn is_sub_buffer(&self) -> bool {
+        self.parent.is_some()
+    }
+
+    /// Number of elements of the buffer.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Resolves `range` to element indices of the buffer.
+    /// Returns `OCLErrorKind::InvalidBufferRange` if the range is reversed or out of bounds.
+    pub fn range(&self, range: impl RangeBounds<usize>) -> Result<Range<usize>, Error> {
+        resolve_range(range, self.len)
+    }
+
+    /// The underlying `cl_mem` object. It stays valid as long as the buffer is alive.
+    #[inline]
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.ptr
+    }
+
+    /// The commands that access the buffer, see [`CLDevice::enqueue_with_deps`].
+    #[inline]
+    pub fn dependencies(&self) -> &Dependencies {
+        &self.deps
+    }
+}
+
+/// Resolves `range` to element indices of an allocation with `len` elements.
+pub(crate) fn resolve_range(
+    range: impl RangeBounds<usize>,
+    len: usize,
+) -> Result<Range<usize>, Error> {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start
+            .checked_add(1)
+            .ok_or(OCLErrorKind::InvalidBufferRange)?,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end.checked_add(1).ok_or(OCLErrorKind::InvalidBufferRange)?,
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+    if start > end || end > len {
+        return Err(OCLErrorKind::InvalidBufferRange.into());
+    }
+    Ok(start..end)
+}
+
+/// The device must not access host memory after a memory object is created, hence `data` can only be copied.
+pub(crate) fn check_host_ptr_flags(flags: u64, has_data: bool) -> Result<(), Error> {
+    let use_host_ptr = flags & MemFlags::MemUseHostPtr as u64 != 0;
+    let copy_host_ptr = flags & MemFlags::MemCopyHostPtr as u64 != 0;
+    if use_host_ptr || has_data != copy_host_ptr {
+        return Err(OCLErrorKind::InvalidHostPtr.into());
+    }
+    Ok(())
+}
+
+/// A non-blocking transfer that borrows the host memory for `'a`.
+/// Dropping the transfer waits for its completion, use [`PendingTransfer::wait`] to handle errors.
+/// Awaiting the transfer waits without blocking the thread.
+#[must_use = "dropping a pending transfer blocks until it is finished"]
+pub struct PendingTransfer<'a> {
+    /// `None` once the transfer is finished
+    future: Option<EventFuture>,
+    _p: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> PendingTransfer<'a> {
+    /// # Safety
+    /// The host memory of the transfer must be borrowed for `'a`.
+    pub unsafe fn new(event: Event) -> PendingTransfer<'a> {
+        PendingTransfer {
+            future: Some(event.into_future()),
+            _p: PhantomData,
+        }
+    }
+
+    /// The event of the transfer, e.g. for profiling.
+    ///
+    /// # Panics
+    /// If the transfer was awaited to completion.
+    pub fn event(&self) -> &Event {
+        self.future.as_ref().unwrap().event()
+    }
+
+    /// Blocks until the transfer is finished.
+    pub fn wait(mut self) -> Result<(), Error> {
+        match self.future.take() {
+            Some(future) => future.into_event().wait(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Future for PendingTransfer<'_> {
+    type Output = Result<(), Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let future = self
+            .future
+            .as_mut()
+            .expect("pending transfer polled after completion");
+        let result = ready!(Pin::new(&mut *future).poll(cx));
+        // if registering the callback failed, the transfer may still be running and dropping must wait
+        if future.is_terminated() {
+            self.future = None;
+        }
+        Poll::Ready(result)
+    }
+}
+
+impl Drop for PendingTransfer<'_> {
+    fn drop(&mut self) {
+        if let Some(future) = self.future.take() {
+            // a failed wait must not panic in drop, `PendingTransfer::wait` reports the error instead
+            let _ = future.into_event().wait();
+        }
+    }
+}
+
+impl<T> Clone for Buffer<T> {
+    /// Retains the memory object, the clone refers to the same device memory.
+    ///
+    /// # Panics
+    /// If the OpenCL implementation fails to retain the memory object, e.g. when it runs out of host memory.
+    fn clone(&self) -> Self {
+        unsafe {
+            retain_mem_object(self.ptr).expect("could not retain the buffer");
+            if let Some(parent) = self.parent {
+                retain_mem_object(parent).expect("could not retain the parent buffer");
+            }
+        }
+        Buffer {
+            ptr: self.ptr,
+            len: self.len,
+            parent: self.parent,
+            offset: self.offset,
+            deps: self.deps.clone(),
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<T> Drop for Buffer<T> {
+    fn drop(&mut self) {
+        // a failed release leaks the memory object, which is preferable to a panic in drop
+        unsafe {
+            let _ = release_mem_object(self.ptr);
+            if let Some(parent) = self.parent {
+                let _ = release_mem_object(parent);
+            }
+        }
+    }
+}
+
+impl<T> Debug for Buffer<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Buffer")
+            .field("ptr", &self.ptr)
+            .field("len", &self.len)
+            .field("parent", &self.parent)
+            .field("offset", &self.offset)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        api::{mock, MemFlags, OCLErrorKind},
+        Buffer, CLDevice, Error,
+    };
+
+    #[test]
+    fn test_buffer_read_write() -> Result<(), Error> {
+        mock::install()?;
+        let device = CLDevice::new(0)?;
+
+        let mut buf = Buffer::<f32>::new(&device, 4)?;
+        device.write(&mut buf, &[1., 2., 3., 4.])?;
+        assert_eq!(device.read(&buf)?, [1., 2., 3., 4.]);
+
+        let mut copy = Buffer::from_slice(&device, &[0f32; 4])?;
+        device.copy_from(&mut copy, &buf)?;
+        assert_eq!(device.read(&copy)?, [1., 2., 3., 4.]);
+
+        let err = device.write(&mut buf, &[1., 2.]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<OCLErrorKind>(),
+            Some(&OCLErrorKind::BufferLengthMismatch)
+        );
+
+        // dropping a transfer whose wait fails does not panic
+        let mut data = [0f32; 4];
+        let transfer = unsafe { device.read_at_async(&buf, 0, &mut data)? };
+        mock::fail_next("clWaitForEvents", OCLErrorKind::OutOfResources);
+        drop(transfer);
+        Ok(())
+    }
+
+    #[test]
+    fn test_buffer_host_ptr_flags() -> Result<(), Error> {
+        mock::install()?;
+        let device = CLDevice::new(0)?;
+        let data = [1f32; 4];
+
+        for (flags, data) in [
+            (
+                MemFlags::MemReadWrite | MemFlags::MemUseHostPtr,
+                Some(&data[..]),
+            ),
+            (MemFlags::MemReadWrite | MemFlags::MemUseHostPtr, None),
+            (
+                MemFlags::MemReadWrite | MemFlags::MemAllocHostPtr,
+                Some(&data[..]),
+            ),
+            (MemFlags::MemReadWrite | MemFlags::MemCopyHostPtr, None),
+        ] {
+            let err = Buffer::with_flags(device.ctx(), flags, 4, data).unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<OCLErrorKind>(),
+                Some(&OCLErrorKind::InvalidHostPtr)
+            );
+        }
+
+        let flags = MemFlags::MemAllocHostPtr | MemFlags::MemCopyHostPtr;
+        let buf = Buffer::with_flags(device.ctx(), flags, 4, Some(&data[..]))?;
+        assert_eq!(device.read(&buf)?, data);
+        Ok(())
+    }
+
+    #[test]
+    fn test_buffer_release() -> Result<(), Error> {
+        mock::install()?;
+        let device = CLDevice::new(0)?;
+
+        let buf = Buffer::<i32>::new(&device, 10)?;
+        let clone = buf.clone();
+        assert_eq!(buf.as_ptr(), clone.as_ptr());
+        assert_eq!(mock::mem_object_count(device.ctx.0), 1);
+
+        drop(buf);
+        assert_eq!(mock::mem_object_count(device.ctx.0), 1);
+
+        drop(clone);
+        assert_eq!(mock::mem_object_count(device.ctx.0), 0);
+
+        // a failed release does not panic on drop
+        let buf = Buffer::<i32>::new(&device, 10)?;
+        mock::fail_next("clReleaseMemObject", OCLErrorKind::InvalidMemObject);
+        drop(buf);
+        assert_eq!(mock::mem_object_count(device.ctx.0), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sub_buffer() -> Result<(), Error> {
+        mock::install()?;
+        let device = CLDevice::new(0)?;
+
+        let data = (0..64).map(|x| x as f32).collect::<Vec<_>>();
+        let buf = Buffer::from_slice(&device, &data)?;
+
+        // 32 * 4 bytes, a multiple of the 128 byte alignment of the mock device
+        let mut sub = buf.sub_buffer(32..)?;
+        assert!(sub.is_sub_buffer());
+        assert_eq!((sub.len(), sub.offset()), (32, 32));
+        assert_eq!(device.read(&sub)?, &data[32..]);
+
+        device.write(&mut sub, &[1.; 32])?;
+        let read = device.read(&buf)?;
+        assert_eq!(read[..32], data[..32]);
+        assert_eq!(read[32..], [1.; 32]);
+
+        // nested sub-buffers refer to the root allocation
+        let nested = sub.sub_buffer(..=31)?;
+        assert_eq!((nested.len(), nested.offset()), (32, 32));
+        assert_eq!(device.read(&nested)?, [1.; 32]);
+        drop(nested);
+
+        for range in [1..4, 33..34] {
+            assert_eq!(
+                buf.sub_buffer(range)
+                    .unwrap_err()
+                    .downcast_ref::<OCLErrorKind>(),
+                Some(&OCLErrorKind::MisalignedSubBufferOffset)
+            );
+        }
+        assert_eq!(
+            buf.sub_buffer(32..32)
+                .unwrap_err()
+                .downcast_ref::<OCLErrorKind>(),
+            Some(&OCLErrorKind::InvalidBufferRange)
+        );
+
+        // the sub-buffer keeps the parent alive
+        drop(buf);
+        assert_eq!(mock::mem_object_count(device.ctx.0), 2);
+        assert_eq!(device.read(&sub)?, [1.; 32]);
+        drop(sub);
+        assert_eq!(mock::mem_object_count(device.ctx.0), 0);
+        Ok(())
+    }
+}
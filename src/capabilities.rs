@@ -0,0 +1,248 @@
+use crate::{
+    api::{
+        get_device_info, CLIntDevice, CommandQueueProperties, DeviceInfo, FpConfig, LocalMemType,
+        OCLErrorKind,
+    },
+    Error,
+};
+
+/// Preferred or native vector widths of the device for each scalar type.
+/// A width of `0` means that the type is not supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VectorWidths {
+    pub char: u32,
+    pub short: u32,
+    pub int: u32,
+    pub long: u32,
+    pub float: u32,
+    pub double: u32,
+    pub half: u32,
+}
+
+impl VectorWidths {
+    /// `params` are the info parameters of char, short, int, long, float, double and half in this order.
+    /// Widths that are unknown to the device (e.g. the native widths before OpenCL 1.1) are `0`.
+    fn query(device: CLIntDevice, params: [DeviceInfo; 7]) -> Result<Self, Error> {
+        let mut widths = [0; 7];
+        for (width, param) in widths.iter_mut().zip(params) {
+            *width = optional(get_device_info(device, param).map(|info| info.size as u32))?;
+        }
+        let [char, short, int, long, float, double, half] = widths;
+        Ok(VectorWidths {
+            char,
+            short,
+            int,
+            long,
+            float,
+            double,
+            half,
+        })
+    }
+}
+
+/// A snapshot of the capabilities of an OpenCL device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceCapabilities {
+    pub name: String,
+    pub vendor: String,
+    pub vendor_id: u32,
+    pub version: String,
+    pub driver_version: String,
+    pub opencl_c_version: String,
+    pub profile: String,
+    /// `DeviceType` bitfield
+    pub device_type: u64,
+    pub extensions: Vec<String>,
+    pub max_compute_units: u32,
+    /// in MHz
+    pub max_clock_frequency: u32,
+    pub address_bits: u32,
+    pub max_work_group_size: usize,
+    pub max_work_item_sizes: Vec<usize>,
+    pub global_mem_size: u64,
+    pub max_mem_alloc_size: u64,
+    pub global_mem_cache_size: u64,
+    pub global_mem_cacheline_size: u32,
+    pub max_constant_buffer_size: u64,
+    pub local_mem_size: u64,
+    pub local_mem_type: LocalMemType,
+    /// in bits
+    pub mem_base_addr_align: u32,
+    pub unified_mem: bool,
+    pub image_support: bool,
+    pub endian_little: bool,
+    /// `FpConfig` bitfields, `0` if the precision is not supported
+    pub single_fp_config: u64,
+    pub double_fp_config: u64,
+    pub half_fp_config: u64,
+    pub preferred_vector_widths: VectorWidths,
+    pub native_vector_widths: VectorWidths,
+    /// in nanoseconds
+    pub profiling_timer_resolution: usize,
+    /// `SvmCapability` bitfield, `0` if the device does not support OpenCL 2.0
+    pub svm_capabilities: u64,
+    /// `CommandQueueProperties` bitfield
+    pub queue_properties: u64,
+}
+
+impl DeviceCapabilities {
+    pub fn query(device: CLIntDevice) -> Result<DeviceCapabilities, Error> {
+        Ok(DeviceCapabilities {
+            name: device.get_name()?,
+            vendor: device.get_vendor()?,
+            vendor_id: device.get_vendor_id()?,
+            version: device.get_version()?,
+            driver_version: device.get_driver_version()?,
+            opencl_c_version: optional(device.get_opencl_c_version())?,
+            profile: device.get_profile()?,
+            device_type: device.get_device_type()?,
+            extensions: device.get_extensions()?,
+            max_compute_units: device.get_max_compute_units()?,
+            max_clock_frequency: device.get_max_clock_frequency()?,
+            address_bits: device.get_address_bits()?,
+            max_work_group_size: device.get_max_work_group_size()?,
+            max_work_item_sizes: device.get_max_work_item_sizes()?,
+            global_mem_size: device.get_global_mem()?,
+            max_mem_alloc_size: device.get_max_mem_alloc()?,
+            global_mem_cache_size: device.get_global_mem_cache_size()?,
+            global_mem_cacheline_size: device.get_global_mem_cacheline_size()?,
+            max_constant_buffer_size: device.get_max_constant_buffer_size()?,
+            local_mem_size: device.get_local_mem_size()?,
+            local_mem_type: device.get_local_mem_type()?,
+            mem_base_addr_align: device.get_mem_base_addr_align()?,
+            unified_mem: device.unified_mem()?,
+            image_support: device.image_support()?,
+            endian_little: device.endian_little()?,
+            single_fp_config: device.get_single_fp_config()?,
+            double_fp_config: optional(device.get_double_fp_config())?,
+            half_fp_config: optional(device.get_half_fp_config())?,
+            preferred_vector_widths: VectorWidths::query(
+                device,
+                [
+                    DeviceInfo::PreferredVectorWidthChar,
+                    DeviceInfo::PreferredVectorWidthShort,
+                    DeviceInfo::PreferredVectorWidthInt,
+                    DeviceInfo::PreferredVectorWidthLong,
+                    DeviceInfo::PreferredVectorWidthFloat,
+                    DeviceInfo::PreferredVectorWidthDouble,
+                    DeviceInfo::PreferredVectorWidthHalf,
+                ],
+            )?,
+            native_vector_widths: VectorWidths::query(
+                device,
+                [
+                    DeviceInfo::NativeVectorWidthChar,
+                    DeviceInfo::NativeVectorWidthShort,
+                    DeviceInfo::NativeVectorWidthInt,
+                    DeviceInfo::NativeVectorWidthLong,
+                    DeviceInfo::NativeVectorWidthFloat,
+                    DeviceInfo::NativeVectorWidthDouble,
+                    DeviceInfo::NativeVectorWidthHalf,
+                ],
+            )?,
+            profiling_timer_resolution: device.get_profiling_timer_resolution()?,
+            svm_capabilities: optional(device.get_svm_capabilities())?,
+            queue_properties: device.get_queue_properties()?,
+        })
+    }
+
+    #[inline]
+    pub fn supports_double(&self) -> bool {
+        self.double_fp_config != 0
+    }
+
+    #[inline]
+    pub fn supports_half(&self) -> bool {
+        self.half_fp_config != 0
+    }
+
+    #[inline]
+    pub fn supports_out_of_order(&self) -> bool {
+        self.queue_properties & CommandQueueProperties::OutOfOrderExecModeEnable as u64 != 0
+    }
+
+    #[inline]
+    pub fn supports_fma(&self) -> bool {
+        FpConfig::Fma.is_set(self.single_fp_config)
+    }
+
+    pub fn has_extension(&self, extension: &str) -> bool {
+        self.extensions.iter().any(|ext| ext == extension)
+    }
+}
+
+// Parameters that are missing on older devices or without the corresponding extension are reported as `InvalidValue`.
+pub(crate) fn optional<T: Default>(value: Result<T, Error>) -> Result<T, Error> {
+    match value {
+        Err(err) if err.downcast_ref() == Some(&OCLErrorKind::InvalidValue) => Ok(T::default()),
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VectorWidths;
+    use crate::{
+        api::{mock, DeviceInfo, DeviceReturnInfo, LocalMemType, OCLErrorKind},
+        CLDevice, Error,
+    };
+
+    #[test]
+    fn test_device_return_info_decoding() {
+        let info = DeviceReturnInfo::new(256u32.to_ne_bytes().to_vec());
+        assert_eq!(info.size, 256);
+
+        let info = DeviceReturnInfo::new(0x1234_5678_9abcu64.to_ne_bytes().to_vec());
+        assert_eq!(info.size, 0x1234_5678_9abc);
+
+        let info = DeviceReturnInfo::new(b"name\0".to_vec());
+        assert_eq!(info.string, "name");
+
+        let sizes = [1usize, 2, 3]
+            .iter()
+            .flat_map(|size| size.to_ne_bytes())
+            .collect();
+        assert_eq!(DeviceReturnInfo::new(sizes).usizes(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_capabilities() -> Result<(), Error> {
+        mock::install()?;
+        let device = CLDevice::new(0)?;
+        let caps = device.capabilities()?;
+
+        assert_eq!(caps.name, "min-cl mock device");
+        assert_eq!(caps.max_compute_units, 8);
+        assert_eq!(caps.max_work_group_size, 256);
+        assert_eq!(caps.max_work_item_sizes, [256, 256, 64]);
+        assert_eq!(caps.local_mem_size, 32 * 1024);
+        assert_eq!(caps.local_mem_type, LocalMemType::Local);
+        assert_eq!(caps.global_mem_size, 1024 * 1024 * 1024);
+        assert_eq!(caps.preferred_vector_widths.float, 4);
+        assert_eq!(caps.native_vector_widths.char, 4);
+        assert!(caps.has_extension("cl_khr_fp64"));
+        assert!(caps.supports_double());
+        assert!(!caps.supports_half());
+        assert!(caps.supports_fma());
+        assert!(caps.supports_out_of_order());
+        assert!(caps.endian_little);
+
+        // OpenCL 1.0 devices reject the native widths
+        mock::fail_next("clGetDeviceInfo", OCLErrorKind::InvalidValue);
+        let widths = VectorWidths::query(
+            device.device(),
+            [
+                DeviceInfo::NativeVectorWidthChar,
+                DeviceInfo::NativeVectorWidthShort,
+                DeviceInfo::NativeVectorWidthInt,
+                DeviceInfo::NativeVectorWidthLong,
+                DeviceInfo::NativeVectorWidthFloat,
+                DeviceInfo::NativeVectorWidthDouble,
+                DeviceInfo::NativeVectorWidthHalf,
+            ],
+        )?;
+        assert_eq!(widths.char, 0);
+        assert_eq!(widths.float, caps.native_vector_widths.float);
+        Ok(())
+    }
+}
@@ -1,16 +1,27 @@
-use std::{cell::RefCell, ffi::c_void, fmt::Debug, time::Duration};
+use std::{
+    ffi::c_void,
+    fmt::Debug,
+    ops::RangeBounds,
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
 
 use crate::{
     api::{
-        create_command_queue, create_context, enqueue_full_copy_buffer, enqueue_map_buffer,
-        enqueue_nd_range_kernel, enqueue_read_buffer, enqueue_write_buffer, get_device_ids,
-        get_platforms, wait_for_events, CLIntDevice, CommandQueue, Context, DeviceType, Event,
-        Kernel, OCLErrorKind, Platform,
+        create_command_queue, create_command_queue_with_properties, create_context,
+        enqueue_barrier, enqueue_copy_buffer_rect, enqueue_fill_buffer, enqueue_full_copy_buffer,
+        enqueue_map_buffer, enqueue_nd_range_kernel, enqueue_read_buffer_at,
+        enqueue_read_buffer_rect, enqueue_write_buffer_at, enqueue_write_buffer_rect,
+        find_platform, finish, get_device_ids, get_platforms, CLIntDevice, CommandQueue,
+        CommandQueueProperties, Context, DeviceType, Event, Kernel, MapFlags, OCLErrorKind,
+        Platform, RectLayout,
     },
     init_devices,
     kernel_cache::KernelCache,
     measure_perf::measure_perf,
-    Error, DEVICES,
+    Access, Buffer, Dependencies, DeviceCapabilities, DeviceSelector, Error, PendingTransfer, Pod,
+    TraceRecorder, DEVICES,
 };
 
 pub fn all_devices() -> Result<Vec<Vec<CLIntDevice>>, Error> {
@@ -32,13 +43,35 @@ pub fn all_devices_of_platform(platform: Platform) -> Vec<CLIntDevice> {
 }
 
 /// Internal representation of an OpenCL Device.
+///
+/// # Concurrency
+/// A `CLDevice` is `Send` and `Sync` and can be shared between threads, e.g. via `Arc<CLDevice>`.
+/// All threads enqueue into the same command queue. The trace and the kernel cache are guarded by mutexes,
+/// and the [`Dependencies`] of the memory objects of a command stay locked while it is enqueued,
+/// so the wait lists of concurrent commands on the same memory objects agree with the order of the queue.
+///
+/// # Streams
+/// Further command queues of the device are created with [`CLDevice::create_stream`], e.g. to overlap
+/// uploads and downloads with kernels. The stream enqueues all commands called on it into its own queue.
+/// Commands on different streams or on an out-of-order queue (see [`CLDevice::enable_out_of_order`])
+/// only wait for the previous commands on the same memory objects. The queue of such a command is flushed
+/// before a command of another stream waits for it.
+///
+/// [`Kernel`]s are not `Sync`, as setting kernel arguments is not thread-safe in OpenCL.
+/// Each thread needs its own kernel objects, e.g. created with [`KernelCache::kernels`].
 pub struct CLDevice {
     pub device: CLIntDevice,
     pub ctx: Context,
     pub queue: CommandQueue,
     pub unified_mem: bool,
-    pub event_wait_list: RefCell<Vec<Event>>,
-    pub kernel_cache: RefCell<KernelCache>,
+    /// Whether the command queue records device timestamps, see [`CLDevice::enable_profiling`].
+    pub profiling: bool,
+    /// Whether the command queue executes commands out of order, see [`CLDevice::enable_out_of_order`].
+    pub out_of_order: bool,
+    /// Captures enqueued commands while a trace is running, see [`CLDevice::start_trace`].
+    pub trace: Mutex<Option<TraceRecorder>>,
+    /// Shared with the streams of the device
+    pub kernel_cache: Arc<Mutex<KernelCache>>,
 }
 
 impl Debug for CLDevice {
@@ -46,13 +79,17 @@ impl Debug for CLDevice {
         f.debug_struct("CLDevice")
             .field("name", &self.device.get_name().unwrap())
             .field("unified_mem", &self.unified_mem)
-            .field("event_wait_list", &self.event_wait_list.borrow())
+            .field("profiling", &self.profiling)
+            .field("out_of_order", &self.out_of_order)
             .finish()
     }
 }
 
-unsafe impl Sync for CLDevice {}
-unsafe impl Send for CLDevice {}
+/// Locks `mutex`. A panic while holding the lock leaves the guarded state valid, hence poisoning is ignored.
+#[inline]
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
 
 impl TryFrom<CLIntDevice> for CLDevice {
     type Error = Error;
@@ -67,7 +104,9 @@ impl TryFrom<CLIntDevice> for CLDevice {
             ctx,
             queue,
             unified_mem,
-            event_wait_list: RefCell::new(Vec::with_capacity(100)),
+            profiling: false,
+            out_of_order: false,
+            trace: Mutex::new(None),
             kernel_cache: Default::default(),
         })
     }
@@ -106,12 +145,31 @@ pub fn extract_indices_from_device_idx(device_idx: usize) -> Result<(usize, usiz
 }
 
 impl CLDevice {
+    /// Creates the device at the given indices of [`all_devices`].
     pub fn from_indices(platform_idx: usize, device_idx: usize) -> Result<CLDevice, Error> {
-        let platform = get_platforms()?[platform_idx];
-        let devices = get_device_ids(platform, &(DeviceType::GPU as u64))?;
+        let platform = *get_platforms()?
+            .get(platform_idx)
+            .ok_or(OCLErrorKind::InvalidDeviceIdx)?;
+        let device = *all_devices_of_platform(platform)
+            .get(device_idx)
+            .ok_or(OCLErrorKind::InvalidDeviceIdx)?;
+        device.try_into()
+    }
 
-        devices[device_idx].try_into()
+    /// Creates the best device matching `selector`. See [`DeviceSelector::select`].
+    pub fn select(selector: &DeviceSelector) -> Result<CLDevice, Error> {
+        selector.select()
     }
+    /// Creates the `device_idx`-th device of the first platform whose name or vendor contains `platform`, ignoring case.
+    /// GPUs and accelerators are listed before CPUs, as in [`all_devices_of_platform`].
+    pub fn from_platform(platform: &str, device_idx: usize) -> Result<CLDevice, Error> {
+        let platform = find_platform(platform)?;
+        let device = *all_devices_of_platform(platform)
+            .get(device_idx)
+            .ok_or(OCLErrorKind::InvalidDeviceIdx)?;
+        device.try_into()
+    }
+
     pub fn new(device_idx: usize) -> Result<CLDevice, Error> {
         init_devices();
 
@@ -127,6 +185,8 @@ impl CLDevice {
             .map(|(_, _, _, device)| device)?)
     }
 
+    /// Enqueues the kernel without dependencies on memory objects, hence it is ordered by the queue, see [`CLDevice::untracked`].
+    /// Use [`CLDevice::launch`] to order the kernel after the previous commands on its arguments only.
     pub fn enqueue_nd_range_kernel(
         &self,
         kernel: &Kernel,
@@ -135,26 +195,51 @@ impl CLDevice {
         lws: Option<&[usize; 3]>,
         offset: Option<[usize; 3]>,
     ) -> Result<(), Error> {
-        let event = unsafe {
-            enqueue_nd_range_kernel(
-                &self.queue,
-                kernel,
-                wd,
-                gws,
-                lws,
-                offset,
-                Some(&self.event_wait_list.borrow()),
-            )
-        }?;
+        self.untracked(|| self.enqueue_kernel_with_deps(kernel, wd, gws, lws, offset, &[]))
+    }
 
-        {
-            let mut event_wait_list = self.event_wait_list.borrow_mut();
-            event_wait_list.clear();
-            event_wait_list.push(event);
+    /// Enqueues commands whose memory objects are not tracked by [`Dependencies`].
+    /// On an out-of-order queue, they are enclosed by barriers, hence they are ordered after all previous commands
+    /// and before all following commands, as on an in-order queue.
+    pub fn untracked<R>(&self, enqueue: impl FnOnce() -> Result<R, Error>) -> Result<R, Error> {
+        if !self.out_of_order {
+            return enqueue();
         }
+        enqueue_barrier(&self.queue, None)?;
+        let result = enqueue()?;
+        enqueue_barrier(&self.queue, None)?;
+        Ok(result)
+    }
 
-        // self.wait_for_events().unwrap();
+    /// Like [`CLDevice::enqueue_nd_range_kernel`], ordered by the dependencies of the kernel arguments.
+    pub(crate) fn enqueue_kernel_with_deps(
+        &self,
+        kernel: &Kernel,
+        wd: usize,
+        gws: &[usize; 3],
+        lws: Option<&[usize; 3]>,
+        offset: Option<[usize; 3]>,
+        deps: &[(&Dependencies, Access)],
+    ) -> Result<(), Error> {
+        let event = self.enqueue_with_deps(deps, |wait_list| unsafe {
+            enqueue_nd_range_kernel(&self.queue, kernel, wd, gws, lws, offset, Some(wait_list))
+        })?;
 
+        if self.is_tracing() {
+            // the kernel is enqueued, hence tracing must not fail the launch
+            self.record(
+                kernel.name().unwrap_or_default(),
+                "kernel",
+                vec![
+                    ("global_work_size", format!("{:?}", &gws[..wd])),
+                    (
+                        "local_work_size",
+                        format!("{:?}", lws.map(|lws| &lws[..wd])),
+                    ),
+                ],
+                &event,
+            );
+        }
         Ok(())
     }
 
@@ -164,15 +249,44 @@ impl CLDevice {
         dst_slice: &mut [T],
         block: bool,
     ) -> Result<Event, Error> {
-        unsafe {
-            enqueue_read_buffer(
+        self.enqueue_read_buffer_at(src_ptr, 0, dst_slice, block)
+    }
+
+    /// Reads `dst_slice.len()` elements starting at element `offset` of the buffer.
+    /// Unlike the safe methods, the command is not ordered by the [`Dependencies`] of the buffer, see [`CLDevice::untracked`].
+    /// # Safety
+    /// `src_ptr` must be a valid buffer with at least `offset + dst_slice.len()` elements.
+    /// If `block` is false, `dst_slice` must not be accessed until the returned event completes.
+    pub unsafe fn enqueue_read_buffer_at<T>(
+        &self,
+        src_ptr: *mut c_void,
+        offset: usize,
+        dst_slice: &mut [T],
+        block: bool,
+    ) -> Result<Event, Error> {
+        self.untracked(|| self.read_buffer_after(src_ptr, offset, dst_slice, block, &[]))
+    }
+
+    unsafe fn read_buffer_after<T>(
+        &self,
+        src_ptr: *mut c_void,
+        offset: usize,
+        dst_slice: &mut [T],
+        block: bool,
+        wait_list: &[Event],
+    ) -> Result<Event, Error> {
+        let event = unsafe {
+            enqueue_read_buffer_at(
                 self.queue(),
                 src_ptr,
+                offset,
                 dst_slice,
                 block,
-                Some(&self.event_wait_list.borrow()),
+                Some(wait_list),
             )
-        }
+        }?;
+        self.record_transfer("read_buffer", std::mem::size_of_val(dst_slice), &event);
+        Ok(event)
     }
 
     pub unsafe fn enqueue_write_buffer<T>(
@@ -181,15 +295,44 @@ impl CLDevice {
         src_slice: &[T],
         block: bool,
     ) -> Result<Event, Error> {
-        unsafe {
-            enqueue_write_buffer(
+        self.enqueue_write_buffer_at(dst_ptr, 0, src_slice, block)
+    }
+
+    /// Writes `src_slice` to the buffer, starting at element `offset`.
+    /// Unlike the safe methods, the command is not ordered by the [`Dependencies`] of the buffer, see [`CLDevice::untracked`].
+    /// # Safety
+    /// `dst_ptr` must be a valid buffer with at least `offset + src_slice.len()` elements.
+    /// If `block` is false, `src_slice` must stay valid until the returned event completes.
+    pub unsafe fn enqueue_write_buffer_at<T>(
+        &self,
+        dst_ptr: *mut c_void,
+        offset: usize,
+        src_slice: &[T],
+        block: bool,
+    ) -> Result<Event, Error> {
+        self.untracked(|| self.write_buffer_after(dst_ptr, offset, src_slice, block, &[]))
+    }
+
+    unsafe fn write_buffer_after<T>(
+        &self,
+        dst_ptr: *mut c_void,
+        offset: usize,
+        src_slice: &[T],
+        block: bool,
+        wait_list: &[Event],
+    ) -> Result<Event, Error> {
+        let event = unsafe {
+            enqueue_write_buffer_at(
                 self.queue(),
                 dst_ptr,
-                &src_slice,
+                offset,
+                src_slice,
                 block,
-                Some(&self.event_wait_list.borrow()),
+                Some(wait_list),
             )
-        }
+        }?;
+        self.record_transfer("write_buffer", std::mem::size_of_val(src_slice), &event);
+        Ok(event)
     }
 
     pub unsafe fn enqueue_full_copy_buffer<T>(
@@ -198,41 +341,438 @@ impl CLDevice {
         dst_mem: *mut c_void,
         size: usize,
     ) -> Result<Event, Error> {
-        unsafe {
-            enqueue_full_copy_buffer::<T>(
-                self.queue(),
-                src_mem,
-                dst_mem,
-                size,
-                Some(&self.event_wait_list.borrow()),
-            )
-        }
+        self.untracked(|| self.copy_buffer_after::<T>(src_mem, dst_mem, size, &[]))
+    }
+
+    unsafe fn copy_buffer_after<T>(
+        &self,
+        src_mem: *mut c_void,
+        dst_mem: *mut c_void,
+        size: usize,
+        wait_list: &[Event],
+    ) -> Result<Event, Error> {
+        let event = unsafe {
+            enqueue_full_copy_buffer::<T>(self.queue(), src_mem, dst_mem, size, Some(wait_list))
+        }?;
+        self.record_transfer("copy_buffer", size * std::mem::size_of::<T>(), &event);
+        Ok(event)
     }
 
     pub unsafe fn unified_ptr<T>(&self, ptr: *mut c_void, len: usize) -> Result<*mut T, Error> {
-        unsafe {
+        self.untracked(|| unsafe {
             enqueue_map_buffer::<T>(
                 self.queue(),
                 ptr,
                 true,
-                2 | 1,
+                MapFlags::Read | MapFlags::Write,
                 0,
                 len,
-                Some(&self.event_wait_list.borrow()),
+                None,
             )
             .map(|ptr| ptr as *mut T)
+        })
+    }
+
+    /// Reads the contents of the buffer into a new `Vec`. Blocks until the read is finished.
+    pub fn read<T: Pod>(&self, buf: &Buffer<T>) -> Result<Vec<T>, Error> {
+        // Pod guarantees that zeroed memory is a valid value
+        let mut data = vec![unsafe { std::mem::zeroed::<T>() }; buf.len()];
+        self.read_into(buf, &mut data)?;
+        Ok(data)
+    }
+
+    /// Reads the contents of the buffer into `data`. Blocks until the read is finished.
+    pub fn read_into<T: Pod>(&self, buf: &Buffer<T>, data: &mut [T]) -> Result<(), Error> {
+        if data.len() != buf.len() {
+            return Err(OCLErrorKind::BufferLengthMismatch.into());
+        }
+        self.read_at(buf, 0, data)
+    }
+
+    /// Writes `data` to the buffer. Blocks until the write is finished.
+    pub fn write<T: Pod>(&self, buf: &mut Buffer<T>, data: &[T]) -> Result<(), Error> {
+        if data.len() != buf.len() {
+            return Err(OCLErrorKind::BufferLengthMismatch.into());
+        }
+        self.write_at(buf, 0, data)
+    }
+
+    /// Reads the elements of `range` into a new `Vec`. Blocks until the read is finished.
+    pub fn read_range<T: Pod>(
+        &self,
+        buf: &Buffer<T>,
+        range: impl RangeBounds<usize>,
+    ) -> Result<Vec<T>, Error> {
+        let range = buf.range(range)?;
+        let mut data = vec![unsafe { std::mem::zeroed::<T>() }; range.len()];
+        self.read_at(buf, range.start, &mut data)?;
+        Ok(data)
+    }
+
+    /// Reads `data.len()` elements starting at element `offset`. Blocks until the read is finished.
+    pub fn read_at<T: Pod>(
+        &self,
+        buf: &Buffer<T>,
+        offset: usize,
+        data: &mut [T],
+    ) -> Result<(), Error> {
+        buf.range(offset..offset.saturating_add(data.len()))?;
+        self.enqueue_with_deps(&[(buf.dependencies(), Access::Read)], |wait_list| unsafe {
+            self.read_buffer_after(buf.as_ptr(), offset, data, true, wait_list)
+        })?;
+        Ok(())
+    }
+
+    /// Writes `data` starting at element `offset`. Blocks until the write is finished.
+    pub fn write_at<T: Pod>(
+        &self,
+        buf: &mut Buffer<T>,
+        offset: usize,
+        data: &[T],
+    ) -> Result<(), Error> {
+        buf.range(offset..offset.saturating_add(data.len()))?;
+        self.enqueue_with_deps(&[(buf.dependencies(), Access::Write)], |wait_list| unsafe {
+            self.write_buffer_after(buf.as_ptr(), offset, data, true, wait_list)
+        })?;
+        Ok(())
+    }
+
+    /// Enqueues a read of `data.len()` elements starting at element `offset`.
+    /// `buf` and `data` stay borrowed until the returned transfer is finished or dropped.
+    /// # Safety
+    /// The returned transfer must not be leaked (e.g. with `std::mem::forget`), as `data` could be accessed while the device writes to it.
+    pub unsafe fn read_at_async<'a, T: Pod>(
+        &self,
+        buf: &'a Buffer<T>,
+        offset: usize,
+        data: &'a mut [T],
+    ) -> Result<PendingTransfer<'a>, Error> {
+        buf.range(offset..offset.saturating_add(data.len()))?;
+        let event =
+            self.enqueue_with_deps(&[(buf.dependencies(), Access::Read)], |wait_list| unsafe {
+                self.read_buffer_after(buf.as_ptr(), offset, data, false, wait_list)
+            })?;
+        Ok(unsafe { PendingTransfer::new(event) })
+    }
+
+    /// Enqueues a write of `data` starting at element `offset`.
+    /// `buf` and `data` stay borrowed until the returned transfer is finished or dropped.
+    /// # Safety
+    /// The returned transfer must not be leaked (e.g. with `std::mem::forget`), as `data` could be freed while the device reads from it.
+    pub unsafe fn write_at_async<'a, T: Pod>(
+        &self,
+        buf: &'a mut Buffer<T>,
+        offset: usize,
+        data: &'a [T],
+    ) -> Result<PendingTransfer<'a>, Error> {
+        buf.range(offset..offset.saturating_add(data.len()))?;
+        let event =
+            self.enqueue_with_deps(&[(buf.dependencies(), Access::Write)], |wait_list| unsafe {
+                self.write_buffer_after(buf.as_ptr(), offset, data, false, wait_list)
+            })?;
+        Ok(unsafe { PendingTransfer::new(event) })
+    }
+
+    /// Reads the contents of the buffer into a new `Vec` without blocking the thread.
+    ///
+    /// Dropping the future before the read is finished blocks until it is finished.
+    pub async fn read_async<T: Pod>(&self, buf: &Buffer<T>) -> Result<Vec<T>, Error> {
+        // Pod guarantees that zeroed memory is a valid value
+        let mut data = vec![unsafe { std::mem::zeroed::<T>() }; buf.len()];
+        // `data` is owned by the future, hence leaking the future leaks the memory the device writes to
+        unsafe { self.read_at_async(buf, 0, &mut data)? }.await?;
+        Ok(data)
+    }
+
+    /// Writes `data` to the buffer without blocking the thread.
+    /// `data` is taken by value, as the device may still read from it if the future is leaked.
+    ///
+    /// Dropping the future before the write is finished blocks until it is finished.
+    pub async fn write_async<T: Pod>(
+        &self,
+        buf: &mut Buffer<T>,
+        data: Vec<T>,
+    ) -> Result<(), Error> {
+        if data.len() != buf.len() {
+            return Err(OCLErrorKind::BufferLengthMismatch.into());
+        }
+        unsafe { self.write_at_async(buf, 0, &data)? }.await
+    }
+
+    /// Enqueues a copy of the contents of `src` to `dst`.
+    pub fn copy_from<T>(&self, dst: &mut Buffer<T>, src: &Buffer<T>) -> Result<(), Error> {
+        if dst.len() != src.len() {
+            return Err(OCLErrorKind::BufferLengthMismatch.into());
+        }
+        self.enqueue_with_deps(
+            &[
+                (dst.dependencies(), Access::Write),
+                (src.dependencies(), Access::Read),
+            ],
+            |wait_list| unsafe {
+                self.copy_buffer_after::<T>(src.as_ptr(), dst.as_ptr(), src.len(), wait_list)
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Reads the `region` (width, height, depth in elements) of the buffer into `data`. Blocks until the read is finished.
+    ///
+    /// # Example
+    /// Reads the 2x2 tile at column 1, row 2 of a 4x4 matrix:
+    /// ```ignore
+    /// let mut tile = [0f32; 4];
+    /// device.read_rect(&buf, RectLayout::matrix(4).at(1, 2), &mut tile, RectLayout::matrix(2), [2, 2, 1])?;
+    /// ```
+    pub fn read_rect<T: Pod>(
+        &self,
+        buf: &Buffer<T>,
+        buf_layout: RectLayout,
+        data: &mut [T],
+        data_layout: RectLayout,
+        region: [usize; 3],
+    ) -> Result<(), Error> {
+        buf_layout.validate(region, buf.len())?;
+        data_layout.validate(region, data.len())?;
+        self.enqueue_with_deps(&[(buf.dependencies(), Access::Read)], |wait_list| unsafe {
+            enqueue_read_buffer_rect(
+                self.queue(),
+                buf.as_ptr(),
+                buf_layout,
+                data,
+                data_layout,
+                region,
+                true,
+                Some(wait_list),
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Writes the `region` (width, height, depth in elements) of `data` to the buffer. Blocks until the write is finished.
+    pub fn write_rect<T: Pod>(
+        &self,
+        buf: &mut Buffer<T>,
+        buf_layout: RectLayout,
+        data: &[T],
+        data_layout: RectLayout,
+        region: [usize; 3],
+    ) -> Result<(), Error> {
+        buf_layout.validate(region, buf.len())?;
+        data_layout.validate(region, data.len())?;
+        self.enqueue_with_deps(&[(buf.dependencies(), Access::Write)], |wait_list| unsafe {
+            enqueue_write_buffer_rect(
+                self.queue(),
+                buf.as_ptr(),
+                buf_layout,
+                data,
+                data_layout,
+                region,
+                true,
+                Some(wait_list),
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Enqueues a copy of the `region` (width, height, depth in elements) from `src` to `dst`.
+    /// Following commands on `dst` wait for the copy.
+    pub fn copy_rect<T>(
+        &self,
+        dst: &mut Buffer<T>,
+        dst_layout: RectLayout,
+        src: &Buffer<T>,
+        src_layout: RectLayout,
+        region: [usize; 3],
+    ) -> Result<(), Error> {
+        dst_layout.validate(region, dst.len())?;
+        src_layout.validate(region, src.len())?;
+        self.enqueue_with_deps(
+            &[
+                (dst.dependencies(), Access::Write),
+                (src.dependencies(), Access::Read),
+            ],
+            |wait_list| unsafe {
+                enqueue_copy_buffer_rect::<T>(
+                    self.queue(),
+                    src.as_ptr(),
+                    src_layout,
+                    dst.as_ptr(),
+                    dst_layout,
+                    region,
+                    Some(wait_list),
+                )
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Fills the elements of `range` with `pattern`, e.g. `device.fill(&mut buf, 0f32, ..)`.
+    /// The size of `T` must be a power of two and at most 128 bytes.
+    /// The fill is ordered after the previous commands on the buffer, following commands on it wait for the fill.
+    pub fn fill<T: Pod>(
+        &self,
+        buf: &mut Buffer<T>,
+        pattern: T,
+        range: impl RangeBounds<usize>,
+    ) -> Result<(), Error> {
+        let size = std::mem::size_of::<T>();
+        if !size.is_power_of_two() || size > 128 {
+            return Err(OCLErrorKind::InvalidPatternSize.into());
+        }
+        let range = buf.range(range)?;
+        if range.is_empty() {
+            return Ok(());
         }
+
+        self.enqueue_with_deps(&[(buf.dependencies(), Access::Write)], |wait_list| unsafe {
+            enqueue_fill_buffer(
+                self.queue(),
+                buf.as_ptr(),
+                &pattern,
+                range.start,
+                range.len(),
+                Some(wait_list),
+            )
+        })?;
+        Ok(())
     }
 
+    /// Blocks until all enqueued commands are finished.
     #[inline]
     pub fn wait_for_events(&self) -> Result<(), Error> {
-        unsafe {
-            wait_for_events(&self.event_wait_list.borrow())?;
+        finish(&self.queue)
+    }
+
+    /// Locks the kernel cache of the device.
+    #[inline]
+    pub fn kernel_cache(&self) -> MutexGuard<'_, KernelCache> {
+        lock(&self.kernel_cache)
+    }
+
+    /// Recreates the command queue with profiling enabled.
+    /// Afterwards, the events of all enqueued commands provide device timestamps via [`Event::profiling`].
+    pub fn enable_profiling(&mut self) -> Result<(), Error> {
+        if self.profiling {
+            return Ok(());
+        }
+        self.recreate_queue(
+            self.queue_properties() | CommandQueueProperties::ProfilingEnable as u64,
+        )?;
+        self.profiling = true;
+        Ok(())
+    }
+
+    /// Recreates the command queue with out-of-order execution enabled.
+    /// Afterwards, commands are only ordered by the [`Dependencies`] of their memory objects.
+    /// Commands without tracked dependencies, e.g. [`CLDevice::enqueue_nd_range_kernel`] or launches whose arguments
+    /// were not set with [`crate::Launch::args`], are enclosed by barriers, see [`CLDevice::untracked`].
+    ///
+    /// Returns `OCLErrorKind::InvalidQueueProperties` if the device does not support out-of-order queues.
+    pub fn enable_out_of_order(&mut self) -> Result<(), Error> {
+        if self.out_of_order {
+            return Ok(());
         }
-        self.event_wait_list.borrow_mut().clear();
+        let out_of_order = CommandQueueProperties::OutOfOrderExecModeEnable as u64;
+        if self.device.get_queue_properties()? & out_of_order == 0 {
+            return Err(OCLErrorKind::InvalidQueueProperties.into());
+        }
+        self.recreate_queue(self.queue_properties() | out_of_order)?;
+        self.out_of_order = true;
         Ok(())
     }
 
+    /// `CommandQueueProperties` bitfield of the command queue.
+    pub fn queue_properties(&self) -> u64 {
+        let mut properties = 0;
+        if self.profiling {
+            properties |= CommandQueueProperties::ProfilingEnable as u64;
+        }
+        if self.out_of_order {
+            properties |= CommandQueueProperties::OutOfOrderExecModeEnable as u64;
+        }
+        properties
+    }
+
+    fn recreate_queue(&mut self, properties: u64) -> Result<(), Error> {
+        finish(&self.queue)?;
+        self.queue = create_command_queue_with_properties(&self.ctx, self.device, properties)?;
+        Ok(())
+    }
+
+    /// Creates a stream, a device with a new command queue in the same context and with the same properties.
+    /// Memory objects, programs and the kernel cache are shared with this device.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let upload = device.create_stream()?;
+    /// upload.write(&mut input, &data)?;
+    /// // waits for the upload, as it accesses `input`
+    /// device.launch(&kernel).global(n).args((&input, &mut output)).enqueue()?;
+    /// ```
+    pub fn create_stream(&self) -> Result<CLDevice, Error> {
+        let queue =
+            create_command_queue_with_properties(&self.ctx, self.device, self.queue_properties())?;
+        Ok(CLDevice {
+            device: self.device,
+            ctx: self.ctx.clone(),
+            queue,
+            unified_mem: self.unified_mem,
+            profiling: self.profiling,
+            out_of_order: self.out_of_order,
+            trace: Mutex::new(None),
+            kernel_cache: self.kernel_cache.clone(),
+        })
+    }
+
+    /// Starts recording every command enqueued through this device. Enables profiling if necessary.
+    /// A running trace is discarded.
+    pub fn start_trace(&mut self) -> Result<(), Error> {
+        self.enable_profiling()?;
+        *lock(&self.trace) = Some(TraceRecorder::new());
+        Ok(())
+    }
+
+    /// Stops recording and returns the captured commands, or `None` if no trace was running.
+    pub fn stop_trace(&self) -> Option<TraceRecorder> {
+        lock(&self.trace).take()
+    }
+
+    /// Stops recording and writes the captured commands as Chrome Trace Event JSON to `path`.
+    pub fn write_trace(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        self.stop_trace()
+            .ok_or(OCLErrorKind::TraceNotStarted)?
+            .write(path)
+    }
+
+    fn record(
+        &self,
+        name: String,
+        category: &'static str,
+        args: Vec<(&'static str, String)>,
+        event: &Event,
+    ) {
+        if let Some(trace) = lock(&self.trace).as_mut() {
+            trace.record(name, category, self.queue.0 as usize, args, event.clone());
+        }
+    }
+
+    #[inline]
+    fn is_tracing(&self) -> bool {
+        lock(&self.trace).is_some()
+    }
+
+    fn record_transfer(&self, name: &str, bytes: usize, event: &Event) {
+        if self.is_tracing() {
+            self.record(
+                name.into(),
+                "transfer",
+                vec![("bytes", bytes.to_string())],
+                event,
+            );
+        }
+    }
+
     /// Context of the OpenCL device.
     #[inline]
     pub fn ctx(&self) -> &Context {
@@ -271,6 +811,11 @@ impl CLDevice {
         self.device().get_version()
     }
 
+    /// Queries a snapshot of the capabilities of the device.
+    pub fn capabilities(&self) -> Result<DeviceCapabilities, Error> {
+        DeviceCapabilities::query(self.device)
+    }
+
     /// Checks whether the device supports unified memory.
     #[inline]
     pub fn unified_mem(&self) -> bool {
@@ -280,13 +825,27 @@ impl CLDevice {
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        future::{Future, IntoFuture},
+        pin::pin,
+        sync::Arc,
+        task::{Context, Poll, Wake, Waker},
+        thread,
+        time::Duration,
+    };
+
     use crate::{
-        api::{create_buffer, MemFlags},
-        CLDevice,
+        api::{
+            create_buffer,
+            mock::{self, MockCommand},
+            EventStatus, MemFlags, OCLErrorKind, RectLayout,
+        },
+        Buffer, CLDevice, Error, KernelCache,
     };
 
     #[test]
     fn test_get_fastest() {
+        crate::api::mock::install().unwrap();
         let device = CLDevice::fastest().unwrap();
         unsafe {
             create_buffer::<f32>(&device.ctx, MemFlags::MemReadWrite as u64, 10000, None).unwrap()
@@ -302,4 +861,380 @@ mod tests {
             device.device.get_global_mem().unwrap() as f32 * 10f32.powf(-9.)
         )
     }
+
+    #[test]
+    fn test_fill() -> Result<(), Error> {
+        crate::api::mock::install()?;
+        let device = CLDevice::new(0)?;
+        let mut buf = Buffer::from_slice(&device, &[1u32; 8])?;
+
+        device.fill(&mut buf, 0, ..)?;
+        device.fill(&mut buf, 5, 2..4)?;
+        device.fill(&mut buf, 9, 6..=7)?;
+        assert_eq!(device.read(&buf)?, [0, 0, 5, 5, 0, 0, 9, 9]);
+
+        let mut pairs = Buffer::<[f32; 2]>::new(&device, 3)?;
+        device.fill(&mut pairs, [1., 2.], ..)?;
+        assert_eq!(device.read(&pairs)?, [[1., 2.]; 3]);
+
+        let err = device.fill(&mut buf, 1, 4..9).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<OCLErrorKind>(),
+            Some(&OCLErrorKind::InvalidBufferRange)
+        );
+
+        let mut triples = Buffer::<[u8; 3]>::new(&device, 2)?;
+        let err = device.fill(&mut triples, [1, 2, 3], ..).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<OCLErrorKind>(),
+            Some(&OCLErrorKind::InvalidPatternSize)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rect() -> Result<(), Error> {
+        crate::api::mock::install()?;
+        let device = CLDevice::new(0)?;
+        let matrix = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+        let mut buf = Buffer::from_slice(&device, &matrix)?;
+        let (mat4, mat2) = (RectLayout::matrix(4), RectLayout::matrix(2));
+
+        let mut tile = [0i32; 4];
+        device.read_rect(&buf, mat4.at(1, 2), &mut tile, mat2, [2, 2, 1])?;
+        assert_eq!(tile, [9, 10, 13, 14]);
+
+        device.write_rect(&mut buf, mat4.at(2, 0), &[-1, -2, -3, -4], mat2, [2, 2, 1])?;
+        let mut copy = Buffer::from_slice(&device, &[0i32; 6])?;
+        let mat3 = RectLayout::matrix(3);
+        device.copy_rect(&mut copy, mat3.at(1, 0), &buf, mat4.at(2, 0), [2, 2, 1])?;
+        assert_eq!(device.read(&copy)?, [0, -1, -2, 0, -3, -4]);
+
+        // two 2x2 slices of a volume with padded rows
+        let volume = Buffer::from_slice(&device, &[1, 2, 0, 3, 4, 0, 5, 6, 0, 7, 8, 0])?;
+        let mut out = [0; 8];
+        let padded = RectLayout::new([0; 3], 3, 6);
+        device.read_rect(&volume, padded, &mut out, RectLayout::default(), [2, 2, 2])?;
+        assert_eq!(out, [1, 2, 3, 4, 5, 6, 7, 8]);
+
+        let err = device
+            .read_rect(&buf, mat4.at(0, 3), &mut tile, mat2, [2, 2, 1])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<OCLErrorKind>(),
+            Some(&OCLErrorKind::InvalidBufferRange)
+        );
+        let err = device
+            .read_rect(&buf, mat4.at(3, 0), &mut tile, mat2, [2, 2, 1])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<OCLErrorKind>(),
+            Some(&OCLErrorKind::InvalidRect)
+        );
+
+        // pitches whose products overflow must not wrap into a valid range
+        for layout in [
+            RectLayout::new([0, 1, 0], 1 << (usize::BITS - 1), 0),
+            RectLayout::new([0, 1, 0], 2, usize::MAX),
+            RectLayout::new([usize::MAX, 0, 0], 4, 0),
+        ] {
+            let err = device
+                .read_rect(&buf, layout, &mut tile, mat2, [1, 2, 1])
+                .unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<OCLErrorKind>(),
+                Some(&OCLErrorKind::InvalidRect)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_offset_transfers() -> Result<(), Error> {
+        crate::api::mock::install()?;
+        let device = CLDevice::new(0)?;
+        let mut buf = Buffer::from_slice(&device, &[0u16; 8])?;
+
+        device.write_at(&mut buf, 3, &[1, 2, 3])?;
+        assert_eq!(device.read(&buf)?, [0, 0, 0, 1, 2, 3, 0, 0]);
+        assert_eq!(device.read_range(&buf, 2..5)?, [0, 1, 2]);
+
+        let mut out = [0; 2];
+        device.read_at(&buf, 4, &mut out)?;
+        assert_eq!(out, [2, 3]);
+
+        let data = [7, 8];
+        unsafe { device.write_at_async(&mut buf, 6, &data)? }.wait()?;
+        {
+            let _transfer = unsafe { device.read_at_async(&buf, 5, &mut out)? };
+        }
+        assert_eq!(out, [3, 7]);
+
+        for err in [
+            device.write_at(&mut buf, 7, &[1, 2]).unwrap_err(),
+            device.read_range(&buf, 6..9).unwrap_err(),
+            device.read_at(&buf, usize::MAX, &mut out).unwrap_err(),
+        ] {
+            assert_eq!(
+                err.downcast_ref::<OCLErrorKind>(),
+                Some(&OCLErrorKind::InvalidBufferRange)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_platform() -> Result<(), Error> {
+        crate::api::mock::install()?;
+        let device = CLDevice::from_platform("MIN-CL mock", 0)?;
+        let platform = device.device.get_platform()?;
+        assert_eq!(platform.name()?, "min-cl mock platform");
+        assert_eq!(platform.vendor()?, "min-cl");
+        assert_eq!(platform.profile()?, "FULL_PROFILE");
+        assert_eq!(platform.extensions()?, ["cl_khr_icd"]);
+        assert_eq!(platform.host_timer_resolution()?, 1);
+
+        let err = CLDevice::from_platform("pocl", 0).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<OCLErrorKind>(),
+            Some(&OCLErrorKind::PlatformNotFound)
+        );
+        let err = CLDevice::from_platform("min-cl", 1).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<OCLErrorKind>(),
+            Some(&OCLErrorKind::InvalidDeviceIdx)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_profiling() -> Result<(), Error> {
+        crate::api::mock::install()?;
+        let mut device = CLDevice::new(0)?;
+        let buf = Buffer::<f32>::new(&device, 16)?;
+
+        let event = unsafe { device.enqueue_write_buffer(buf.as_ptr(), &[1f32; 16], false)? };
+        let err = event.profiling().unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<OCLErrorKind>(),
+            Some(&OCLErrorKind::ProfilingInfoNotAvailable)
+        );
+
+        device.enable_profiling()?;
+        assert!(device.profiling);
+
+        let event = unsafe { device.enqueue_write_buffer(buf.as_ptr(), &[1f32; 16], false)? };
+        let profiling = event.profiling()?;
+        assert!(profiling.queued <= profiling.submit);
+        assert!(profiling.submit <= profiling.start);
+        assert!(profiling.start < profiling.end);
+        assert!(profiling.duration() > Duration::ZERO);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shared_device() -> Result<(), Error> {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<CLDevice>();
+
+        mock::install()?;
+        mock::register_kernel("shared_add", |launch| {
+            let rhs = launch.scalar::<u32>(1);
+            let values = launch.read::<u32>(0);
+            launch.write(
+                0,
+                &values.iter().map(|value| value + rhs).collect::<Vec<_>>(),
+            );
+        });
+        let device = Arc::new(CLDevice::new(0)?);
+        let src = "__kernel void shared_add(__global uint* values, uint rhs) {}";
+
+        let workers = (0..4u32)
+            .map(|worker| {
+                let device = device.clone();
+                thread::spawn(move || -> Result<Vec<u32>, Error> {
+                    // kernels must not be shared between threads
+                    let kernel = KernelCache::default()
+                        .create_kernels(&device, src)?
+                        .remove("shared_add")
+                        .unwrap();
+                    let mut buf = Buffer::from_slice(&device, &[worker; 64])?;
+                    for _ in 0..10 {
+                        device
+                            .launch(&kernel)
+                            .global(64)
+                            .args((&mut buf, 1u32))
+                            .enqueue()?;
+                    }
+                    device.read(&buf)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for (worker, handle) in workers.into_iter().enumerate() {
+            assert_eq!(handle.join().unwrap()?, [worker as u32 + 10; 64]);
+        }
+        device.wait_for_events()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_streams() -> Result<(), Error> {
+        mock::install()?;
+        mock::register_kernel("stream_double", |launch| {
+            let values = launch.read::<f32>(0);
+            launch.write(
+                0,
+                &values.iter().map(|value| value * 2.).collect::<Vec<_>>(),
+            );
+        });
+        let mut device = CLDevice::new(0)?;
+        device.enable_out_of_order()?;
+        let upload = device.create_stream()?;
+        let download = device.create_stream()?;
+        assert!(upload.out_of_order && download.out_of_order);
+        assert_ne!(upload.queue().0, device.queue().0);
+
+        let src = "__kernel void stream_double(__global float* values) {}";
+        let raw = device
+            .kernel_cache()
+            .kernel(&device, src, "stream_double")?
+            .0;
+        // the kernel cache is shared
+        assert_eq!(
+            upload
+                .kernel_cache()
+                .kernel(&upload, src, "stream_double")?
+                .0,
+            raw
+        );
+
+        let mut buf = Buffer::<f32>::new(&device, 4)?;
+        let other = Buffer::from_slice(&device, &[1f32; 4])?;
+        upload.write(&mut buf, &[1., 2., 3., 4.])?;
+        let mut kernel_cache = device.kernel_cache();
+        let kernel = kernel_cache.kernel(&device, src, "stream_double")?;
+        device
+            .launch(kernel)
+            .global(4)
+            .args((&mut buf,))
+            .enqueue()?;
+        drop(kernel_cache);
+        assert_eq!(download.read(&other)?, [1.; 4]);
+        assert_eq!(download.read(&buf)?, [2., 4., 6., 8.]);
+
+        let write = mock::records(upload.queue().0).pop().unwrap();
+        let launch = mock::records(device.queue().0).pop().unwrap();
+        let reads = mock::records(download.queue().0);
+        // commands on other queues are waited for by their events
+        assert_eq!(launch.wait_list, [write.event]);
+        assert_eq!(reads[0].wait_list, []);
+        assert_eq!(reads[1].wait_list, [launch.event]);
+        // and their queues are flushed
+        assert_eq!(mock::flush_count(upload.queue().0), 1);
+        assert_eq!(mock::flush_count(device.queue().0), 1);
+        assert_eq!(mock::flush_count(download.queue().0), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_out_of_order_untracked() -> Result<(), Error> {
+        mock::install()?;
+        mock::register_kernel("untracked_scale", |_| {});
+        let mut device = CLDevice::new(0)?;
+        device.enable_out_of_order()?;
+        let src = "__kernel void untracked_scale(__global float* x) {}";
+        let kernel = KernelCache::default()
+            .create_kernels(&device, src)?
+            .remove("untracked_scale")
+            .unwrap();
+        let buf = Buffer::<f32>::new(&device, 4)?;
+        kernel.set_args((&buf,))?;
+
+        let commands = || {
+            mock::records(device.queue().0)
+                .into_iter()
+                .map(|record| match record.command {
+                    MockCommand::Barrier => "barrier",
+                    MockCommand::NDRangeKernel { .. } => "kernel",
+                    _ => "other",
+                })
+                .collect::<Vec<_>>()
+        };
+        // kernels whose arguments are not tracked are enclosed by barriers
+        device.enqueue_nd_range_kernel(&kernel, 1, &[4, 1, 1], None, None)?;
+        device.launch(&kernel).global(4).enqueue()?;
+        assert_eq!(
+            commands(),
+            ["barrier", "kernel", "barrier", "barrier", "kernel", "barrier"]
+        );
+
+        device.launch(&kernel).global(4).args((&buf,)).enqueue()?;
+        assert_eq!(commands().last(), Some(&"kernel"));
+        Ok(())
+    }
+
+    /// Polls `future` to completion on the current thread, parking while it is pending.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        struct ThreadWaker(thread::Thread);
+
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let mut future = pin!(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_async() -> Result<(), Error> {
+        fn assert_send<T: Send>(value: T) -> T {
+            value
+        }
+
+        mock::install()?;
+        let device = CLDevice::new(0)?;
+        // the callbacks are only called if the futures flush the queue
+        mock::defer_completion(device.queue().0);
+        let mut buf = Buffer::<i32>::new(&device, 4)?;
+
+        block_on(assert_send(device.write_async(&mut buf, vec![1, 2, 3, 4])))?;
+        assert_eq!(
+            block_on(assert_send(device.read_async(&buf)))?,
+            [1, 2, 3, 4]
+        );
+        assert!(block_on(device.write_async(&mut buf, vec![1])).is_err());
+
+        let mut out = [0; 2];
+        let transfer = unsafe { device.read_at_async(&buf, 2, &mut out)? };
+        assert_eq!(transfer.event().status()?, EventStatus::Queued);
+        let mut future = pin!(transfer.event().clone().into_future());
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(matches!(future.as_mut().poll(&mut cx), Poll::Ready(Ok(()))));
+        assert_eq!(transfer.event().status()?, EventStatus::Complete);
+        block_on(transfer)?;
+        assert_eq!(out, [3, 4]);
+
+        mock::fail_next("clSetEventCallback", OCLErrorKind::InvalidEvent);
+        let transfer = unsafe { device.read_at_async(&buf, 0, &mut out)? };
+        let mut future = transfer.event().clone().into_future();
+        let err = block_on(&mut future).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<OCLErrorKind>(),
+            Some(&OCLErrorKind::InvalidEvent)
+        );
+        assert!(!future.is_terminated());
+        transfer.wait()?;
+        assert_eq!(out, [1, 2]);
+        Ok(())
+    }
 }
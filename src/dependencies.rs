@@ -0,0 +1,224 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::{
+    api::{Event, EventStatus},
+    cl_device::lock,
+    CLDevice, Error,
+};
+
+/// How a command accesses a memory object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// The command only reads, hence it can overlap with other reads.
+    Read,
+    /// The command writes (and possibly reads), hence it is ordered after all previous accesses.
+    Write,
+}
+
+/// The commands that access a memory object, which determine the wait list of the next command.
+///
+/// A command that reads waits for the last write, a command that writes waits for the last write and all reads after it.
+/// Clones share the same state, e.g. a buffer, its clones and its sub-buffers.
+#[derive(Debug, Clone, Default)]
+pub struct Dependencies(Arc<Mutex<Accesses>>);
+
+#[derive(Debug, Default)]
+struct Accesses {
+    /// The last command that wrote to the memory object
+    last_write: Option<Event>,
+    /// Commands that read from the memory object after `last_write`
+    reads: Vec<Event>,
+}
+
+impl Accesses {
+    fn wait_list(&self, access: Access, wait_list: &mut Vec<Event>) {
+        wait_list.extend(self.last_write.iter().cloned());
+        if access == Access::Write {
+            wait_list.extend(self.reads.iter().cloned());
+        }
+    }
+
+    fn record(&mut self, access: Access, event: &Event) {
+        match access {
+            Access::Read => {
+                // completed reads cannot delay a write
+                self.reads
+                    .retain(|read| !matches!(read.status(), Ok(EventStatus::Complete)));
+                self.reads.push(event.clone());
+            }
+            Access::Write => {
+                self.last_write = Some(event.clone());
+                self.reads.clear();
+            }
+        }
+    }
+}
+
+impl Dependencies {
+    /// The events a command with the given access must wait for.
+    pub fn wait_list(&self, access: Access) -> Vec<Event> {
+        let mut wait_list = Vec::new();
+        lock(&self.0).wait_list(access, &mut wait_list);
+        wait_list
+    }
+
+    /// Records a command that accesses the memory object. Following commands are ordered after it according to `access`.
+    pub fn record(&self, access: Access, event: &Event) {
+        lock(&self.0).record(access, event)
+    }
+}
+
+impl CLDevice {
+    /// Enqueues a command that accesses the memory objects of `deps`.
+    /// `enqueue` receives the exact wait list of the command, the returned event is recorded in `deps`.
+    ///
+    /// The dependencies are locked while the command is enqueued, hence concurrent commands on the same memory objects are ordered.
+    /// A memory object that is accessed several times counts as written if any access writes.
+    /// The queues of other streams whose events are waited for are flushed, otherwise the command could wait forever.
+    pub fn enqueue_with_deps(
+        &self,
+        deps: &[(&Dependencies, Access)],
+        enqueue: impl FnOnce(&[Event]) -> Result<Event, Error>,
+    ) -> Result<Event, Error> {
+        let mut deps = deps
+            .iter()
+            .map(|&(deps, access)| (Arc::as_ptr(&deps.0), deps, access))
+            .collect::<Vec<_>>();
+        // locking in address order prevents deadlocks between commands that access the same objects
+        deps.sort_by_key(|&(ptr, _, access)| (ptr, access == Access::Read));
+        deps.dedup_by_key(|&mut (ptr, _, _)| ptr);
+
+        let mut guards = deps
+            .iter()
+            .map(|&(_, deps, access)| (lock(&deps.0), access))
+            .collect::<Vec<(MutexGuard<Accesses>, Access)>>();
+
+        let mut wait_list = Vec::new();
+        for (accesses, access) in &guards {
+            accesses.wait_list(*access, &mut wait_list);
+        }
+        self.flush_foreign_queues(&wait_list)?;
+        let event = enqueue(&wait_list)?;
+
+        for (accesses, access) in &mut guards {
+            accesses.record(*access, &event);
+        }
+        Ok(event)
+    }
+
+    /// Flushes the queues of events that were enqueued by other streams,
+    /// as a queue may only wait for commands of another queue after they are submitted.
+    fn flush_foreign_queues(&self, wait_list: &[Event]) -> Result<(), Error> {
+        let mut flushed = vec![self.queue().0];
+        for event in wait_list {
+            let queue = event.command_queue()?;
+            if !flushed.contains(&queue) {
+                event.flush_queue()?;
+                flushed.push(queue);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        api::mock::{self, MockCommand},
+        Access, Buffer, CLDevice, Error, KernelCache, ReadOnly,
+    };
+
+    #[test]
+    fn test_dependencies() -> Result<(), Error> {
+        mock::install()?;
+        mock::register_kernel("deps_scale", |_| {});
+        let device = CLDevice::new(0)?;
+        let src = "__kernel void deps_scale(__global float* x) {}";
+        let kernel = KernelCache::default()
+            .create_kernels(&device, src)?
+            .remove("deps_scale")
+            .unwrap();
+
+        let mut a = Buffer::from_slice(&device, &[1f32; 4])?;
+        let mut b = Buffer::from_slice(&device, &[2f32; 4])?;
+        let mut c = Buffer::<f32>::new(&device, 4)?;
+        assert!(a.dependencies().wait_list(Access::Write).is_empty());
+
+        device.fill(&mut a, 3., ..)?;
+        device.launch(&kernel).global(4).args((&mut b,)).enqueue()?;
+        device.copy_from(&mut c, &a)?;
+        let mut out = [0f32; 4];
+        let read = unsafe { device.read_at_async(&a, 0, &mut out)? };
+        read.wait()?;
+        device.write(&mut a, &[4.; 4])?;
+        device.copy_from(&mut b, &c)?;
+
+        let records = mock::records(device.queue().0);
+        let records = &records[records.len() - 6..];
+        let event = |idx: usize| records[idx].event;
+        assert!(matches!(
+            records[1].command,
+            MockCommand::NDRangeKernel { .. }
+        ));
+
+        // independent commands do not wait for each other
+        assert_eq!(records[0].wait_list, []);
+        assert_eq!(records[1].wait_list, []);
+        // reads wait for the last write
+        assert_eq!(records[2].wait_list, [event(0)]);
+        assert_eq!(records[3].wait_list, [event(0)]);
+        // writes wait for the last write and the reads after it,
+        // the copy is pruned as mock commands complete when they are enqueued
+        assert_eq!(records[4].wait_list, [event(0), event(3)]);
+        let mut wait_list = records[5].wait_list.clone();
+        wait_list.sort();
+        let mut expected = vec![event(1), event(2)];
+        expected.sort();
+        assert_eq!(wait_list, expected);
+
+        // clones share the dependencies
+        assert_eq!(a.dependencies().wait_list(Access::Read)[0].0, event(4));
+        assert_eq!(
+            b.clone().dependencies().wait_list(Access::Read)[0].0,
+            event(5)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_only_args() -> Result<(), Error> {
+        mock::install()?;
+        mock::register_kernel("deps_copy", |_| {});
+        let device = CLDevice::new(0)?;
+        // keeps the reads from being pruned
+        mock::defer_completion(device.queue().0);
+        let src = "__kernel void deps_copy(__global const float* x, __global float* y) {}";
+        let kernel = KernelCache::default()
+            .create_kernels(&device, src)?
+            .remove("deps_copy")
+            .unwrap();
+
+        let mut input = Buffer::<f32>::new(&device, 4)?;
+        let (mut out_a, mut out_b) = (Buffer::<f32>::new(&device, 4)?, Buffer::new(&device, 4)?);
+        device.write(&mut input, &[1.; 4])?;
+        for out in [&mut out_a, &mut out_b] {
+            device
+                .launch(&kernel)
+                .global(4)
+                .args((ReadOnly(&input), out))
+                .enqueue()?;
+        }
+        device.write(&mut input, &[2.; 4])?;
+
+        let records = mock::records(device.queue().0);
+        let records = &records[records.len() - 4..];
+        let event = |idx: usize| records[idx].event;
+        // kernels that only read the input overlap
+        assert_eq!(records[1].wait_list, [event(0)]);
+        assert_eq!(records[2].wait_list, [event(0)]);
+        assert_eq!(records[3].wait_list, [event(0), event(1), event(2)]);
+        // the outputs are written
+        assert_eq!(out_a.dependencies().wait_list(Access::Read)[0].0, event(1));
+        Ok(())
+    }
+}
@@ -0,0 +1,283 @@
+use crate::{
+    all_devices,
+    api::{CLIntDevice, OCLErrorKind},
+    CLDevice, Error,
+};
+
+/// Environment variable that, if set, overrides the selection of every [`DeviceSelector`].
+///
+/// `<platform_idx>:<device_idx>` picks a device by its indices in [`all_devices`].
+/// Any other value picks the first device whose name or vendor, or whose platform's name or vendor, contains the value (ignoring case).
+pub const DEVICE_ENV_VAR: &str = "MIN_CL_DEVICE";
+
+/// Selects an OpenCL device by its properties instead of its position in the benchmarked `DEVICES` list.
+///
+/// Candidates are visited in platform order, GPUs and accelerators before CPUs.
+/// # Example
+/// ```no_run
+/// use min_cl::{api::DeviceType, DeviceSelector};
+///
+/// let device = DeviceSelector::new()
+///     .device_type(DeviceType::GPU as u64)
+///     .min_global_mem(4 * 1024 * 1024 * 1024)
+///     .extension("cl_khr_fp64")
+///     .select()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DeviceSelector {
+    device_type: Option<u64>,
+    name: Option<String>,
+    min_global_mem: Option<u64>,
+    extensions: Vec<String>,
+    min_version: Option<(u32, u32)>,
+    prefer_unified_mem: bool,
+}
+
+impl DeviceSelector {
+    pub fn new() -> DeviceSelector {
+        DeviceSelector::default()
+    }
+
+    /// Only devices matching the `DeviceType` bitfield.
+    pub fn device_type(mut self, device_type: u64) -> Self {
+        self.device_type = Some(device_type);
+        self
+    }
+
+    /// Only devices whose name or vendor, or whose platform's name or vendor, contains `name` (ignoring case).
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_lowercase());
+        self
+    }
+
+    /// Only devices with at least `bytes` of global memory.
+    pub fn min_global_mem(mut self, bytes: u64) -> Self {
+        self.min_global_mem = Some(bytes);
+        self
+    }
+
+    /// Only devices supporting `extension`. Can be called multiple times.
+    pub fn extension(mut self, extension: &str) -> Self {
+        self.extensions.push(extension.into());
+        self
+    }
+
+    /// Only devices supporting at least OpenCL `major.minor`.
+    pub fn min_version(mut self, major: u32, minor: u32) -> Self {
+        self.min_version = Some((major, minor));
+        self
+    }
+
+    /// Prefers devices with unified memory over the other matching devices.
+    pub fn prefer_unified_mem(mut self, prefer: bool) -> Self {
+        self.prefer_unified_mem = prefer;
+        self
+    }
+
+    /// Returns all devices that match the selector, in order of preference.
+    /// A device whose properties cannot be queried does not match.
+    pub fn candidates(&self) -> Result<Vec<CLIntDevice>, Error> {
+        let mut candidates = Vec::new();
+        for device in all_devices()?.into_iter().flatten() {
+            // a single broken device must not prevent selecting one of the others
+            if self.matches(device).unwrap_or(false) {
+                candidates.push(device);
+            }
+        }
+
+        if self.prefer_unified_mem {
+            let mut unified = Vec::with_capacity(candidates.len());
+            for device in candidates {
+                // a device whose memory cannot be queried counts as not unified
+                unified.push((!device.unified_mem().unwrap_or(false), device));
+            }
+            // stable, hence the platform order is kept otherwise
+            unified.sort_by_key(|(not_unified, _)| *not_unified);
+            candidates = unified.into_iter().map(|(_, device)| device).collect();
+        }
+        Ok(candidates)
+    }
+
+    /// Creates the best matching device. The selection is overridden by [`DEVICE_ENV_VAR`], if set.
+    pub fn select(&self) -> Result<CLDevice, Error> {
+        let device_override = std::env::var(DEVICE_ENV_VAR).ok();
+        self.select_device(device_override.as_deref())?.try_into()
+    }
+
+    fn select_device(&self, device_override: Option<&str>) -> Result<CLIntDevice, Error> {
+        match device_override {
+            Some(device_override) => select_override(device_override),
+            None => self
+                .candidates()?
+                .first()
+                .copied()
+                .ok_or_else(|| OCLErrorKind::NoMatchingDevice.into()),
+        }
+    }
+
+    fn matches(&self, device: CLIntDevice) -> Result<bool, Error> {
+        if let Some(device_type) = self.device_type {
+            if device.get_device_type()? & device_type == 0 {
+                return Ok(false);
+            }
+        }
+        if let Some(name) = &self.name {
+            if !matches_name(device, name)? {
+                return Ok(false);
+            }
+        }
+        if let Some(min_global_mem) = self.min_global_mem {
+            if device.get_global_mem()? < min_global_mem {
+                return Ok(false);
+            }
+        }
+        if !self.extensions.is_empty() {
+            let extensions = device.get_extensions()?;
+            if !self.extensions.iter().all(|ext| extensions.contains(ext)) {
+                return Ok(false);
+            }
+        }
+        if let Some(min_version) = self.min_version {
+            match parse_version(&device.get_version()?) {
+                Some(version) if version >= min_version => (),
+                _ => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+}
+
+fn select_override(device_override: &str) -> Result<CLIntDevice, Error> {
+    let devices = all_devices()?;
+
+    if let Some((platform_idx, device_idx)) = device_override.split_once(':') {
+        if let (Ok(platform_idx), Ok(device_idx)) = (
+            platform_idx.trim().parse::<usize>(),
+            device_idx.trim().parse::<usize>(),
+        ) {
+            return devices
+                .get(platform_idx)
+                .and_then(|devices| devices.get(device_idx))
+                .copied()
+                .ok_or_else(|| OCLErrorKind::InvalidDeviceIdx.into());
+        }
+    }
+
+    let name = device_override.to_lowercase();
+    for device in devices.into_iter().flatten() {
+        // like in `DeviceSelector::candidates`, a device that cannot be queried does not match
+        if matches_name(device, &name).unwrap_or(false) {
+            return Ok(device);
+        }
+    }
+    Err(OCLErrorKind::NoMatchingDevice.into())
+}
+
+/// `name` must be lowercase.
+fn matches_name(device: CLIntDevice, name: &str) -> Result<bool, Error> {
+    Ok(device.get_name()?.to_lowercase().contains(name)
+        || device.get_vendor()?.to_lowercase().contains(name)
+        || device.get_platform()?.matches(name)?)
+}
+
+/// Parses the version of `"OpenCL <major>.<minor> <vendor-specific>"`.
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let (major, minor) = version
+        .strip_prefix("OpenCL ")?
+        .split_whitespace()
+        .next()?
+        .split_once('.')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_version, DeviceSelector};
+    use crate::{
+        api::{mock, DeviceType, OCLErrorKind},
+        Error,
+    };
+
+    fn assert_kind(err: Error, kind: OCLErrorKind) {
+        assert_eq!(err.downcast_ref::<OCLErrorKind>(), Some(&kind));
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("OpenCL 1.2 CUDA 12.0.0"), Some((1, 2)));
+        assert_eq!(parse_version("OpenCL 3.0 "), Some((3, 0)));
+        assert_eq!(parse_version("1.2"), None);
+    }
+
+    #[test]
+    fn test_device_selector() -> Result<(), Error> {
+        mock::install()?;
+
+        let selector = DeviceSelector::new()
+            .device_type(DeviceType::GPU as u64)
+            .name("MOCK")
+            .min_global_mem(512 * 1024 * 1024)
+            .extension("cl_khr_fp64")
+            .min_version(1, 2)
+            .prefer_unified_mem(true);
+        assert_eq!(selector.candidates()?.len(), 1);
+        assert_eq!(selector.select()?.name()?, "min-cl mock device");
+
+        let no_match = [
+            DeviceSelector::new().device_type(DeviceType::CPU as u64),
+            DeviceSelector::new().name("pocl"),
+            DeviceSelector::new().min_global_mem(2 * 1024 * 1024 * 1024),
+            DeviceSelector::new().extension("cl_khr_fp16"),
+            DeviceSelector::new().min_version(2, 0),
+        ];
+        for selector in no_match {
+            assert!(selector.candidates()?.is_empty());
+            assert_kind(
+                selector.select_device(None).unwrap_err(),
+                OCLErrorKind::NoMatchingDevice,
+            );
+        }
+
+        // a failing device query skips the device instead of failing the selection
+        let selector = DeviceSelector::new().device_type(DeviceType::GPU as u64);
+        mock::fail_next("clGetDeviceInfo", OCLErrorKind::OutOfResources);
+        assert!(selector.candidates()?.is_empty());
+        assert_eq!(selector.candidates()?.len(), 1);
+
+        // and counts as not unified
+        let selector = DeviceSelector::new().prefer_unified_mem(true);
+        mock::fail_next("clGetDeviceInfo", OCLErrorKind::OutOfResources);
+        assert_eq!(selector.candidates()?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_device_override() -> Result<(), Error> {
+        mock::install()?;
+
+        // the override ignores the selector
+        let selector = DeviceSelector::new().name("pocl");
+        let device = selector.select_device(Some("0:0"))?;
+        assert_eq!(device.get_name()?, "min-cl mock device");
+        let device = selector.select_device(Some("Mock Platform"))?;
+        assert_eq!(device.get_name()?, "min-cl mock device");
+
+        assert_kind(
+            selector.select_device(Some("0:1")).unwrap_err(),
+            OCLErrorKind::InvalidDeviceIdx,
+        );
+        assert_kind(
+            selector.select_device(Some("nvidia")).unwrap_err(),
+            OCLErrorKind::NoMatchingDevice,
+        );
+
+        // a failing device query skips the device instead of failing the selection
+        mock::fail_next("clGetDeviceInfo", OCLErrorKind::OutOfResources);
+        assert_kind(
+            selector.select_device(Some("Mock Platform")).unwrap_err(),
+            OCLErrorKind::NoMatchingDevice,
+        );
+        Ok(())
+    }
+}
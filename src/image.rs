@@ -0,0 +1,788 @@
+use std::{ffi::c_void, fmt::Debug, marker::PhantomData, ops::Deref};
+
+use crate::{
+    api::{
+        cl_mem, create_image, create_sampler, enqueue_copy_buffer_to_image, enqueue_copy_image,
+        enqueue_copy_image_to_buffer, enqueue_read_image, enqueue_write_image,
+        get_supported_image_formats, release_mem_object, retain_mem_object, AddressingMode,
+        FilterMode, ImageFormat, ImageType, MemFlags, OCLErrorKind, Sampler,
+    },
+    buffer::check_host_ptr_flags,
+    Access, Buffer, CLDevice, Dependencies, Error, Pod,
+};
+
+/// An OpenCL image whose pixels are of type `T`, e.g. `[u8; 4]` for `RGBA`/`UnormInt8` or `f32` for `R`/`Float`.
+///
+/// Images are created as [`Image2D`], [`Image3D`] or [`Image2DArray`], which dereference to this type.
+/// The image is released on drop. Cloning an image retains the memory object.
+pub struct Image<T> {
+    ptr: cl_mem,
+    format: ImageFormat,
+    image_type: ImageType,
+    size: [usize; 3],
+    /// Shared with clones
+    deps: Dependencies,
+    _p: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for Image<T> {}
+unsafe impl<T: Sync> Sync for Image<T> {}
+
+impl<T> Image<T> {
+    /// Allocates an image with the given `MemFlags`.
+    /// `size` is the width, height and depth (3D) or array size (2D array) in pixels. The last value is `1` for 2D images.
+    /// If `data` is `Some`, `flags` must contain `MemCopyHostPtr` and `data` must hold all pixels, row by row.
+    ///
+    /// `MemUseHostPtr` is rejected with `OCLErrorKind::InvalidHostPtr`, as the image would outlive the borrow of `data`.
+    pub fn with_flags(
+        device: &CLDevice,
+        flags: u64,
+        format: ImageFormat,
+        image_type: ImageType,
+        size: [usize; 3],
+        data: Option<&[T]>,
+    ) -> Result<Image<T>, Error> {
+        check_host_ptr_flags(flags, data.is_some())?;
+        if std::mem::size_of::<T>() != format.pixel_size() {
+            return Err(OCLErrorKind::ImageFormatMismatch.into());
+        }
+
+        let cl_device = device.device();
+        let max_size = match image_type {
+            ImageType::Image2D => {
+                let [width, height] = cl_device.get_image2d_max_size()?;
+                [width, height, 1]
+            }
+            ImageType::Image3D => cl_device.get_image3d_max_size()?,
+            ImageType::Image2DArray => {
+                let [width, height] = cl_device.get_image2d_max_size()?;
+                [width, height, cl_device.get_image_max_array_size()?]
+            }
+        };
+        if size
+            .iter()
+            .zip(max_size)
+            .any(|(&len, max)| len == 0 || len > max)
+        {
+            return Err(OCLErrorKind::InvalidImageSize.into());
+        }
+
+        if !device
+            .supported_image_formats(flags, image_type)?
+            .contains(&format)
+        {
+            return Err(OCLErrorKind::ImageFormatNotSupported.into());
+        }
+        if let Some(data) = data {
+            if data.len() != size.iter().product::<usize>() {
+                return Err(OCLErrorKind::BufferLengthMismatch.into());
+            }
+        }
+
+        let ptr = unsafe { create_image(device.ctx(), flags, format, image_type, size, data)? };
+        Ok(Image {
+            ptr,
+            format,
+            image_type,
+            size,
+            deps: Dependencies::default(),
+            _p: PhantomData,
+        })
+    }
+
+    #[inline]
+    pub fn format(&self) -> ImageFormat {
+        self.format
+    }
+
+    #[inline]
+    pub fn image_type(&self) -> ImageType {
+        self.image_type
+    }
+
+    /// Width, height and depth or array size in pixels.
+    #[inline]
+    pub fn size(&self) -> [usize; 3] {
+        self.size
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.size[0]
+    }
+
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.size[1]
+    }
+
+    /// Total number of pixels of the image.
+    #[inline]
+    pub fn pixels(&self) -> usize {
+        self.size.iter().product()
+    }
+
+    /// The underlying `cl_mem` object. It stays valid as long as the image is alive.
+    #[inline]
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.ptr
+    }
+
+    /// The commands that access the image, see [`CLDevice::enqueue_with_deps`].
+    #[inline]
+    pub fn dependencies(&self) -> &Dependencies {
+        &self.deps
+    }
+
+    /// Returns `OCLErrorKind::InvalidImageRegion` if the `region` at `origin` is empty or out of bounds.
+    fn validate_region(&self, origin: [usize; 3], region: [usize; 3]) -> Result<(), Error> {
+        for axis in 0..3 {
+            match origin[axis].checked_add(region[axis]) {
+                Some(end) if region[axis] > 0 && end <= self.size[axis] => (),
+                _ => return Err(OCLErrorKind::InvalidImageRegion.into()),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T> AsRef<Image<T>> for Image<T> {
+    fn as_ref(&self) -> &Image<T> {
+        self
+    }
+}
+
+impl<T> Clone for Image<T> {
+    /// Retains the memory object, the clone refers to the same device memory.
+    ///
+    /// # Panics
+    /// If the OpenCL implementation fails to retain the memory object, e.g. when it runs out of host memory.
+    fn clone(&self) -> Self {
+        unsafe { retain_mem_object(self.ptr).expect("could not retain the image") };
+        Image {
+            ptr: self.ptr,
+            format: self.format,
+            image_type: self.image_type,
+            size: self.size,
+            deps: self.deps.clone(),
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<T> Drop for Image<T> {
+    fn drop(&mut self) {
+        // a failed release leaks the memory object, which is preferable to a panic in drop
+        let _ = unsafe { release_mem_object(self.ptr) };
+    }
+}
+
+impl<T> Debug for Image<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Image")
+            .field("ptr", &self.ptr)
+            .field("format", &self.format)
+            .field("image_type", &self.image_type)
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+// no `DerefMut`, as swapping e.g. a 3D image into an `Image2D` would break its dimensionality,
+// writes take `&mut impl AsRef<Image<T>>` instead
+macro_rules! image_types {
+    ($($(#[$attr:meta])* $name:ident),*) => {
+        $(
+            $(#[$attr])*
+            pub struct $name<T>(Image<T>);
+
+            impl<T> Deref for $name<T> {
+                type Target = Image<T>;
+
+                fn deref(&self) -> &Self::Target {
+                    &self.0
+                }
+            }
+
+            impl<T> AsRef<Image<T>> for $name<T> {
+                fn as_ref(&self) -> &Image<T> {
+                    &self.0
+                }
+            }
+
+            impl<T> Clone for $name<T> {
+                fn clone(&self) -> Self {
+                    $name(self.0.clone())
+                }
+            }
+
+            impl<T> Debug for $name<T> {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    self.0.fmt(f)
+                }
+            }
+        )*
+    };
+}
+
+image_types!(
+    /// A 2D image. Read in kernels as `image2d_t`.
+    Image2D,
+    /// A 3D image. Read in kernels as `image3d_t`.
+    Image3D,
+    /// An array of 2D images of the same size. Read in kernels as `image2d_array_t`.
+    Image2DArray
+);
+
+impl<T> Image2D<T> {
+    /// Allocates an uninitialized image.
+    pub fn new(
+        device: &CLDevice,
+        format: ImageFormat,
+        width: usize,
+        height: usize,
+    ) -> Result<Image2D<T>, Error> {
+        let size = [width, height, 1];
+        Image::with_flags(
+            device,
+            MemFlags::MemReadWrite as u64,
+            format,
+            ImageType::Image2D,
+            size,
+            None,
+        )
+        .map(Image2D)
+    }
+
+    /// Allocates an image that is initialized with the pixels of `data`, row by row.
+    pub fn from_slice(
+        device: &CLDevice,
+        format: ImageFormat,
+        width: usize,
+        height: usize,
+        data: &[T],
+    ) -> Result<Image2D<T>, Error>
+    where
+        T: Pod,
+    {
+        let size = [width, height, 1];
+        Image::with_flags(
+            device,
+            MemFlags::MemReadWrite | MemFlags::MemCopyHostPtr,
+            format,
+            ImageType::Image2D,
+            size,
+            Some(data),
+        )
+        .map(Image2D)
+    }
+}
+
+impl<T> Image3D<T> {
+    /// Allocates an uninitialized image.
+    pub fn new(
+        device: &CLDevice,
+        format: ImageFormat,
+        width: usize,
+        height: usize,
+        depth: usize,
+    ) -> Result<Image3D<T>, Error> {
+        let size = [width, height, depth];
+        Image::with_flags(
+            device,
+            MemFlags::MemReadWrite as u64,
+            format,
+            ImageType::Image3D,
+            size,
+            None,
+        )
+        .map(Image3D)
+    }
+
+    /// Allocates an image that is initialized with the pixels of `data`, slice by slice.
+    pub fn from_slice(
+        device: &CLDevice,
+        format: ImageFormat,
+        width: usize,
+        height: usize,
+        depth: usize,
+        data: &[T],
+    ) -> Result<Image3D<T>, Error>
+    where
+        T: Pod,
+    {
+        let size = [width, height, depth];
+        Image::with_flags(
+            device,
+            MemFlags::MemReadWrite | MemFlags::MemCopyHostPtr,
+            format,
+            ImageType::Image3D,
+            size,
+            Some(data),
+        )
+        .map(Image3D)
+    }
+
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.size[2]
+    }
+}
+
+impl<T> Image2DArray<T> {
+    /// Allocates `array_size` uninitialized images.
+    pub fn new(
+        device: &CLDevice,
+        format: ImageFormat,
+        width: usize,
+        height: usize,
+        array_size: usize,
+    ) -> Result<Image2DArray<T>, Error> {
+        let size = [width, height, array_size];
+        Image::with_flags(
+            device,
+            MemFlags::MemReadWrite as u64,
+            format,
+            ImageType::Image2DArray,
+            size,
+            None,
+        )
+        .map(Image2DArray)
+    }
+
+    /// Allocates images that are initialized with the pixels of `data`, image by image.
+    pub fn from_slice(
+        device: &CLDevice,
+        format: ImageFormat,
+        width: usize,
+        height: usize,
+        array_size: usize,
+        data: &[T],
+    ) -> Result<Image2DArray<T>, Error>
+    where
+        T: Pod,
+    {
+        let size = [width, height, array_size];
+        Image::with_flags(
+            device,
+            MemFlags::MemReadWrite | MemFlags::MemCopyHostPtr,
+            format,
+            ImageType::Image2DArray,
+            size,
+            Some(data),
+        )
+        .map(Image2DArray)
+    }
+
+    #[inline]
+    pub fn array_size(&self) -> usize {
+        self.size[2]
+    }
+}
+
+impl Sampler {
+    /// With `normalized_coords`, kernels address the image with coordinates in `[0, 1]`, otherwise in pixels.
+    pub fn new(
+        device: &CLDevice,
+        normalized_coords: bool,
+        addressing_mode: AddressingMode,
+        filter_mode: FilterMode,
+    ) -> Result<Sampler, Error> {
+        create_sampler(
+            device.ctx(),
+            normalized_coords,
+            addressing_mode,
+            filter_mode,
+        )
+    }
+}
+
+impl CLDevice {
+    /// Returns the image formats of the given image type that support the `MemFlags`.
+    pub fn supported_image_formats(
+        &self,
+        flags: u64,
+        image_type: ImageType,
+    ) -> Result<Vec<ImageFormat>, Error> {
+        get_supported_image_formats(self.ctx(), flags, image_type)
+    }
+
+    /// Reads all pixels of the image into a new `Vec`. Blocks until the read is finished.
+    pub fn read_image<T: Pod>(&self, image: &Image<T>) -> Result<Vec<T>, Error> {
+        // Pod guarantees that zeroed memory is a valid value
+        let mut data = vec![unsafe { std::mem::zeroed::<T>() }; image.pixels()];
+        self.read_image_region(image, [0; 3], image.size(), &mut data)?;
+        Ok(data)
+    }
+
+    /// Reads the `region` (width, height, depth in pixels) at `origin` into the tightly packed `data`.
+    /// Blocks until the read is finished.
+    pub fn read_image_region<T: Pod>(
+        &self,
+        image: &Image<T>,
+        origin: [usize; 3],
+        region: [usize; 3],
+        data: &mut [T],
+    ) -> Result<(), Error> {
+        image.validate_region(origin, region)?;
+        if data.len() != region.iter().product::<usize>() {
+            return Err(OCLErrorKind::BufferLengthMismatch.into());
+        }
+        self.enqueue_with_deps(
+            &[(image.dependencies(), Access::Read)],
+            |wait_list| unsafe {
+                enqueue_read_image(
+                    self.queue(),
+                    image.as_ptr(),
+                    origin,
+                    region,
+                    data,
+                    true,
+                    Some(wait_list),
+                )
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Writes `data` to all pixels of the image. Blocks until the write is finished.
+    pub fn write_image<T: Pod>(
+        &self,
+        image: &mut impl AsRef<Image<T>>,
+        data: &[T],
+    ) -> Result<(), Error> {
+        let size = (*image).as_ref().size();
+        self.write_image_region(image, [0; 3], size, data)
+    }
+
+    /// Writes the tightly packed `data` to the `region` (width, height, depth in pixels) at `origin`.
+    /// Blocks until the write is finished.
+    pub fn write_image_region<T: Pod>(
+        &self,
+        image: &mut impl AsRef<Image<T>>,
+        origin: [usize; 3],
+        region: [usize; 3],
+        data: &[T],
+    ) -> Result<(), Error> {
+        let image = (*image).as_ref();
+        image.validate_region(origin, region)?;
+        if data.len() != region.iter().product::<usize>() {
+            return Err(OCLErrorKind::BufferLengthMismatch.into());
+        }
+        self.enqueue_with_deps(
+            &[(image.dependencies(), Access::Write)],
+            |wait_list| unsafe {
+                enqueue_write_image(
+                    self.queue(),
+                    image.as_ptr(),
+                    origin,
+                    region,
+                    data,
+                    true,
+                    Some(wait_list),
+                )
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Enqueues a copy of the `region` (width, height, depth in pixels) between images of the same format.
+    /// Following commands on `dst` wait for the copy.
+    pub fn copy_image<T>(
+        &self,
+        dst: &mut impl AsRef<Image<T>>,
+        dst_origin: [usize; 3],
+        src: &Image<T>,
+        src_origin: [usize; 3],
+        region: [usize; 3],
+    ) -> Result<(), Error> {
+        let dst = (*dst).as_ref();
+        if dst.format() != src.format() {
+            return Err(OCLErrorKind::ImageFormatMismatch.into());
+        }
+        dst.validate_region(dst_origin, region)?;
+        src.validate_region(src_origin, region)?;
+        self.enqueue_with_deps(
+            &[
+                (dst.dependencies(), Access::Write),
+                (src.dependencies(), Access::Read),
+            ],
+            |wait_list| unsafe {
+                enqueue_copy_image(
+                    self.queue(),
+                    src.as_ptr(),
+                    src_origin,
+                    dst.as_ptr(),
+                    dst_origin,
+                    region,
+                    Some(wait_list),
+                )
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Enqueues a copy of the `region` (width, height, depth in pixels) of the image to the buffer, starting at element `dst_offset`.
+    /// The pixels are tightly packed in the buffer. Following commands on `dst` wait for the copy.
+    pub fn copy_image_to_buffer<T>(
+        &self,
+        dst: &mut Buffer<T>,
+        dst_offset: usize,
+        src: &Image<T>,
+        src_origin: [usize; 3],
+        region: [usize; 3],
+    ) -> Result<(), Error> {
+        src.validate_region(src_origin, region)?;
+        dst.range(dst_offset..dst_offset.saturating_add(region.iter().product()))?;
+        self.enqueue_with_deps(
+            &[
+                (dst.dependencies(), Access::Write),
+                (src.dependencies(), Access::Read),
+            ],
+            |wait_list| unsafe {
+                enqueue_copy_image_to_buffer::<T>(
+                    self.queue(),
+                    src.as_ptr(),
+                    src_origin,
+                    region,
+                    dst.as_ptr(),
+                    dst_offset,
+                    Some(wait_list),
+                )
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Enqueues a copy of tightly packed pixels, starting at element `src_offset` of the buffer, to the `region` (width, height, depth in pixels) of the image.
+    /// Following commands on `dst` wait for the copy.
+    pub fn copy_buffer_to_image<T>(
+        &self,
+        dst: &mut impl AsRef<Image<T>>,
+        dst_origin: [usize; 3],
+        region: [usize; 3],
+        src: &Buffer<T>,
+        src_offset: usize,
+    ) -> Result<(), Error> {
+        let dst = (*dst).as_ref();
+        dst.validate_region(dst_origin, region)?;
+        src.range(src_offset..src_offset.saturating_add(region.iter().product()))?;
+        self.enqueue_with_deps(
+            &[
+                (dst.dependencies(), Access::Write),
+                (src.dependencies(), Access::Read),
+            ],
+            |wait_list| unsafe {
+                enqueue_copy_buffer_to_image::<T>(
+                    self.queue(),
+                    src.as_ptr(),
+                    src_offset,
+                    dst.as_ptr(),
+                    dst_origin,
+                    region,
+                    Some(wait_list),
+                )
+            },
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Image, Image2D, Image2DArray, Image3D};
+    use crate::{
+        api::{
+            mock::{self, MockCommand},
+            AddressingMode, ChannelOrder, ChannelType, FilterMode, ImageFormat, ImageType,
+            MemFlags, OCLErrorKind, Sampler,
+        },
+        Access, Buffer, CLDevice, Error,
+    };
+
+    #[track_caller]
+    fn assert_kind<T>(result: Result<T, Error>, kind: OCLErrorKind) {
+        let err = result.err().expect("expected an error");
+        assert_eq!(err.downcast_ref::<OCLErrorKind>(), Some(&kind));
+    }
+
+    #[test]
+    fn test_image_format() {
+        let rgba8 = ImageFormat::new(ChannelOrder::RGBA, ChannelType::UnormInt8);
+        assert_eq!(rgba8.pixel_size(), 4);
+        assert_eq!(ImageFormat::from_raw(rgba8.as_raw()), Some(rgba8));
+
+        let rgb565 = ImageFormat::new(ChannelOrder::RGB, ChannelType::UnormShort565);
+        assert_eq!(rgb565.pixel_size(), 2);
+        let rg_half = ImageFormat::new(ChannelOrder::RG, ChannelType::HalfFloat);
+        assert_eq!(rg_half.pixel_size(), 4);
+
+        let mut unknown = rgba8.as_raw();
+        unknown.image_channel_order = 0x10C0;
+        assert_eq!(ImageFormat::from_raw(unknown), None);
+    }
+
+    #[test]
+    fn test_image_2d() -> Result<(), Error> {
+        mock::install()?;
+        let device = CLDevice::new(0)?;
+        let rgba8 = ImageFormat::new(ChannelOrder::RGBA, ChannelType::UnormInt8);
+
+        let formats =
+            device.supported_image_formats(MemFlags::MemReadWrite as u64, ImageType::Image2D)?;
+        assert!(formats.contains(&rgba8));
+
+        let pixels = (0..8u8).map(|x| [x; 4]).collect::<Vec<_>>();
+        let mut image = Image2D::from_slice(&device, rgba8, 4, 2, &pixels)?;
+        assert_eq!((image.width(), image.height(), image.pixels()), (4, 2, 8));
+        assert_eq!(device.read_image(&image)?, pixels);
+
+        device.write_image_region(&mut image, [1, 1, 0], [2, 1, 1], &[[9; 4]; 2])?;
+        let mut row = [[0; 4]; 4];
+        device.read_image_region(&image, [0, 1, 0], [4, 1, 1], &mut row)?;
+        assert_eq!(row, [[4; 4], [9; 4], [9; 4], [7; 4]]);
+
+        let records = mock::records(device.queue().0);
+        assert_eq!(
+            records[records.len() - 2].command,
+            MockCommand::WriteImage {
+                image: image.as_ptr(),
+                origin: [1, 1, 0],
+                region: [2, 1, 1],
+            }
+        );
+
+        assert_kind(
+            device.read_image_region(&image, [3, 0, 0], [2, 1, 1], &mut [[0; 4]; 2]),
+            OCLErrorKind::InvalidImageRegion,
+        );
+        assert_kind(
+            device.write_image(&mut image, &pixels[..4]),
+            OCLErrorKind::BufferLengthMismatch,
+        );
+
+        // a failed release does not panic on drop
+        mock::fail_next("clReleaseMemObject", OCLErrorKind::InvalidMemObject);
+        drop(image);
+        Ok(())
+    }
+
+    #[test]
+    fn test_image_creation_errors() -> Result<(), Error> {
+        mock::install()?;
+        let device = CLDevice::new(0)?;
+        let rgba8 = ImageFormat::new(ChannelOrder::RGBA, ChannelType::UnormInt8);
+
+        // the pixel type does not match the format
+        assert_kind(
+            Image2D::<f32>::new(
+                &device,
+                ImageFormat::new(ChannelOrder::RGBA, ChannelType::Float),
+                4,
+                4,
+            ),
+            OCLErrorKind::ImageFormatMismatch,
+        );
+        assert_kind(
+            Image2D::<[f32; 2]>::new(
+                &device,
+                ImageFormat::new(ChannelOrder::RG, ChannelType::Float),
+                4,
+                4,
+            ),
+            OCLErrorKind::ImageFormatNotSupported,
+        );
+        assert_kind(
+            Image2D::<[u8; 4]>::new(&device, rgba8, 0, 4),
+            OCLErrorKind::InvalidImageSize,
+        );
+        assert_kind(
+            Image3D::<[u8; 4]>::new(&device, rgba8, 4, 4, 4096),
+            OCLErrorKind::InvalidImageSize,
+        );
+        assert_kind(
+            Image2D::from_slice(&device, rgba8, 2, 2, &[[0u8; 4]; 3]),
+            OCLErrorKind::BufferLengthMismatch,
+        );
+        // the image would outlive the borrowed pixels
+        assert_kind(
+            Image::with_flags(
+                &device,
+                MemFlags::MemReadWrite | MemFlags::MemUseHostPtr,
+                rgba8,
+                ImageType::Image2D,
+                [2, 2, 1],
+                Some(&[[0u8; 4]; 4]),
+            ),
+            OCLErrorKind::InvalidHostPtr,
+        );
+        assert_eq!(mock::mem_object_count(device.ctx().0), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_image_copies() -> Result<(), Error> {
+        mock::install()?;
+        let device = CLDevice::new(0)?;
+        let r32f = ImageFormat::new(ChannelOrder::R, ChannelType::Float);
+
+        let voxels = (0..2 * 2 * 3).map(|x| x as f32).collect::<Vec<_>>();
+        let volume = Image3D::from_slice(&device, r32f, 2, 2, 3, &voxels)?;
+        assert_eq!(volume.depth(), 3);
+
+        // the last slice of the volume
+        let mut buf = Buffer::<f32>::new(&device, 4)?;
+        device.copy_image_to_buffer(&mut buf, 0, &volume, [0, 0, 2], [2, 2, 1])?;
+        assert_eq!(device.read(&buf)?, [8., 9., 10., 11.]);
+
+        let mut layers = Image2DArray::<f32>::new(&device, r32f, 2, 2, 2)?;
+        assert_eq!(layers.array_size(), 2);
+        device.copy_buffer_to_image(&mut layers, [0, 0, 1], [2, 2, 1], &buf, 0)?;
+        device.copy_image(&mut layers, [0, 0, 0], &volume, [0, 0, 0], [2, 2, 1])?;
+
+        // following commands on the image wait for the last copy
+        let last = mock::records(device.queue().0).pop().unwrap();
+        assert!(matches!(last.command, MockCommand::CopyImage { .. }));
+        assert_eq!(
+            layers.dependencies().wait_list(Access::Read)[0].0,
+            last.event
+        );
+        assert_eq!(
+            device.read_image(&layers)?,
+            [0., 1., 2., 3., 8., 9., 10., 11.]
+        );
+
+        assert_kind(
+            device.copy_image_to_buffer(&mut buf, 1, &volume, [0; 3], [2, 2, 1]),
+            OCLErrorKind::InvalidBufferRange,
+        );
+        let mut rgba = Image2D::<[u8; 4]>::new(
+            &device,
+            ImageFormat::new(ChannelOrder::RGBA, ChannelType::UnormInt8),
+            2,
+            2,
+        )?;
+        let bgra = Image2D::<[u8; 4]>::new(
+            &device,
+            ImageFormat::new(ChannelOrder::BGRA, ChannelType::UnormInt8),
+            2,
+            2,
+        )?;
+        assert_kind(
+            device.copy_image(&mut rgba, [0; 3], &bgra, [0; 3], [2, 2, 1]),
+            OCLErrorKind::ImageFormatMismatch,
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sampler() -> Result<(), Error> {
+        mock::install()?;
+        let device = CLDevice::new(0)?;
+
+        let sampler = Sampler::new(&device, true, AddressingMode::Repeat, FilterMode::Linear)?;
+        let clone = sampler.clone();
+        assert_eq!(sampler.as_ptr(), clone.as_ptr());
+        Ok(())
+    }
+}
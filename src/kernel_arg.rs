@@ -0,0 +1,438 @@
+use std::{ffi::c_void, marker::PhantomData, mem::size_of};
+
+use crate::{
+    api::{cl_mem, set_kernel_arg, Kernel, KernelArgError, OCLErrorKind, Sampler},
+    Access, Buffer, Dependencies, Error, Image, Image2D, Image2DArray, Image3D, SvmBuffer,
+};
+
+/// A value that can be passed as kernel argument.
+///
+/// Implemented for scalars, vectors (`[f32; 4]` for `float4`, 3-component vectors are padded to 4 components),
+/// [`Buffer`], [`Image`], [`Sampler`], [`SvmBuffer`] and [`LocalMem`].
+pub trait KernelArg {
+    /// Sets the `index`-th argument of `kernel` to this value.
+    fn set_arg(&self, kernel: &Kernel, index: usize) -> Result<(), Error>;
+
+    /// The dependencies of the memory object and how the kernel accesses it, `None` for values.
+    /// Memory objects passed by shared reference or wrapped in [`ReadOnly`] count as read, all others as written.
+    #[inline]
+    fn dependencies(&self) -> Option<(&Dependencies, Access)> {
+        None
+    }
+}
+
+// a shared reference cannot be written, e.g. the buffer may be mapped for reading meanwhile
+impl<A: KernelArg + ?Sized> KernelArg for &A {
+    #[inline]
+    fn set_arg(&self, kernel: &Kernel, index: usize) -> Result<(), Error> {
+        (**self).set_arg(kernel, index)
+    }
+
+    #[inline]
+    fn dependencies(&self) -> Option<(&Dependencies, Access)> {
+        (**self)
+            .dependencies()
+            .map(|(deps, _)| (deps, Access::Read))
+    }
+}
+
+impl<A: KernelArg + ?Sized> KernelArg for &mut A {
+    #[inline]
+    fn set_arg(&self, kernel: &Kernel, index: usize) -> Result<(), Error> {
+        (**self).set_arg(kernel, index)
+    }
+
+    #[inline]
+    fn dependencies(&self) -> Option<(&Dependencies, Access)> {
+        (**self).dependencies()
+    }
+}
+
+/// Passes `value` by value, which must have the size of the kernel parameter.
+fn set_value<T>(kernel: &Kernel, index: usize, value: &T) -> Result<(), Error> {
+    unsafe {
+        set_kernel_arg(
+            kernel,
+            index,
+            value as *const T as *const c_void,
+            size_of::<T>(),
+            true,
+        )
+    }
+}
+
+macro_rules! impl_kernel_arg {
+    ($($t:ty),*) => {
+        $(
+            impl KernelArg for $t {
+                #[inline]
+                fn set_arg(&self, kernel: &Kernel, index: usize) -> Result<(), Error> {
+                    set_value(kernel, index, self)
+                }
+            }
+
+            impl KernelArg for [$t; 3] {
+                fn set_arg(&self, kernel: &Kernel, index: usize) -> Result<(), Error> {
+                    let [x, y, z] = *self;
+                    set_value(kernel, index, &[x, y, z, <$t>::default()])
+                }
+            }
+
+            impl_kernel_arg!(@vector $t; 2, 4, 8, 16);
+        )*
+    };
+    (@vector $t:ty; $($n:literal),*) => {
+        $(
+            impl KernelArg for [$t; $n] {
+                #[inline]
+                fn set_arg(&self, kernel: &Kernel, index: usize) -> Result<(), Error> {
+                    set_value(kernel, index, self)
+                }
+            }
+        )*
+    };
+}
+
+// `size_t` depends on the device, hence `usize` and `isize` are not implemented
+impl_kernel_arg!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+impl<T> KernelArg for Buffer<T> {
+    #[inline]
+    fn set_arg(&self, kernel: &Kernel, index: usize) -> Result<(), Error> {
+        unsafe { set_kernel_arg(kernel, index, self.as_ptr(), size_of::<cl_mem>(), false) }
+    }
+
+    #[inline]
+    fn dependencies(&self) -> Option<(&Dependencies, Access)> {
+        Some((Buffer::dependencies(self), Access::Write))
+    }
+}
+
+impl<T> KernelArg for Image<T> {
+    #[inline]
+    fn set_arg(&self, kernel: &Kernel, index: usize) -> Result<(), Error> {
+        unsafe { set_kernel_arg(kernel, index, self.as_ptr(), size_of::<cl_mem>(), false) }
+    }
+
+    #[inline]
+    fn dependencies(&self) -> Option<(&Dependencies, Access)> {
+        Some((Image::dependencies(self), Access::Write))
+    }
+}
+
+impl<T> KernelArg for Image2D<T> {
+    #[inline]
+    fn set_arg(&self, kernel: &Kernel, index: usize) -> Result<(), Error> {
+        (**self).set_arg(kernel, index)
+    }
+
+    #[inline]
+    fn dependencies(&self) -> Option<(&Dependencies, Access)> {
+        KernelArg::dependencies(&**self)
+    }
+}
+
+impl<T> KernelArg for Image3D<T> {
+    #[inline]
+    fn set_arg(&self, kernel: &Kernel, index: usize) -> Result<(), Error> {
+        (**self).set_arg(kernel, index)
+    }
+
+    #[inline]
+    fn dependencies(&self) -> Option<(&Dependencies, Access)> {
+        KernelArg::dependencies(&**self)
+    }
+}
+
+impl<T> KernelArg for Image2DArray<T> {
+    #[inline]
+    fn set_arg(&self, kernel: &Kernel, index: usize) -> Result<(), Error> {
+        (**self).set_arg(kernel, index)
+    }
+
+    #[inline]
+    fn dependencies(&self) -> Option<(&Dependencies, Access)> {
+        KernelArg::dependencies(&**self)
+    }
+}
+
+impl KernelArg for Sampler {
+    #[inline]
+    fn set_arg(&self, kernel: &Kernel, index: usize) -> Result<(), Error> {
+        set_value(kernel, index, &self.0)
+    }
+}
+
+impl<T> KernelArg for SvmBuffer<T> {
+    #[inline]
+    fn set_arg(&self, kernel: &Kernel, index: usize) -> Result<(), Error> {
+        self.set_kernel_arg(kernel, index)
+    }
+
+    #[inline]
+    fn dependencies(&self) -> Option<(&Dependencies, Access)> {
+        Some((SvmBuffer::dependencies(self), Access::Write))
+    }
+}
+
+/// A memory object argument that the kernel only reads, e.g. `ReadOnly(input)` for an owned buffer.
+/// Shared references, e.g. `&input`, are read-only already.
+/// Kernels and transfers that only read the memory object as well are not ordered after the launch.
+#[derive(Debug, Clone, Copy)]
+pub struct ReadOnly<A>(pub A);
+
+impl<A: KernelArg> KernelArg for ReadOnly<A> {
+    #[inline]
+    fn set_arg(&self, kernel: &Kernel, index: usize) -> Result<(), Error> {
+        self.0.set_arg(kernel, index)
+    }
+
+    #[inline]
+    fn dependencies(&self) -> Option<(&Dependencies, Access)> {
+        self.0.dependencies().map(|(deps, _)| (deps, Access::Read))
+    }
+}
+
+/// Local memory of `len` elements of type `T` for a `__local` kernel parameter. Created with [`LocalMem()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalMem<T> {
+    len: usize,
+    _p: PhantomData<T>,
+}
+
+/// Allocates `len` elements of type `T` of local memory for a `__local` kernel parameter.
+///
+/// # Example
+/// ```ignore
+/// kernel.set_args((&buf, LocalMem::<f32>(256)))?;
+/// ```
+#[allow(non_snake_case)]
+#[inline]
+pub fn LocalMem<T>(len: usize) -> LocalMem<T> {
+    LocalMem {
+        len,
+        _p: PhantomData,
+    }
+}
+
+impl<T> LocalMem<T> {
+    /// Number of elements
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T> KernelArg for LocalMem<T> {
+    #[inline]
+    fn set_arg(&self, kernel: &Kernel, index: usize) -> Result<(), Error> {
+        // local memory is declared with a null value
+        unsafe {
+            set_kernel_arg(
+                kernel,
+                index,
+                std::ptr::null(),
+                self.len * size_of::<T>(),
+                true,
+            )
+        }
+    }
+}
+
+/// A tuple of [`KernelArg`]s that sets all parameters of a kernel, see [`Kernel::set_args`].
+pub trait KernelArgs {
+    /// Number of arguments
+    const LEN: usize;
+
+    fn set_args(&self, kernel: &Kernel) -> Result<(), Error>;
+
+    /// Appends the dependencies of the memory object arguments and their access to `deps`.
+    fn dependencies<'a>(&'a self, deps: &mut Vec<(&'a Dependencies, Access)>);
+}
+
+macro_rules! impl_kernel_args {
+    ($($idx:tt $arg:ident),+) => {
+        impl<$($arg: KernelArg),+> KernelArgs for ($($arg,)+) {
+            const LEN: usize = [$($idx),+].len();
+
+            fn set_args(&self, kernel: &Kernel) -> Result<(), Error> {
+                $(kernel.set_arg($idx, &self.$idx)?;)+
+                Ok(())
+            }
+
+            fn dependencies<'a>(&'a self, deps: &mut Vec<(&'a Dependencies, Access)>) {
+                deps.extend([$(self.$idx.dependencies()),+].into_iter().flatten());
+            }
+        }
+    };
+}
+
+impl_kernel_args!(0 A);
+impl_kernel_args!(0 A, 1 B);
+impl_kernel_args!(0 A, 1 B, 2 C);
+impl_kernel_args!(0 A, 1 B, 2 C, 3 D);
+impl_kernel_args!(0 A, 1 B, 2 C, 3 D, 4 E);
+impl_kernel_args!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F);
+impl_kernel_args!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G);
+impl_kernel_args!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H);
+impl_kernel_args!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I);
+impl_kernel_args!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I, 9 J);
+impl_kernel_args!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I, 9 J, 10 K);
+impl_kernel_args!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I, 9 J, 10 K, 11 L);
+impl_kernel_args!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I, 9 J, 10 K, 11 L, 12 M);
+impl_kernel_args!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I, 9 J, 10 K, 11 L, 12 M, 13 N);
+impl_kernel_args!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I, 9 J, 10 K, 11 L, 12 M, 13 N, 14 O);
+impl_kernel_args!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I, 9 J, 10 K, 11 L, 12 M, 13 N, 14 O, 15 P);
+
+impl Kernel {
+    /// Sets the `index`-th argument. A rejected argument is reported as [`KernelArgError`].
+    pub fn set_arg(&self, index: usize, arg: &impl KernelArg) -> Result<(), Error> {
+        arg.set_arg(self, index)
+            .map_err(|err| match err.downcast_ref::<OCLErrorKind>() {
+                Some(&kind) => self.arg_error(kind, index),
+                None => err,
+            })
+    }
+
+    /// Sets all arguments of the kernel, e.g. `kernel.set_args((&lhs, &rhs, &mut out, 2f32))`.
+    ///
+    /// If the number of arguments does not match the number of kernel parameters,
+    /// a [`KernelArgError`] with `OCLErrorKind::InvalidArgIndex` (too many) or `OCLErrorKind::InvalidKernelArgs` (too few) is returned.
+    pub fn set_args<A: KernelArgs>(&self, args: A) -> Result<(), Error> {
+        let num_args = self.num_args()?;
+        if A::LEN > num_args {
+            return Err(self.arg_error(OCLErrorKind::InvalidArgIndex, num_args));
+        }
+        if A::LEN < num_args {
+            return Err(self.arg_error(OCLErrorKind::InvalidKernelArgs, A::LEN));
+        }
+        args.set_args(self)
+    }
+
+    fn arg_error(&self, kind: OCLErrorKind, index: usize) -> Error {
+        KernelArgError {
+            kind,
+            kernel: self.name().unwrap_or_default(),
+            index,
+        }
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        api::{
+            build_program, create_kernel, create_program_with_source, create_sampler, mock,
+            AddressingMode, FilterMode, Kernel, KernelArgError, OCLErrorKind,
+        },
+        Buffer, CLDevice, Error, LocalMem,
+    };
+
+    fn kernel(device: &CLDevice, src: &str, name: &str) -> Result<Kernel, Error> {
+        let program = unsafe { create_program_with_source(device.ctx(), src)? };
+        unsafe { build_program(&program, &[device.device()], None)? };
+        create_kernel(&program, name)
+    }
+
+    #[track_caller]
+    fn assert_arg_error(result: Result<(), Error>, kind: OCLErrorKind, index: usize) {
+        let err = result.expect_err("expected an error");
+        let err = err.downcast_ref::<KernelArgError>().unwrap();
+        assert_eq!((err.kind, err.index), (kind, index));
+    }
+
+    #[test]
+    fn test_set_args() -> Result<(), Error> {
+        mock::install()?;
+        let device = CLDevice::new(0)?;
+
+        mock::register_kernel("arg_axpy", |launch| {
+            let a = launch.scalar::<f32>(0);
+            let [x, y, z, w] = launch.scalar::<[f32; 4]>(3);
+            let values = launch.read::<f32>(1);
+            let out = values
+                .iter()
+                .map(|v| a * v + x + y + z + w)
+                .collect::<Vec<_>>();
+            launch.write(2, &out);
+        });
+        let src = "__kernel void arg_axpy(float a, __global const float* x, __global float* out, float3 offset, __local float* scratch, sampler_t s) {}";
+        let kernel = kernel(&device, src, "arg_axpy")?;
+
+        let x = Buffer::from_slice(&device, &[1f32, 2., 3.])?;
+        let mut out = Buffer::<f32>::new(&device, 3)?;
+        let sampler = create_sampler(
+            device.ctx(),
+            false,
+            AddressingMode::ClampToEdge,
+            FilterMode::Nearest,
+        )?;
+        kernel.set_args((
+            2f32,
+            &x,
+            &mut out,
+            [0.5f32, 0.25, 0.25],
+            LocalMem::<f32>(64),
+            &sampler,
+        ))?;
+        device.enqueue_nd_range_kernel(&kernel, 1, &[3, 0, 0], None, None)?;
+        assert_eq!(device.read(&out)?, [3., 5., 7.]);
+
+        assert_arg_error(kernel.set_arg(0, &2f64), OCLErrorKind::InvalidArgSize, 0);
+        assert_arg_error(
+            kernel.set_arg(3, &[1f32, 2.]),
+            OCLErrorKind::InvalidArgSize,
+            3,
+        );
+        assert_arg_error(kernel.set_arg(4, &1f32), OCLErrorKind::InvalidArgValue, 4);
+        assert_arg_error(kernel.set_arg(6, &1f32), OCLErrorKind::InvalidArgIndex, 6);
+        assert_arg_error(
+            kernel.set_args((1f32, &x)),
+            OCLErrorKind::InvalidKernelArgs,
+            2,
+        );
+
+        let err = kernel.set_args((1f32,)).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("Could not set argument 1 of kernel \"arg_axpy\""));
+        Ok(())
+    }
+
+    #[test]
+    fn test_work_group_info() -> Result<(), Error> {
+        mock::install()?;
+        let device = CLDevice::new(0)?;
+        let src = "__kernel void arg_reduce(__global const float* x, __local float* a, __local int* b) {}";
+        let kernel = kernel(&device, src, "arg_reduce")?;
+
+        let info = kernel.work_group_info(device.device())?;
+        assert_eq!(info.work_group_size, 256);
+        assert_eq!(info.preferred_work_group_size_multiple, 32);
+        assert_eq!(info.compile_work_group_size, [0; 3]);
+        assert_eq!((info.local_mem_size, info.private_mem_size), (0, 0));
+
+        let x = Buffer::from_slice(&device, &[1f32; 4])?;
+        kernel.set_args((&x, LocalMem::<f32>(64), LocalMem::<i32>(16)))?;
+        let info = kernel.work_group_info(device.device())?;
+        assert_eq!(info.local_mem_size, 320);
+
+        // short replies are rejected instead of panicking
+        for len in [0, 4] {
+            mock::truncate_info("clGetKernelWorkGroupInfo", Some(len));
+            let err = kernel.work_group_info(device.device()).unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<OCLErrorKind>(),
+                Some(&OCLErrorKind::InvalidValue)
+            );
+        }
+        mock::truncate_info("clGetKernelWorkGroupInfo", None);
+        Ok(())
+    }
+}
@@ -1,94 +1,358 @@
 use crate::{CLDevice, Error};
 
 use super::api::{
-    build_program, create_kernels_in_program, create_program_with_source, Kernel, OCLErrorKind,
+    build_program, create_kernel, create_kernels_in_program, create_program_with_binary,
+    create_program_with_source, get_program_binaries, source_hash, Kernel, Program,
 };
-use std::collections::HashMap;
+use std::{collections::HashMap, path::PathBuf};
+
+const BUILD_OPTIONS: &str = "-cl-std=CL1.2"; //-cl-single-precision-constant
 
 #[derive(Debug, Default)]
-/// This stores the previously compiled OpenCL kernels.
+/// This stores the previously compiled OpenCL programs and kernels.
 pub struct KernelCache {
-    /// Uses the kernel source code to retrieve the corresponding `Kernel`.
-    pub kernel_cache: HashMap<String, Kernel>,
+    /// Built programs by source code, then by build options.
+    pub programs: HashMap<String, HashMap<String, Program>>,
+    /// Kernels by source code, then by build options, then by kernel name.
+    pub kernels: HashMap<String, HashMap<String, HashMap<String, Kernel>>>,
+    /// Optional on-disk cache of program binaries that survives process restarts.
+    pub binary_cache: Option<BinaryCache>,
 }
 
 impl KernelCache {
-    /// Returns a cached kernel. If the kernel source code does not exist, a new kernel is created and cached.
-    pub fn kernel(&mut self, device: &CLDevice, src: &str) -> Result<&Kernel, Error> {
-        if self.kernel_cache.contains_key(src) {
-            return Ok(self.kernel_cache.get(src).unwrap());
+    /// Creates a kernel cache that additionally stores compiled programs in `binary_cache`.
+    pub fn with_binary_cache(binary_cache: BinaryCache) -> KernelCache {
+        KernelCache {
+            binary_cache: Some(binary_cache),
+            ..Default::default()
         }
-        /*if let Some(kernel) = self.kernel_cache.get(src) {
-            return Ok(kernel);
-        }*/
-
-        let program = unsafe { create_program_with_source(&device.ctx, src)? };
-        unsafe {
-            build_program(&program, &[device.device], Some("-cl-std=CL1.2"))?;
-        } //-cl-single-precision-constant
-
-        let kernel = unsafe {
-            create_kernels_in_program(&program)?
-                .into_iter()
-                .next()
-                .ok_or(OCLErrorKind::InvalidKernel)?
+    }
+
+    /// Returns the cached kernel called `name` of the program built from `src`.
+    /// If it does not exist, the program is built if necessary and the kernel is created and cached.
+    pub fn kernel(&mut self, device: &CLDevice, src: &str, name: &str) -> Result<&Kernel, Error> {
+        self.kernel_with_options(device, src, BUILD_OPTIONS, name)
+    }
+
+    /// Like [`KernelCache::kernel`], with the given build options instead of `-cl-std=CL1.2`.
+    pub fn kernel_with_options(
+        &mut self,
+        device: &CLDevice,
+        src: &str,
+        options: &str,
+        name: &str,
+    ) -> Result<&Kernel, Error> {
+        // nested maps can be queried by `&str`, hence a cache hit does not copy the source
+        let cached = self
+            .kernels
+            .get(src)
+            .and_then(|by_options| by_options.get(options))
+            .is_some_and(|by_name| by_name.contains_key(name));
+        if !cached {
+            let kernel = create_kernel(self.program(device, src, options)?, name)?;
+            self.kernels
+                .entry(src.to_string())
+                .or_default()
+                .entry(options.to_string())
+                .or_default()
+                .insert(name.to_string(), kernel);
+        }
+        Ok(&self.kernels[src][options][name])
+    }
+
+    /// Creates every kernel of the program built from `src`, keyed by kernel name.
+    /// The kernels are not cached, hence their arguments are independent of the cached kernels.
+    pub fn create_kernels(
+        &mut self,
+        device: &CLDevice,
+        src: &str,
+    ) -> Result<HashMap<String, Kernel>, Error> {
+        self.create_kernels_with_options(device, src, BUILD_OPTIONS)
+    }
+
+    /// Like [`KernelCache::create_kernels`], with the given build options instead of `-cl-std=CL1.2`.
+    pub fn create_kernels_with_options(
+        &mut self,
+        device: &CLDevice,
+        src: &str,
+        options: &str,
+    ) -> Result<HashMap<String, Kernel>, Error> {
+        let program = self.program(device, src, options)?;
+        unsafe { create_kernels_in_program(program)? }
+            .into_iter()
+            .map(|kernel| Ok((kernel.name()?, kernel)))
+            .collect()
+    }
+
+    /// Returns the cached program built from `src` with `options`. If it does not exist, the program is built and cached.
+    pub fn program(
+        &mut self,
+        device: &CLDevice,
+        src: &str,
+        options: &str,
+    ) -> Result<&Program, Error> {
+        let cached = self
+            .programs
+            .get(src)
+            .is_some_and(|by_options| by_options.contains_key(options));
+        if !cached {
+            let program = self.build(device, src, options)?;
+            self.programs
+                .entry(src.to_string())
+                .or_default()
+                .insert(options.to_string(), program);
+        }
+        Ok(&self.programs[src][options])
+    }
+
+    /// Builds the program from a cached binary if possible, otherwise from source.
+    fn build(&self, device: &CLDevice, src: &str, options: &str) -> Result<Program, Error> {
+        let Some(binary_cache) = &self.binary_cache else {
+            return build_from_source(device, src, options);
+        };
+
+        // without a key (e.g. a failing device query) the program can still be built from source
+        let Ok(key) = binary_cache.key(device, src, options) else {
+            return build_from_source(device, src, options);
         };
 
-        self.kernel_cache.insert(src.to_string(), kernel);
-        Ok(self.kernel_cache.get(src).unwrap())
+        if let Some(binary) = binary_cache.load(&key) {
+            // a rejected binary (e.g. after a driver update) falls back to a source build, which overwrites the entry
+            if let Ok(program) = build_from_binary(device, &binary, options) {
+                return Ok(program);
+            }
+        }
+
+        let program = build_from_source(device, src, options)?;
+
+        // a failure to write the cache must not fail the kernel creation
+        if let Some(binary) = get_program_binaries(&program)
+            .ok()
+            .and_then(|binaries| binaries.into_iter().next())
+        {
+            binary_cache.store(&key, &binary).ok();
+        }
+        Ok(program)
+    }
+}
+
+fn build_from_source(device: &CLDevice, src: &str, options: &str) -> Result<Program, Error> {
+    let program = unsafe { create_program_with_source(&device.ctx, src)? };
+    unsafe { build_program(&program, &[device.device], Some(options))? };
+    Ok(program)
+}
+
+fn build_from_binary(device: &CLDevice, binary: &[u8], options: &str) -> Result<Program, Error> {
+    let program = unsafe { create_program_with_binary(&device.ctx, device.device, binary)? };
+    unsafe { build_program(&program, &[device.device], Some(options))? };
+    Ok(program)
+}
+
+/// Stores program binaries in a directory.
+///
+/// A binary is keyed by the source, the build options, the device name, the driver version and the platform,
+/// so changing any of them results in a recompilation.
+#[derive(Debug, Clone)]
+pub struct BinaryCache {
+    dir: PathBuf,
+}
+
+impl BinaryCache {
+    /// Uses `dir` as cache directory. The directory is created if it does not exist.
+    pub fn new(dir: impl Into<PathBuf>) -> Result<BinaryCache, Error> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(BinaryCache { dir })
+    }
+
+    pub fn dir(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    /// Returns the cache key for a program built from `src` with `options` on `device`.
+    pub fn key(&self, device: &CLDevice, src: &str, options: &str) -> Result<BinaryKey, Error> {
+        let int_device = device.device();
+        let platform = int_device.get_platform()?.name()?;
+        let identity = [
+            options,
+            &int_device.get_name()?,
+            &int_device.get_driver_version()?,
+            &platform,
+            src,
+        ]
+        .join("\n");
+        Ok(BinaryKey {
+            hash: source_hash(&identity),
+            identity,
+        })
+    }
+
+    pub fn path(&self, key: &BinaryKey) -> PathBuf {
+        self.dir.join(format!("{:016x}.bin", key.hash))
     }
+
+    /// Returns the stored binary, if the entry was stored under exactly the same key.
+    /// An entry of another key with a colliding hash is ignored.
+    pub fn load(&self, key: &BinaryKey) -> Option<Vec<u8>> {
+        let entry = std::fs::read(self.path(key)).ok()?;
+        let (len, rest) = entry.split_first_chunk::<8>()?;
+        let len = usize::try_from(u64::from_le_bytes(*len)).ok()?;
+        if len > rest.len() {
+            return None;
+        }
+        let (identity, binary) = rest.split_at(len);
+        (identity == key.identity.as_bytes()).then(|| binary.to_vec())
+    }
+
+    pub fn store(&self, key: &BinaryKey, binary: &[u8]) -> Result<(), Error> {
+        // the identity precedes the binary, so that `load` can detect hash collisions
+        let mut entry = Vec::with_capacity(8 + key.identity.len() + binary.len());
+        entry.extend_from_slice(&(key.identity.len() as u64).to_le_bytes());
+        entry.extend_from_slice(key.identity.as_bytes());
+        entry.extend_from_slice(binary);
+
+        // write and rename, so that concurrent processes never read a partially written binary
+        let tmp = self
+            .dir
+            .join(format!("{:016x}.{}.tmp", key.hash, std::process::id()));
+        std::fs::write(&tmp, entry)?;
+        std::fs::rename(&tmp, self.path(key))?;
+        Ok(())
+    }
+}
+
+/// Identifies a cached binary by the source, the build options, the device name, the driver version and the platform.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryKey {
+    hash: u64,
+    identity: String,
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{CLDevice, Error};
+    use crate::{
+        api::{
+            create_program_with_source,
+            mock::{fail_next, MOCK_BINARY_MAGIC},
+            source_hash, BuildError, OCLErrorKind,
+        },
+        CLDevice, Error,
+    };
 
-    use super::KernelCache;
-    use std::collections::HashMap;
+    use super::{BinaryCache, BinaryKey, KernelCache, BUILD_OPTIONS};
 
     #[test]
     fn test_kernel_cache() -> Result<(), Error> {
+        crate::api::mock::install()?;
         let device = CLDevice::new(0)?;
+        let mut kernel_cache = KernelCache::default();
 
-        let mut kernel_cache = KernelCache {
-            kernel_cache: HashMap::new(),
-        };
+        let src = "
+            __kernel void foo(__global float* test) {}
+            __kernel void bar(__global float* test, __global float* out) {}
+        ";
 
-        /*let mut kernel_fn = || {
+        let foo = kernel_cache.kernel(&device, src, "foo")?.0;
+        let same_foo = kernel_cache.kernel(&device, src, "foo")?.0;
+        assert_eq!(foo, same_foo);
 
-        };*/
+        let bar = kernel_cache.kernel(&device, src, "bar")?;
+        assert_eq!(bar.name()?, "bar");
+        assert_eq!(bar.num_args()?, 2);
+        assert_ne!(foo, bar.0);
+        assert_eq!(kernel_cache.programs[src].len(), 1);
 
-        let kernel = kernel_cache
-            .kernel(
-                &device,
-                "
-                __kernel void foo(__global float* test) {}
-            ",
-            )?
+        // other build options result in another program
+        let foo_opt = kernel_cache
+            .kernel_with_options(&device, src, "-cl-fast-relaxed-math", "foo")?
             .0;
+        assert_ne!(foo, foo_opt);
+        assert_eq!(kernel_cache.programs.len(), 1);
+        assert_eq!(kernel_cache.programs[src].len(), 2);
 
-        let same_kernel = kernel_cache
-            .kernel(
-                &device,
-                "
-                __kernel void foo(__global float* test) {}
-            ",
-            )?
-            .0;
+        let err = kernel_cache.kernel(&device, src, "baz").unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<OCLErrorKind>(),
+            Some(&OCLErrorKind::InvalidKernelName)
+        );
 
-        assert_eq!(kernel, same_kernel);
+        let kernels = kernel_cache.create_kernels(&device, src)?;
+        let mut names = kernels.keys().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["bar", "foo"]);
+        assert_ne!(kernels["foo"].0, foo);
 
-        let another_kernel = kernel_cache
-            .kernel(
-                &device,
-                "
-                __kernel void bar(__global float* test, __global float* out) {}
-            ",
-            )?
-            .0;
+        // the program built with these options is reused
+        let kernels =
+            kernel_cache.create_kernels_with_options(&device, src, "-cl-fast-relaxed-math")?;
+        assert_eq!(kernels.len(), 2);
+        assert_eq!(kernel_cache.programs[src].len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_kernel_cache_build_log() -> Result<(), Error> {
+        crate::api::mock::install()?;
+        let device = CLDevice::new(0)?;
+
+        let src = "
+            #error missing semicolon
+            __kernel void broken(__global float* test) {}
+        ";
+        let err = KernelCache::default()
+            .kernel(&device, src, "broken")
+            .unwrap_err();
+        let err = err.downcast_ref::<BuildError>().unwrap();
+
+        assert_eq!(err.kind, OCLErrorKind::BuildProgramFailures);
+        assert_eq!(err.options.as_deref(), Some("-cl-std=CL1.2"));
+        assert_eq!(err.source_hash, Some(source_hash(src)));
+        assert!(err.logs[0].1.contains("missing semicolon"));
+        assert!(err.to_string().contains("missing semicolon"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_cache() -> Result<(), Error> {
+        crate::api::mock::install()?;
+        let device = CLDevice::new(0)?;
+
+        let dir = std::env::temp_dir().join(format!("min-cl-binary-cache-{}", std::process::id()));
+        let binary_cache = BinaryCache::new(&dir)?;
+        let src = "__kernel void cached(__global float* test) {}";
+        let key = binary_cache.key(&device, src, BUILD_OPTIONS)?;
+
+        KernelCache::with_binary_cache(binary_cache.clone()).kernel(&device, src, "cached")?;
+        assert!(binary_cache.path(&key).exists());
+
+        // the source cannot be compiled now, hence the kernel must come from the stored binary
+        fail_next("clCreateProgramWithSource", OCLErrorKind::OutOfResources);
+        KernelCache::with_binary_cache(binary_cache.clone()).kernel(&device, src, "cached")?;
+        assert!(unsafe { create_program_with_source(&device.ctx, src) }.is_err());
+
+        // a rejected binary falls back to compiling the source
+        std::fs::write(binary_cache.path(&key), b"not a binary")?;
+        KernelCache::with_binary_cache(binary_cache.clone()).kernel(&device, src, "cached")?;
+        assert!(binary_cache
+            .load(&key)
+            .unwrap()
+            .starts_with(MOCK_BINARY_MAGIC));
+
+        // an entry stored under another key with the same hash is not loaded
+        let other = binary_cache.key(&device, "__kernel void other() {}", BUILD_OPTIONS)?;
+        let colliding = BinaryKey {
+            hash: key.hash,
+            ..other
+        };
+        binary_cache.store(&colliding, b"other binary")?;
+        assert_eq!(binary_cache.load(&key), None);
+        assert_eq!(binary_cache.load(&colliding).unwrap(), b"other binary");
 
-        assert_ne!(kernel, another_kernel);
+        // a failing key query falls back to compiling the source
+        fail_next("clGetPlatformInfo", OCLErrorKind::OutOfHostMemory);
+        KernelCache::with_binary_cache(binary_cache.clone()).kernel(&device, src, "cached")?;
 
+        std::fs::remove_dir_all(dir)?;
         Ok(())
     }
 }
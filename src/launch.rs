@@ -0,0 +1,463 @@
+use crate::{
+    api::{Kernel, OCLErrorKind},
+    Access, CLDevice, Dependencies, Error, KernelArgs,
+};
+
+/// A global or local work size with one to three dimensions, e.g. `n`, `[n]`, `[width, height]` or `[width, height, depth]`.
+pub trait WorkSize {
+    /// Number of dimensions and the size of each dimension. Unused dimensions are `1`.
+    fn dims(&self) -> (usize, [usize; 3]);
+}
+
+impl WorkSize for usize {
+    #[inline]
+    fn dims(&self) -> (usize, [usize; 3]) {
+        (1, [*self, 1, 1])
+    }
+}
+
+impl WorkSize for [usize; 1] {
+    #[inline]
+    fn dims(&self) -> (usize, [usize; 3]) {
+        (1, [self[0], 1, 1])
+    }
+}
+
+impl WorkSize for [usize; 2] {
+    #[inline]
+    fn dims(&self) -> (usize, [usize; 3]) {
+        (2, [self[0], self[1], 1])
+    }
+}
+
+impl WorkSize for [usize; 3] {
+    #[inline]
+    fn dims(&self) -> (usize, [usize; 3]) {
+        (3, *self)
+    }
+}
+
+/// Global and local size of a [`Launch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkSizes {
+    /// Number of dimensions
+    pub dims: usize,
+    /// Rounded up to a multiple of the local size
+    pub global: [usize; 3],
+    /// `None` if the OpenCL implementation chooses the local size.
+    pub local: Option<[usize; 3]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LocalSize {
+    /// Chosen by the OpenCL implementation
+    Driver,
+    Auto,
+    Fixed(usize, [usize; 3]),
+}
+
+/// A kernel launch, created by [`CLDevice::launch`].
+///
+/// If a local size is set, the global size is rounded up to a multiple of it,
+/// hence kernels must check `get_global_id` against the actual problem size.
+///
+/// # Example
+/// ```ignore
+/// device.launch(&kernel).global([n]).local_auto().args((&mut buf, n as u32)).enqueue()?;
+/// ```
+#[must_use = "a launch does nothing until it is enqueued"]
+pub struct Launch<'a> {
+    device: &'a CLDevice,
+    kernel: &'a Kernel,
+    global: Option<(usize, [usize; 3])>,
+    local: LocalSize,
+    offset: Option<[usize; 3]>,
+    /// Dependencies of the memory object arguments and their access, `None` if the arguments were not set by [`Launch::args`]
+    deps: Option<Vec<(Dependencies, Access)>>,
+    /// The first error of setting the arguments, reported by [`Launch::enqueue`].
+    error: Option<Error>,
+}
+
+impl<'a> Launch<'a> {
+    /// The number of work items. Determines the dimensionality of the launch.
+    pub fn global(mut self, size: impl WorkSize) -> Self {
+        self.global = Some(size.dims());
+        self
+    }
+
+    /// The work group size, which must have as many dimensions as the global size.
+    pub fn local(mut self, size: impl WorkSize) -> Self {
+        let (dims, size) = size.dims();
+        self.local = LocalSize::Fixed(dims, size);
+        self
+    }
+
+    /// Picks a work group size from the work group size limit and the preferred work group size multiple of the kernel.
+    /// Kernels with `__attribute__((reqd_work_group_size(X, Y, Z)))` use the required size.
+    pub fn local_auto(mut self) -> Self {
+        self.local = LocalSize::Auto;
+        self
+    }
+
+    /// The global id of the first work item. Unused dimensions are ignored.
+    pub fn offset(mut self, offset: impl WorkSize) -> Self {
+        self.offset = Some(offset.dims().1);
+        self
+    }
+
+    /// Sets all arguments of the kernel, see [`Kernel::set_args`]. Errors are reported by [`Launch::enqueue`].
+    /// The kernel is ordered after the previous commands on the memory objects of `args`,
+    /// arguments that were set on the kernel directly are not tracked.
+    /// Memory objects that the kernel writes must be passed by mutable reference or by value,
+    /// shared references and [`crate::ReadOnly`] arguments are only read.
+    pub fn args<A: KernelArgs>(mut self, args: A) -> Self {
+        let mut deps = Vec::new();
+        args.dependencies(&mut deps);
+        self.deps = Some(
+            deps.into_iter()
+                .map(|(deps, access)| (deps.clone(), access))
+                .collect(),
+        );
+        if self.error.is_none() {
+            self.error = self.kernel.set_args(args).err();
+        }
+        self
+    }
+
+    /// Returns the sizes the kernel would be launched with, after validating them against the device limits.
+    pub fn work_sizes(&self) -> Result<WorkSizes, Error> {
+        let (dims, mut global) = self.global.ok_or(OCLErrorKind::InvalidGlobalWorkSize)?;
+        if global[..dims].contains(&0) {
+            return Err(OCLErrorKind::InvalidGlobalWorkSize.into());
+        }
+
+        let fixed = match self.local {
+            LocalSize::Driver => {
+                return Ok(WorkSizes {
+                    dims,
+                    global,
+                    local: None,
+                })
+            }
+            LocalSize::Auto => None,
+            LocalSize::Fixed(local_dims, local) => Some((local_dims, local)),
+        };
+
+        let cl_device = self.device.device();
+        let info = self.kernel.work_group_info(cl_device)?;
+        let max_items = cl_device.get_max_work_item_sizes()?;
+        let max_group = cl_device
+            .get_max_work_group_size()?
+            .min(info.work_group_size);
+
+        let local = match fixed {
+            Some((local_dims, local)) => {
+                if local_dims != dims {
+                    return Err(OCLErrorKind::InvalidWorkDimension.into());
+                }
+                if local[..dims].contains(&0) || local.iter().product::<usize>() > max_group {
+                    return Err(OCLErrorKind::InvalidWorkGroupSize.into());
+                }
+                local
+            }
+            // kernels with `reqd_work_group_size` can only be launched with that size
+            None if info.compile_work_group_size != [0; 3] => info.compile_work_group_size,
+            None => auto_local_size(
+                dims,
+                global,
+                info.preferred_work_group_size_multiple,
+                max_group,
+                &max_items,
+            ),
+        };
+        if local[..dims]
+            .iter()
+            .zip(&max_items)
+            .any(|(local, max)| local > max)
+        {
+            return Err(OCLErrorKind::InvalidWorkItemSize.into());
+        }
+
+        for (global, local) in global.iter_mut().zip(local).take(dims) {
+            *global = global
+                .checked_next_multiple_of(local)
+                .ok_or(OCLErrorKind::InvalidGlobalWorkSize)?;
+        }
+        Ok(WorkSizes {
+            dims,
+            global,
+            local: Some(local),
+        })
+    }
+
+    /// Enqueues the kernel after the previous commands on its memory object arguments.
+    /// Memory object arguments that are not passed by shared reference or wrapped in [`crate::ReadOnly`] count as written,
+    /// hence following commands on them wait for the kernel.
+    ///
+    /// Without [`Launch::args`], the arguments are not tracked and the kernel is ordered by the queue, see [`CLDevice::untracked`].
+    pub fn enqueue(self) -> Result<(), Error> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        let sizes = self.work_sizes()?;
+        let Some(deps) = &self.deps else {
+            return self.device.enqueue_nd_range_kernel(
+                self.kernel,
+                sizes.dims,
+                &sizes.global,
+                sizes.local.as_ref(),
+                self.offset,
+            );
+        };
+        let deps = deps
+            .iter()
+            .map(|(deps, access)| (deps, *access))
+            .collect::<Vec<_>>();
+        self.device.enqueue_kernel_with_deps(
+            self.kernel,
+            sizes.dims,
+            &sizes.global,
+            sizes.local.as_ref(),
+            self.offset,
+            &deps,
+        )
+    }
+}
+
+/// Starts with the preferred multiple in the first dimension and doubles the dimensions in turn
+/// while the work group fits into the limits. No dimension exceeds the global size,
+/// hence rounding up the global size less than doubles the work items of each dimension.
+fn auto_local_size(
+    dims: usize,
+    global: [usize; 3],
+    multiple: usize,
+    max_group: usize,
+    max_items: &[usize],
+) -> [usize; 3] {
+    let mut local = [1; 3];
+    local[0] = multiple
+        .clamp(1, max_group.min(max_items[0]))
+        .min(global[0]);
+
+    let mut grown = true;
+    while grown {
+        grown = false;
+        for dim in 0..dims {
+            let size = local[dim] * 2;
+            if size <= max_items[dim]
+                && local.iter().product::<usize>() * 2 <= max_group
+                && size <= global[dim]
+            {
+                local[dim] = size;
+                grown = true;
+            }
+        }
+    }
+    local
+}
+
+impl CLDevice {
+    /// Starts building a launch of `kernel` on this device.
+    pub fn launch<'a>(&'a self, kernel: &'a Kernel) -> Launch<'a> {
+        Launch {
+            device: self,
+            kernel,
+            global: None,
+            local: LocalSize::Driver,
+            offset: None,
+            deps: None,
+            error: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{auto_local_size, WorkSizes};
+    use crate::{
+        api::{
+            build_program, create_kernel, create_program_with_source,
+            mock::{self, MockCommand},
+            Kernel, KernelArgError, OCLErrorKind,
+        },
+        Buffer, CLDevice, Error,
+    };
+
+    fn kernel(device: &CLDevice, src: &str, name: &str) -> Result<Kernel, Error> {
+        let program = unsafe { create_program_with_source(device.ctx(), src)? };
+        unsafe { build_program(&program, &[device.device()], None)? };
+        create_kernel(&program, name)
+    }
+
+    #[track_caller]
+    fn assert_kind<T: std::fmt::Debug>(result: Result<T, Error>, kind: OCLErrorKind) {
+        let err = result.expect_err("expected an error");
+        assert_eq!(err.downcast_ref::<OCLErrorKind>(), Some(&kind));
+    }
+
+    #[test]
+    fn test_auto_local_size() {
+        assert_eq!(
+            auto_local_size(1, [1000, 1, 1], 32, 256, &[256; 3]),
+            [256, 1, 1]
+        );
+        assert_eq!(
+            auto_local_size(1, [10, 1, 1], 32, 256, &[256; 3]),
+            [10, 1, 1]
+        );
+        assert_eq!(auto_local_size(1, [1, 1, 1], 32, 256, &[256; 3]), [1; 3]);
+        assert_eq!(
+            auto_local_size(2, [512, 512, 1], 32, 256, &[256; 3]),
+            [128, 2, 1]
+        );
+        assert_eq!(
+            auto_local_size(2, [40, 3, 1], 32, 256, &[256; 3]),
+            [32, 2, 1]
+        );
+        assert_eq!(
+            auto_local_size(2, [3, 1000, 1], 32, 256, &[256; 3]),
+            [3, 64, 1]
+        );
+        assert_eq!(
+            auto_local_size(3, [100, 7, 5], 32, 1024, &[1024; 3]),
+            [64, 4, 4]
+        );
+        assert_eq!(
+            auto_local_size(3, [64, 64, 64], 16, 192, &[64; 3]),
+            [32, 2, 2]
+        );
+    }
+
+    #[test]
+    fn test_launch() -> Result<(), Error> {
+        mock::install()?;
+        let device = CLDevice::new(0)?;
+        let src = "__kernel void launch_fill(__global float* out, uint n) {}";
+        let kernel = kernel(&device, src, "launch_fill")?;
+        let mut out = Buffer::<f32>::new(&device, 1000)?;
+
+        device
+            .launch(&kernel)
+            .global(1000)
+            .local_auto()
+            .args((&mut out, 1000u32))
+            .enqueue()?;
+        device
+            .launch(&kernel)
+            .global([30, 20])
+            .local([8, 8])
+            .offset([1, 2])
+            .enqueue()?;
+        device.launch(&kernel).global([7, 3, 2]).enqueue()?;
+
+        let records = mock::records(device.queue().0);
+        let launches = records
+            .iter()
+            .filter_map(|record| match &record.command {
+                MockCommand::NDRangeKernel {
+                    global_work_size,
+                    local_work_size,
+                    global_work_offset,
+                    ..
+                } => Some((
+                    global_work_size.clone(),
+                    local_work_size.clone(),
+                    global_work_offset.clone(),
+                )),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            launches,
+            [
+                (vec![1024], Some(vec![256]), None),
+                (vec![32, 24], Some(vec![8, 8]), Some(vec![1, 2])),
+                (vec![7, 3, 2], None, None),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_reqd_work_group_size() -> Result<(), Error> {
+        mock::install()?;
+        let device = CLDevice::new(0)?;
+        let src = "__kernel __attribute__((reqd_work_group_size(16, 2, 1))) void launch_reqd() {}";
+        let kernel = kernel(&device, src, "launch_reqd")?;
+
+        let launch = device.launch(&kernel).global([100, 5]).local_auto();
+        assert_eq!(
+            launch.work_sizes()?,
+            WorkSizes {
+                dims: 2,
+                global: [112, 6, 1],
+                local: Some([16, 2, 1]),
+            }
+        );
+        launch.enqueue()?;
+        assert_kind(
+            device
+                .launch(&kernel)
+                .global([100, 5])
+                .local([8, 2])
+                .enqueue(),
+            OCLErrorKind::InvalidWorkGroupSize,
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_launch_errors() -> Result<(), Error> {
+        mock::install()?;
+        let device = CLDevice::new(0)?;
+        let src = "__kernel void launch_errors(__global float* out) {}";
+        let kernel = kernel(&device, src, "launch_errors")?;
+        let mut out = Buffer::<f32>::new(&device, 8)?;
+        kernel.set_args((&out,))?;
+
+        assert_kind(
+            device.launch(&kernel).enqueue(),
+            OCLErrorKind::InvalidGlobalWorkSize,
+        );
+        assert_kind(
+            device.launch(&kernel).global([8, 0]).enqueue(),
+            OCLErrorKind::InvalidGlobalWorkSize,
+        );
+        assert_kind(
+            device.launch(&kernel).global([8, 8]).local(8).enqueue(),
+            OCLErrorKind::InvalidWorkDimension,
+        );
+        assert_kind(
+            device
+                .launch(&kernel)
+                .global([8, 8])
+                .local([32, 16])
+                .enqueue(),
+            OCLErrorKind::InvalidWorkGroupSize,
+        );
+        assert_kind(
+            device
+                .launch(&kernel)
+                .global([1, 1, 128])
+                .local([1, 1, 128])
+                .enqueue(),
+            OCLErrorKind::InvalidWorkItemSize,
+        );
+
+        // rounding up to a multiple of the local size must not overflow
+        assert_kind(
+            device.launch(&kernel).global(usize::MAX).local(8).enqueue(),
+            OCLErrorKind::InvalidGlobalWorkSize,
+        );
+
+        let err = device
+            .launch(&kernel)
+            .global(8)
+            .args((&mut out, 1u32))
+            .enqueue()
+            .unwrap_err();
+        let err = err.downcast_ref::<KernelArgError>().unwrap();
+        assert_eq!((err.kind, err.index), (OCLErrorKind::InvalidArgIndex, 1));
+        Ok(())
+    }
+}
@@ -1,8 +1,29 @@
 pub mod api;
+mod buffer;
+mod capabilities;
 mod cl_device;
+mod dependencies;
+mod device_selector;
+mod image;
+mod kernel_arg;
 mod kernel_cache;
+mod launch;
+mod mapped;
 mod measure_perf;
+mod svm;
+mod trace;
+pub use buffer::*;
+pub use capabilities::*;
 pub use cl_device::*;
+pub use dependencies::*;
+pub use device_selector::*;
+pub use image::*;
+pub use kernel_arg::*;
+pub use kernel_cache::{BinaryCache, BinaryKey, KernelCache};
+pub use launch::*;
+pub use mapped::*;
+pub use svm::*;
+pub use trace::*;
 
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 
@@ -23,6 +44,7 @@ mod tests {
 
     #[test]
     fn test_devices() {
+        crate::api::mock::install().unwrap();
         init_devices();
         println!("{:?}", DEVICES.read())
     }
@@ -0,0 +1,325 @@
+use std::{
+    ffi::c_void,
+    marker::PhantomData,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+};
+
+use crate::{
+    api::{
+        enqueue_map_buffer_with_event, enqueue_svm_map, enqueue_svm_unmap,
+        enqueue_unmap_mem_object, MapFlags,
+    },
+    Access, Buffer, CLDevice, Dependencies, Error, Pod, SvmBuffer,
+};
+
+/// A read-only view of a mapped [`Buffer`] or [`SvmBuffer`]. The region is unmapped on drop, use [`MappedSlice::unmap`] to handle errors.
+///
+/// Created by [`CLDevice::map`] and [`CLDevice::map_svm`].
+pub struct MappedSlice<'a, T> {
+    mapping: Mapping<'a>,
+    _p: PhantomData<&'a [T]>,
+}
+
+/// A mutable view of a mapped [`Buffer`] or [`SvmBuffer`]. The region is unmapped on drop, which makes the writes visible to the device.
+/// Use [`MappedSliceMut::unmap`] to handle errors of the unmap.
+///
+/// Created by [`CLDevice::map_mut`] and [`CLDevice::map_svm_mut`].
+pub struct MappedSliceMut<'a, T> {
+    mapping: Mapping<'a>,
+    _p: PhantomData<&'a mut [T]>,
+}
+
+struct Mapping<'a> {
+    device: &'a CLDevice,
+    /// The mapped memory object, `None` for shared virtual memory.
+    mem: Option<*mut c_void>,
+    ptr: *mut c_void,
+    len: usize,
+    deps: &'a Dependencies,
+    /// `Access::Write` unless mapped with `MapFlags::Read` only
+    access: Access,
+}
+
+fn map_access(flags: u64) -> Access {
+    if flags == MapFlags::Read as u64 {
+        Access::Read
+    } else {
+        Access::Write
+    }
+}
+
+impl<'a> Mapping<'a> {
+    fn new<T>(device: &'a CLDevice, buf: &'a Buffer<T>, flags: u64) -> Result<Mapping<'a>, Error> {
+        let access = map_access(flags);
+        let mut ptr = std::ptr::null_mut();
+        device.enqueue_with_deps(&[(buf.dependencies(), access)], |wait_list| unsafe {
+            let (mapped, event) = enqueue_map_buffer_with_event::<T>(
+                device.queue(),
+                buf.as_ptr(),
+                true,
+                flags,
+                0,
+                buf.len(),
+                Some(wait_list),
+            )?;
+            ptr = mapped;
+            Ok(event)
+        })?;
+        Ok(Mapping {
+            device,
+            mem: Some(buf.as_ptr()),
+            ptr,
+            len: buf.len(),
+            deps: buf.dependencies(),
+            access,
+        })
+    }
+
+    fn svm<T>(
+        device: &'a CLDevice,
+        buf: &'a SvmBuffer<T>,
+        flags: u64,
+    ) -> Result<Mapping<'a>, Error> {
+        let access = map_access(flags);
+        device.enqueue_with_deps(&[(buf.dependencies(), access)], |wait_list| unsafe {
+            enqueue_svm_map(
+                device.queue(),
+                true,
+                flags,
+                buf.as_ptr(),
+                buf.len(),
+                Some(wait_list),
+            )
+        })?;
+        Ok(Mapping {
+            device,
+            mem: None,
+            ptr: buf.as_ptr() as *mut c_void,
+            len: buf.len(),
+            deps: buf.dependencies(),
+            access,
+        })
+    }
+
+    fn unmap(&mut self) -> Result<(), Error> {
+        // following commands on the memory object must not start before the region is unmapped
+        self.device
+            .enqueue_with_deps(&[(self.deps, self.access)], |wait_list| unsafe {
+                match self.mem {
+                    Some(mem) => enqueue_unmap_mem_object(
+                        self.device.queue(),
+                        mem,
+                        self.ptr,
+                        Some(wait_list),
+                    ),
+                    None => enqueue_svm_unmap(self.device.queue(), self.ptr, Some(wait_list)),
+                }
+            })?;
+        Ok(())
+    }
+}
+
+impl<'a, T> MappedSlice<'a, T> {
+    /// Unmaps the region. Unlike dropping the guard, this reports errors.
+    pub fn unmap(self) -> Result<(), Error> {
+        ManuallyDrop::new(self).mapping.unmap()
+    }
+}
+
+impl<'a, T> MappedSliceMut<'a, T> {
+    /// Unmaps the region. Unlike dropping the guard, this reports errors.
+    pub fn unmap(self) -> Result<(), Error> {
+        ManuallyDrop::new(self).mapping.unmap()
+    }
+}
+
+impl<T> Deref for MappedSlice<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { std::slice::from_raw_parts(self.mapping.ptr as *const T, self.mapping.len) }
+    }
+}
+
+impl<T> Deref for MappedSliceMut<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { std::slice::from_raw_parts(self.mapping.ptr as *const T, self.mapping.len) }
+    }
+}
+
+impl<T> DerefMut for MappedSliceMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { std::slice::from_raw_parts_mut(self.mapping.ptr as *mut T, self.mapping.len) }
+    }
+}
+
+impl<T> Drop for MappedSlice<'_, T> {
+    fn drop(&mut self) {
+        // a failed unmap must not panic in drop, `MappedSlice::unmap` reports the error instead
+        let _ = self.mapping.unmap();
+    }
+}
+
+impl<T> Drop for MappedSliceMut<'_, T> {
+    fn drop(&mut self) {
+        // a failed unmap must not panic in drop, `MappedSliceMut::unmap` reports the error instead
+        let _ = self.mapping.unmap();
+    }
+}
+
+impl CLDevice {
+    /// Maps the buffer for reading. Blocks until the mapping is finished.
+    ///
+    /// The buffer stays borrowed, hence kernels may only read it meanwhile:
+    /// ```compile_fail
+    /// # use min_cl::{api::Kernel, Buffer, CLDevice, Error};
+    /// # fn launch(device: &CLDevice, kernel: &Kernel, buf: &mut Buffer<f32>) -> Result<(), Error> {
+    /// let mapped = device.map(buf)?;
+    /// device.launch(kernel).global(4).args((&mut *buf,)).enqueue()?;
+    /// drop(mapped);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn map<'a, T: Pod>(&'a self, buf: &'a Buffer<T>) -> Result<MappedSlice<'a, T>, Error> {
+        Ok(MappedSlice {
+            mapping: Mapping::new(self, buf, MapFlags::Read as u64)?,
+            _p: PhantomData,
+        })
+    }
+
+    /// Maps the buffer for writing with the given `MapFlags`, e.g. `MapFlags::Read | MapFlags::Write`.
+    /// With `MapFlags::WriteInvalidateRegion`, the contents of the slice are unspecified until written.
+    /// Blocks until the mapping is finished.
+    pub fn map_mut<'a, T: Pod>(
+        &'a self,
+        buf: &'a mut Buffer<T>,
+        flags: u64,
+    ) -> Result<MappedSliceMut<'a, T>, Error> {
+        Ok(MappedSliceMut {
+            mapping: Mapping::new(self, buf, flags)?,
+            _p: PhantomData,
+        })
+    }
+
+    /// Maps the shared virtual memory for reading. Blocks until the mapping is finished.
+    pub fn map_svm<'a, T: Pod>(
+        &'a self,
+        buf: &'a SvmBuffer<T>,
+    ) -> Result<MappedSlice<'a, T>, Error> {
+        Ok(MappedSlice {
+            mapping: Mapping::svm(self, buf, MapFlags::Read as u64)?,
+            _p: PhantomData,
+        })
+    }
+
+    /// Maps the shared virtual memory for writing with the given `MapFlags`. Blocks until the mapping is finished.
+    pub fn map_svm_mut<'a, T: Pod>(
+        &'a self,
+        buf: &'a mut SvmBuffer<T>,
+        flags: u64,
+    ) -> Result<MappedSliceMut<'a, T>, Error> {
+        Ok(MappedSliceMut {
+            mapping: Mapping::svm(self, buf, flags)?,
+            _p: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        api::{
+            mock::{self, MockCommand},
+            MapFlags, OCLErrorKind,
+        },
+        Access, Buffer, CLDevice, Error, KernelCache,
+    };
+
+    #[test]
+    fn test_map() -> Result<(), Error> {
+        mock::install()?;
+        let device = CLDevice::new(0)?;
+        let mut buf = Buffer::from_slice(&device, &[1f32, 2., 3., 4.])?;
+
+        {
+            let mut mapped = device.map_mut(&mut buf, MapFlags::Read | MapFlags::Write)?;
+            assert_eq!(&*mapped, [1., 2., 3., 4.]);
+            mapped[2] = 7.;
+        }
+        assert_eq!(device.read(&buf)?, [1., 2., 7., 4.]);
+
+        let mapped = device.map(&buf)?;
+        assert_eq!(mapped.iter().sum::<f32>(), 14.);
+        mapped.unmap()?;
+
+        let mut mapped = device.map_mut(&mut buf, MapFlags::WriteInvalidateRegion as u64)?;
+        mapped.copy_from_slice(&[0.; 4]);
+        drop(mapped);
+
+        let records = mock::records(device.queue().0);
+        let unmaps = records
+            .iter()
+            .filter(|record| matches!(record.command, MockCommand::UnmapMemObject { .. }))
+            .count();
+        assert_eq!(unmaps, 3);
+
+        // the map is recorded, hence the unmap waits for it
+        let [.., map, last] = &records[..] else {
+            unreachable!()
+        };
+        assert!(matches!(map.command, MockCommand::MapBuffer { .. }));
+        assert_eq!(last.wait_list, [map.event]);
+        // the unmap is waited for by the next command on the buffer
+        assert_eq!(buf.dependencies().wait_list(Access::Read)[0].0, last.event);
+        assert_eq!(device.read(&buf)?, [0.; 4]);
+
+        // a failed unmap is reported by `unmap`, but does not panic on drop
+        let mapped = device.map(&buf)?;
+        mock::fail_next("clEnqueueUnmapMemObject", OCLErrorKind::OutOfResources);
+        drop(mapped);
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_launch() -> Result<(), Error> {
+        mock::install()?;
+        mock::register_kernel("map_copy", |_| {});
+        let device = CLDevice::new(0)?;
+        // keeps the launch from being pruned
+        mock::defer_completion(device.queue().0);
+        let src = "__kernel void map_copy(__global const float* x, __global float* y) {}";
+        let kernel = KernelCache::default()
+            .create_kernels(&device, src)?
+            .remove("map_copy")
+            .unwrap();
+        let buf = Buffer::from_slice(&device, &[1f32, 2., 3., 4.])?;
+        let mut out = Buffer::<f32>::new(&device, 4)?;
+
+        // a shared reference is only read, hence it can be passed while the buffer is mapped for reading
+        let mapped = device.map(&buf)?;
+        device
+            .launch(&kernel)
+            .global(4)
+            .args((&buf, &mut out))
+            .enqueue()?;
+        let launch = mock::records(device.queue().0).pop().unwrap();
+        assert_eq!(&*mapped, [1., 2., 3., 4.]);
+        mapped.unmap()?;
+
+        let waits = |buf: &Buffer<f32>, access| {
+            buf.dependencies()
+                .wait_list(access)
+                .iter()
+                .map(|event| event.0)
+                .collect::<Vec<_>>()
+        };
+        // following reads of the input do not wait for the kernel, writes do
+        assert!(!waits(&buf, Access::Read).contains(&launch.event));
+        assert!(waits(&buf, Access::Write).contains(&launch.event));
+        assert!(waits(&out, Access::Read).contains(&launch.event));
+        Ok(())
+    }
+}
@@ -1,12 +1,11 @@
-use std::{ffi::c_void, mem::size_of, time::Instant};
+use std::time::Instant;
 
 use crate::{
     api::{
-        build_program, create_buffer, create_kernels_in_program, create_program_with_source,
-        enqueue_nd_range_kernel, release_mem_object, set_kernel_arg, wait_for_event, MemFlags,
-        OCLErrorKind,
+        build_program, create_kernels_in_program, create_program_with_source,
+        enqueue_nd_range_kernel, wait_for_event, OCLErrorKind,
     },
-    CLDevice, Error,
+    Buffer, CLDevice, Error,
 };
 
 const SIZE: usize = 100_000;
@@ -30,28 +29,11 @@ pub fn measure_perf(device: &CLDevice) -> Result<std::time::Duration, Error> {
             .ok_or(OCLErrorKind::InvalidKernel)?
     };
 
-    let lhs = unsafe {
-        create_buffer::<i32>(
-            &device.ctx,
-            MemFlags::MemReadWrite | MemFlags::MemCopyHostPtr,
-            SIZE,
-            Some(&vec![1i32; SIZE]),
-        )?
-    };
-    let rhs = unsafe {
-        create_buffer::<i32>(
-            &device.ctx,
-            MemFlags::MemReadWrite | MemFlags::MemCopyHostPtr,
-            SIZE,
-            Some(&vec![2i32; SIZE]),
-        )?
-    };
-    let out =
-        unsafe { create_buffer::<i32>(&device.ctx, MemFlags::MemReadWrite as u64, SIZE, None)? };
+    let lhs = Buffer::from_slice(device, &vec![1i32; SIZE])?;
+    let rhs = Buffer::from_slice(device, &vec![2i32; SIZE])?;
+    let out = Buffer::<i32>::new(device, SIZE)?;
 
-    unsafe { set_kernel_arg(&kernel, 0, lhs, size_of::<*const c_void>(), false)? };
-    unsafe { set_kernel_arg(&kernel, 1, rhs, size_of::<*const c_void>(), false)? };
-    unsafe { set_kernel_arg(&kernel, 2, out, size_of::<*const c_void>(), false)? };
+    kernel.set_args((&lhs, &rhs, &out))?;
 
     let start = Instant::now();
 
@@ -70,11 +52,5 @@ pub fn measure_perf(device: &CLDevice) -> Result<std::time::Duration, Error> {
         };
     }
 
-    let dur = start.elapsed();
-
-    unsafe { release_mem_object(lhs)? };
-    unsafe { release_mem_object(rhs)? };
-    unsafe { release_mem_object(out)? };
-
-    Ok(dur)
+    Ok(start.elapsed())
 }
@@ -0,0 +1,364 @@
+use std::{ffi::c_void, fmt::Debug, marker::PhantomData, ops::RangeBounds};
+
+use crate::{
+    api::{
+        enqueue_svm_mem_fill, enqueue_svm_memcpy, set_kernel_arg_svm_pointer, svm_alloc, svm_free,
+        wait_for_events, Context, Kernel, MemFlags, OCLErrorKind, SvmCapability,
+    },
+    buffer::resolve_range,
+    capabilities::optional,
+    Access, CLDevice, Dependencies, Error, Pod,
+};
+
+/// A shared virtual memory allocation that holds `len` elements of type `T`. Requires OpenCL 2.0.
+///
+/// The device and the host use the same addresses, hence pointers into the allocation can be stored in other SVM allocations and followed by kernels.
+/// Coarse-grain allocations must be mapped with [`CLDevice::map_svm`] or [`CLDevice::map_svm_mut`] for host access,
+/// fine-grain allocations can be accessed with [`SvmBuffer::as_slice`] and [`SvmBuffer::as_mut_slice`].
+///
+/// The allocation is freed on drop after the commands recorded in its [`Dependencies`] are finished.
+/// Kernels that received the allocation through [`SvmBuffer::set_kernel_arg`] instead of [`crate::Launch::args`]
+/// are not recorded and must be finished before, e.g. with [`CLDevice::wait_for_events`].
+pub struct SvmBuffer<T> {
+    ptr: *mut T,
+    len: usize,
+    flags: u64,
+    ctx: Context,
+    deps: Dependencies,
+    _p: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for SvmBuffer<T> {}
+unsafe impl<T: Sync> Sync for SvmBuffer<T> {}
+
+impl<T> SvmBuffer<T> {
+    /// Allocates an uninitialized coarse-grain allocation with `len` elements.
+    pub fn new(device: &CLDevice, len: usize) -> Result<SvmBuffer<T>, Error> {
+        SvmBuffer::with_flags(device, MemFlags::MemReadWrite as u64, len)
+    }
+
+    /// Allocates a coarse-grain allocation that is initialized with the contents of `data`.
+    pub fn from_slice(device: &CLDevice, data: &[T]) -> Result<SvmBuffer<T>, Error>
+    where
+        T: Pod,
+    {
+        let mut buf = SvmBuffer::new(device, data.len())?;
+        device.write_svm(&mut buf, data)?;
+        Ok(buf)
+    }
+
+    /// Allocates `len` elements with the given `MemFlags`, e.g. `MemFlags::MemReadWrite | MemFlags::MemSvmFineGrainBuffer`.
+    /// Returns `OCLErrorKind::SvmNotSupported` if the device lacks the `SvmCapability` required by `flags`.
+    pub fn with_flags(device: &CLDevice, flags: u64, len: usize) -> Result<SvmBuffer<T>, Error> {
+        let required = if flags & MemFlags::MemSvmAtomics as u64 != 0 {
+            SvmCapability::Atomics
+        } else if flags & MemFlags::MemSvmFineGrainBuffer as u64 != 0 {
+            SvmCapability::FineGrainBuffer
+        } else {
+            SvmCapability::CoarseGrainBuffer
+        };
+        if !device.supports_svm(required)? {
+            return Err(OCLErrorKind::SvmNotSupported.into());
+        }
+        if len == 0 {
+            return Err(OCLErrorKind::InvalidBufferSize.into());
+        }
+
+        let ptr = unsafe { svm_alloc::<T>(device.ctx(), flags, len, 0)? };
+        Ok(SvmBuffer {
+            ptr,
+            len,
+            flags,
+            ctx: device.ctx().clone(),
+            deps: Dependencies::default(),
+            _p: PhantomData,
+        })
+    }
+
+    /// Number of elements of the allocation.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// `MemFlags` the allocation was created with.
+    #[inline]
+    pub fn flags(&self) -> u64 {
+        self.flags
+    }
+
+    /// Whether the host can access the allocation without mapping it.
+    #[inline]
+    pub fn is_fine_grain(&self) -> bool {
+        self.flags & MemFlags::MemSvmFineGrainBuffer as u64 != 0
+    }
+
+    /// The address of the first element, valid on the host and the device as long as the allocation is alive.
+    #[inline]
+    pub fn as_ptr(&self) -> *mut T {
+        self.ptr
+    }
+
+    /// The commands that access the allocation, see [`CLDevice::enqueue_with_deps`].
+    #[inline]
+    pub fn dependencies(&self) -> &Dependencies {
+        &self.deps
+    }
+
+    /// Passes the allocation as the `index`-th argument of `kernel`, which must be created in the same context.
+    pub fn set_kernel_arg(&self, kernel: &Kernel, index: usize) -> Result<(), Error> {
+        unsafe { set_kernel_arg_svm_pointer(kernel, index, self.ptr as *const c_void) }
+    }
+
+    /// # Safety
+    /// The allocation must be fine-grain or mapped, and the device must not write to it while the slice is alive.
+    pub unsafe fn as_slice(&self) -> &[T] {
+        std::slice::from_raw_parts(self.ptr, self.len)
+    }
+
+    /// # Safety
+    /// The allocation must be fine-grain or mapped, and the device must not access it while the slice is alive.
+    pub unsafe fn as_mut_slice(&mut self) -> &mut [T] {
+        std::slice::from_raw_parts_mut(self.ptr, self.len)
+    }
+}
+
+impl<T> Drop for SvmBuffer<T> {
+    fn drop(&mut self) {
+        // `clSVMFree` does not wait for enqueued commands. If waiting fails, the commands terminated abnormally
+        let _ = unsafe { wait_for_events(&self.deps.wait_list(Access::Write)) };
+        unsafe { svm_free(&self.ctx, self.ptr as *mut c_void) }
+    }
+}
+
+impl<T> Debug for SvmBuffer<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SvmBuffer")
+            .field("ptr", &self.ptr)
+            .field("len", &self.len)
+            .field("flags", &self.flags)
+            .finish()
+    }
+}
+
+impl CLDevice {
+    /// Returns the `SvmCapability` bitfield of the device, `0` if the device does not support OpenCL 2.0.
+    pub fn svm_capabilities(&self) -> Result<u64, Error> {
+        optional(self.device().get_svm_capabilities())
+    }
+
+    /// Checks whether the device supports the given kind of shared virtual memory.
+    pub fn supports_svm(&self, capability: SvmCapability) -> Result<bool, Error> {
+        Ok(capability.is_set(self.svm_capabilities()?))
+    }
+
+    /// Reads the contents of the allocation into a new `Vec`. Blocks until the read is finished.
+    pub fn read_svm<T: Pod>(&self, buf: &SvmBuffer<T>) -> Result<Vec<T>, Error> {
+        // Pod guarantees that zeroed memory is a valid value
+        let mut data = vec![unsafe { std::mem::zeroed::<T>() }; buf.len()];
+        self.read_svm_into(buf, &mut data)?;
+        Ok(data)
+    }
+
+    /// Reads the contents of the allocation into `data`. Blocks until the read is finished.
+    pub fn read_svm_into<T: Pod>(&self, buf: &SvmBuffer<T>, data: &mut [T]) -> Result<(), Error> {
+        if data.len() != buf.len() {
+            return Err(OCLErrorKind::BufferLengthMismatch.into());
+        }
+        self.enqueue_with_deps(&[(buf.dependencies(), Access::Read)], |wait_list| unsafe {
+            enqueue_svm_memcpy(
+                self.queue(),
+                true,
+                data.as_mut_ptr(),
+                buf.as_ptr(),
+                data.len(),
+                Some(wait_list),
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Writes `data` to the allocation. Blocks until the write is finished.
+    pub fn write_svm<T: Pod>(&self, buf: &mut SvmBuffer<T>, data: &[T]) -> Result<(), Error> {
+        if data.len() != buf.len() {
+            return Err(OCLErrorKind::BufferLengthMismatch.into());
+        }
+        self.enqueue_with_deps(&[(buf.dependencies(), Access::Write)], |wait_list| unsafe {
+            enqueue_svm_memcpy(
+                self.queue(),
+                true,
+                buf.as_ptr(),
+                data.as_ptr(),
+                data.len(),
+                Some(wait_list),
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Enqueues a copy of the contents of `src` to `dst`. Following commands on `dst` wait for the copy.
+    pub fn copy_svm<T>(&self, dst: &mut SvmBuffer<T>, src: &SvmBuffer<T>) -> Result<(), Error> {
+        if dst.len() != src.len() {
+            return Err(OCLErrorKind::BufferLengthMismatch.into());
+        }
+        self.enqueue_with_deps(
+            &[
+                (dst.dependencies(), Access::Write),
+                (src.dependencies(), Access::Read),
+            ],
+            |wait_list| unsafe {
+                enqueue_svm_memcpy(
+                    self.queue(),
+                    false,
+                    dst.as_ptr(),
+                    src.as_ptr(),
+                    src.len(),
+                    Some(wait_list),
+                )
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Fills the elements of `range` with `pattern`. The size of `T` must be a power of two and at most 128 bytes.
+    /// The fill is ordered after the previous commands on the allocation, following commands on it wait for the fill.
+    pub fn fill_svm<T: Pod>(
+        &self,
+        buf: &mut SvmBuffer<T>,
+        pattern: T,
+        range: impl RangeBounds<usize>,
+    ) -> Result<(), Error> {
+        let size = std::mem::size_of::<T>();
+        if !size.is_power_of_two() || size > 128 {
+            return Err(OCLErrorKind::InvalidPatternSize.into());
+        }
+        let range = resolve_range(range, buf.len())?;
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        self.enqueue_with_deps(&[(buf.dependencies(), Access::Write)], |wait_list| unsafe {
+            enqueue_svm_mem_fill(
+                self.queue(),
+                buf.as_ptr().add(range.start),
+                &pattern,
+                range.len(),
+                Some(wait_list),
+            )
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        api::{
+            build_program, create_kernel, create_program_with_source,
+            mock::{self, MockCommand},
+            MapFlags, MemFlags, OCLErrorKind, SvmCapability,
+        },
+        CLDevice, Error, SvmBuffer,
+    };
+
+    #[test]
+    fn test_svm_capabilities() -> Result<(), Error> {
+        mock::install()?;
+        let device = CLDevice::new(0)?;
+
+        assert!(device.supports_svm(SvmCapability::CoarseGrainBuffer)?);
+        assert!(device.supports_svm(SvmCapability::FineGrainBuffer)?);
+        assert!(!device.supports_svm(SvmCapability::FineGrainSystem)?);
+        assert!(device.supports_svm(SvmCapability::Atomics)?);
+        assert_eq!(device.capabilities()?.svm_capabilities, 0b1011);
+        Ok(())
+    }
+
+    #[test]
+    fn test_svm_buffer() -> Result<(), Error> {
+        mock::install()?;
+        let device = CLDevice::new(0)?;
+
+        let mut buf = SvmBuffer::from_slice(&device, &[1u32, 2, 3, 4, 5, 6, 7, 8])?;
+        assert!(!buf.is_fine_grain());
+        assert_eq!(device.read_svm(&buf)?, [1, 2, 3, 4, 5, 6, 7, 8]);
+
+        device.fill_svm(&mut buf, 0, 2..6)?;
+        let mut copy = SvmBuffer::new(&device, 8)?;
+        device.copy_svm(&mut copy, &buf)?;
+
+        let last = mock::records(device.queue().0).pop().unwrap();
+        assert_eq!(
+            last.command,
+            MockCommand::SvmMemcpy {
+                dst: copy.as_ptr() as *mut _,
+                src: buf.as_ptr() as *const _,
+                size: 32,
+            }
+        );
+
+        assert_eq!(device.read_svm(&copy)?, [1, 2, 0, 0, 0, 0, 7, 8]);
+
+        {
+            let mut mapped = device.map_svm_mut(&mut copy, MapFlags::Read | MapFlags::Write)?;
+            mapped[0] = 9;
+        }
+        assert_eq!(device.map_svm(&copy)?[..2], [9, 2]);
+
+        let err = device.write_svm(&mut buf, &[1, 2]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<OCLErrorKind>(),
+            Some(&OCLErrorKind::BufferLengthMismatch)
+        );
+
+        assert_eq!(mock::svm_allocation_count(device.ctx().0), 2);
+        drop((buf, copy));
+        assert_eq!(mock::svm_allocation_count(device.ctx().0), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_svm_fine_grain() -> Result<(), Error> {
+        mock::install()?;
+        let device = CLDevice::new(0)?;
+
+        let flags = MemFlags::MemReadWrite | MemFlags::MemSvmFineGrainBuffer;
+        let mut buf = SvmBuffer::<f32>::with_flags(&device, flags, 4)?;
+        assert!(buf.is_fine_grain());
+        unsafe { buf.as_mut_slice() }.copy_from_slice(&[1., 2., 3., 4.]);
+        assert_eq!(device.read_svm(&buf)?, [1., 2., 3., 4.]);
+
+        // the kernel follows the pointer of the allocation
+        mock::register_kernel("mock_svm_scale", |launch| {
+            let ptr = launch.scalar::<*mut f32>(0);
+            for i in 0..launch.global_work_size()[0] {
+                unsafe { *ptr.add(i) *= 3. };
+            }
+        });
+        let program = unsafe {
+            create_program_with_source(
+                device.ctx(),
+                "__kernel void mock_svm_scale(__global float* values) {}",
+            )?
+        };
+        unsafe { build_program(&program, &[device.device()], None)? };
+        let kernel = create_kernel(&program, "mock_svm_scale")?;
+        buf.set_kernel_arg(&kernel, 0)?;
+        device.enqueue_nd_range_kernel(&kernel, 1, &[4, 0, 0], None, None)?;
+        device.wait_for_events()?;
+        assert_eq!(unsafe { buf.as_slice() }, [3., 6., 9., 12.]);
+
+        let err =
+            SvmBuffer::<f32>::with_flags(&device, MemFlags::MemReadWrite as u64, 0).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<OCLErrorKind>(),
+            Some(&OCLErrorKind::InvalidBufferSize)
+        );
+        Ok(())
+    }
+}
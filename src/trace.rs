@@ -0,0 +1,198 @@
+use std::{fmt::Write as _, path::Path};
+
+use crate::{api::Event, Error};
+
+/// A command captured by a [`TraceRecorder`].
+#[derive(Debug, Clone)]
+pub struct TraceEntry {
+    /// Kernel name or transfer kind, e.g. `"read_buffer"`.
+    pub name: String,
+    /// `"kernel"` or `"transfer"`.
+    pub category: &'static str,
+    /// Address of the command queue the command was enqueued on.
+    pub queue: usize,
+    /// Additional details shown in the viewer, e.g. work sizes or transferred bytes.
+    pub args: Vec<(&'static str, String)>,
+    pub event: Event,
+}
+
+/// Records enqueued commands and exports their device timelines in the Chrome Trace Event format.
+///
+/// The exported JSON can be opened in `chrome://tracing` or <https://ui.perfetto.dev>.
+/// Every command queue is shown as a separate track.
+/// Timestamps are read from the events, hence the commands must be enqueued on a profiling-enabled queue.
+#[derive(Debug, Clone, Default)]
+pub struct TraceRecorder {
+    pub entries: Vec<TraceEntry>,
+}
+
+impl TraceRecorder {
+    pub fn new() -> TraceRecorder {
+        TraceRecorder::default()
+    }
+
+    pub fn record(
+        &mut self,
+        name: impl Into<String>,
+        category: &'static str,
+        queue: usize,
+        args: Vec<(&'static str, String)>,
+        event: Event,
+    ) {
+        self.entries.push(TraceEntry {
+            name: name.into(),
+            category,
+            queue,
+            args,
+            event,
+        });
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Serializes the recorded commands as Chrome Trace Event JSON.
+    /// Waits for all recorded commands to complete.
+    /// Timestamps are in microseconds, relative to the first queued command.
+    pub fn to_json(&self) -> Result<String, Error> {
+        let mut timelines = Vec::with_capacity(self.entries.len());
+        for entry in &self.entries {
+            entry.event.clone().wait()?;
+            timelines.push(entry.event.profiling()?);
+        }
+
+        let origin = timelines
+            .iter()
+            .map(|profiling| profiling.queued)
+            .min()
+            .unwrap_or(0);
+
+        let mut queues = Vec::new();
+        let mut json = String::from("{\"traceEvents\":[");
+
+        for (idx, (entry, profiling)) in self.entries.iter().zip(&timelines).enumerate() {
+            let tid = match queues.iter().position(|queue| *queue == entry.queue) {
+                Some(tid) => tid,
+                None => {
+                    queues.push(entry.queue);
+                    queues.len() - 1
+                }
+            };
+
+            if idx > 0 {
+                json.push(',');
+            }
+            write!(
+                json,
+                "{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"pid\":0,\"tid\":{tid},\"ts\":{:.3},\"dur\":{:.3},\"args\":{{",
+                escape(&entry.name),
+                entry.category,
+                (profiling.start.saturating_sub(origin)) as f64 / 1000.,
+                profiling.end.saturating_sub(profiling.start) as f64 / 1000.,
+            )?;
+            for (key, value) in &entry.args {
+                write!(json, "\"{key}\":\"{}\",", escape(value))?;
+            }
+            write!(
+                json,
+                "\"queued\":{:.3},\"submit\":{:.3}}}}}",
+                (profiling.queued.saturating_sub(origin)) as f64 / 1000.,
+                (profiling.submit.saturating_sub(origin)) as f64 / 1000.,
+            )?;
+        }
+
+        for (tid, queue) in queues.iter().enumerate() {
+            if !self.entries.is_empty() || tid > 0 {
+                json.push(',');
+            }
+            write!(
+                json,
+                "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":0,\"tid\":{tid},\"args\":{{\"name\":\"queue {queue:#x}\"}}}}"
+            )?;
+        }
+
+        json.push_str("],\"displayTimeUnit\":\"ns\"}");
+        Ok(json)
+    }
+
+    /// Writes the recorded commands as Chrome Trace Event JSON to `path`.
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        std::fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        api::{build_program, create_kernel, create_program_with_source, mock, OCLErrorKind},
+        Buffer, CLDevice, Error,
+    };
+
+    #[test]
+    fn test_trace() -> Result<(), Error> {
+        mock::install()?;
+        let mut device = CLDevice::new(0)?;
+        device.start_trace()?;
+
+        let mut buf = Buffer::<f32>::new(&device, 8)?;
+        let mut copy = Buffer::<f32>::new(&device, 8)?;
+        device.write(&mut buf, &[1.; 8])?;
+        device.copy_from(&mut copy, &buf)?;
+
+        let src = "__kernel void trace_add(__global float* x) {}";
+        let program = unsafe { create_program_with_source(&device.ctx, src)? };
+        unsafe { build_program(&program, &[device.device], None)? };
+        let kernel = create_kernel(&program, "trace_add")?;
+        kernel.set_args((&buf,))?;
+        device.enqueue_nd_range_kernel(&kernel, 1, &[8, 1, 1], None, None)?;
+        // the kernel is enqueued even if its name cannot be traced
+        mock::fail_next("clGetKernelInfo", OCLErrorKind::InvalidKernel);
+        device.enqueue_nd_range_kernel(&kernel, 1, &[8, 1, 1], None, None)?;
+        device.read(&copy)?;
+
+        let trace = device.stop_trace().unwrap();
+        assert_eq!(trace.len(), 5);
+
+        let json = trace.to_json()?;
+        assert!(json.starts_with("{\"traceEvents\":["));
+        assert!(json.contains("\"name\":\"write_buffer\""));
+        assert!(json.contains("\"name\":\"copy_buffer\""));
+        assert!(json.contains("\"name\":\"trace_add\",\"cat\":\"kernel\""));
+        assert!(json.contains("\"global_work_size\":\"[8]\""));
+        assert!(json.contains("\"bytes\":\"32\""));
+        assert!(json.contains("\"name\":\"thread_name\""));
+        assert!(device.stop_trace().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(super::escape("a\"b\\c\n"), "a\\\"b\\\\c\\n");
+    }
+}